@@ -0,0 +1,105 @@
+//! Measures the time it takes to read typical buffer sizes from many streams at once.
+//!
+//! Every buffer size is measured twice, with blocks that are as long as the buffer. When
+//! the streams start at frame 0, every read lies within one block and borrows it. When
+//! they start half a block in, every read spans two blocks and is copied instead.
+//!
+//! Run with `cargo bench -p creek-core --bench read`.
+
+use std::time::{Duration, Instant};
+
+use creek_core::memory::{MemoryDecoderOpts, MemorySource};
+use creek_core::read::{ReadStreamOptions, SeekMode};
+use creek_core::{MemoryDecoder, ReadDiskStream};
+
+const NUM_STREAMS: usize = 64;
+const NUM_CHANNELS: usize = 2;
+const NUM_FRAMES: usize = 44100 * 10;
+const NUM_LOOK_AHEAD_BLOCKS: usize = 16;
+const BUFFER_SIZES: [usize; 4] = [64, 128, 256, 512];
+
+fn main() {
+    let channels: Vec<Vec<f32>> = (0..NUM_CHANNELS)
+        .map(|ch| {
+            (0..NUM_FRAMES)
+                .map(|i| ((i + ch) % 1000) as f32 / 1000.0)
+                .collect()
+        })
+        .collect();
+    let source = MemorySource::from(channels);
+
+    // Sum a few samples of every read so that the reads are not optimized away.
+    let mut checksum = 0.0;
+
+    for &buffer_size in BUFFER_SIZES.iter() {
+        let borrow = bench_reads(&source, buffer_size, 0, &mut checksum);
+        let copy = bench_reads(&source, buffer_size, buffer_size / 2, &mut checksum);
+
+        println!(
+            "{} streams, {:>3} frames per read: borrow {:>8.1} ns per read ({:>5.2} ns per frame), copy {:>8.1} ns per read ({:>5.2} ns per frame)",
+            NUM_STREAMS,
+            buffer_size,
+            borrow,
+            borrow / buffer_size as f64,
+            copy,
+            copy / buffer_size as f64,
+        );
+    }
+
+    println!("checksum: {}", checksum);
+}
+
+/// Returns the average time of a read in nanoseconds, with blocks of `buffer_size` frames
+/// and every stream starting at `start_frame`.
+fn bench_reads(
+    source: &MemorySource<f32>,
+    buffer_size: usize,
+    start_frame: usize,
+    checksum: &mut f32,
+) -> f64 {
+    let mut streams: Vec<ReadDiskStream<MemoryDecoder<f32>>> = (0..NUM_STREAMS)
+        .map(|_| {
+            let opts = ReadStreamOptions::<MemoryDecoder<f32>> {
+                block_size: buffer_size,
+                num_look_ahead_blocks: NUM_LOOK_AHEAD_BLOCKS,
+                additional_opts: MemoryDecoderOpts {
+                    source: source.clone(),
+                    sample_rate: Some(44100),
+                },
+                ..Default::default()
+            };
+            ReadDiskStream::new("memory", 0, opts).unwrap()
+        })
+        .collect();
+
+    for stream in streams.iter_mut() {
+        stream.seek(start_frame, SeekMode::Auto).unwrap();
+        stream.block_until_ready().unwrap();
+    }
+
+    let num_rounds = ((NUM_FRAMES - start_frame) / buffer_size) - 1;
+    let mut elapsed = Duration::ZERO;
+    let mut num_reads = 0;
+
+    for _ in 0..num_rounds {
+        // Only the reads are timed, not the wait for the IO servers.
+        for stream in streams.iter_mut() {
+            while !stream.is_ready().unwrap() {
+                std::thread::sleep(Duration::from_micros(100));
+            }
+        }
+
+        let start = Instant::now();
+        for stream in streams.iter_mut() {
+            let data = stream.read(buffer_size).unwrap();
+            for ch in 0..data.num_channels() {
+                let samples = data.read_channel(ch);
+                *checksum += samples[0] + samples[samples.len() - 1];
+            }
+        }
+        elapsed += start.elapsed();
+        num_reads += NUM_STREAMS;
+    }
+
+    elapsed.as_nanos() as f64 / num_reads as f64
+}
@@ -0,0 +1,177 @@
+use std::ops::Range;
+
+/// How the samples of a block are laid out in memory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlockLayout {
+    /// The channels are stored one after another, so each channel can be accessed as a
+    /// single slice.
+    #[default]
+    Planar,
+    /// The samples of each frame are stored next to each other, in the order the
+    /// channels appear in a file.
+    Interleaved,
+}
+
+/// The samples of a block, stored in a single allocation that holds every channel.
+///
+/// The allocation is made once, and then never grows or shrinks.
+pub(crate) struct SampleBuffer<T: Copy + Clone + Default + Send> {
+    samples: Vec<T>,
+    num_channels: usize,
+    block_size: usize,
+    num_frames: usize,
+    layout: BlockLayout,
+}
+
+impl<T: Copy + Clone + Default + Send> SampleBuffer<T> {
+    pub fn new(num_channels: usize, block_size: usize, layout: BlockLayout) -> Self {
+        Self {
+            samples: vec![T::default(); num_channels * block_size],
+            num_channels,
+            block_size,
+            num_frames: 0,
+            layout,
+        }
+    }
+
+    pub fn layout(&self) -> BlockLayout {
+        self.layout
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.num_channels
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    pub fn set_num_frames(&mut self, num_frames: usize) {
+        assert!(
+            num_frames <= self.block_size,
+            "{} frames do not fit into a block of {} frames",
+            num_frames,
+            self.block_size
+        );
+
+        self.num_frames = num_frames;
+    }
+
+    pub fn clear(&mut self) {
+        self.num_frames = 0;
+    }
+
+    /// Write to all of the memory of the buffer once, so that the OS maps it in now
+    /// instead of when the buffer is first filled.
+    pub fn fault_in(&mut self) {
+        self.samples.fill(T::default());
+    }
+
+    pub fn channel(&self, channel: usize) -> &[T] {
+        self.assert_planar();
+
+        let start = channel * self.block_size;
+        &self.samples[start..start + self.num_frames]
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [T] {
+        self.assert_planar();
+
+        let start = channel * self.block_size;
+        &mut self.samples[start..start + self.num_frames]
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &[T]> {
+        self.assert_planar();
+
+        let num_frames = self.num_frames;
+        self.samples
+            .chunks(self.block_size.max(1))
+            .map(move |ch| &ch[..num_frames])
+    }
+
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.assert_planar();
+
+        let num_frames = self.num_frames;
+        self.samples
+            .chunks_mut(self.block_size.max(1))
+            .map(move |ch| &mut ch[..num_frames])
+    }
+
+    /// Add `num_frames` frames to the end of the buffer, and return the slices of each
+    /// channel that hold them.
+    pub fn append_frames(&mut self, num_frames: usize) -> impl Iterator<Item = &mut [T]> {
+        self.assert_planar();
+
+        let range = self.append_range(num_frames);
+        self.samples
+            .chunks_mut(self.block_size.max(1))
+            .map(move |ch| &mut ch[range.clone()])
+    }
+
+    /// Copy the given frames of every channel in `channels` to the end of the buffer.
+    pub fn append_from_channels<C: AsRef<[T]>>(&mut self, channels: &[C], frames: Range<usize>) {
+        let range = self.append_range(frames.len());
+
+        match self.layout {
+            BlockLayout::Planar => {
+                for (ch, src_ch) in self
+                    .samples
+                    .chunks_mut(self.block_size.max(1))
+                    .zip(channels.iter())
+                {
+                    ch[range.clone()].copy_from_slice(&src_ch.as_ref()[frames.clone()]);
+                }
+            }
+            BlockLayout::Interleaved => {
+                let num_channels = self.num_channels;
+                let samples =
+                    &mut self.samples[range.start * num_channels..range.end * num_channels];
+
+                for (ch_i, src_ch) in channels.iter().enumerate() {
+                    for (dst, src) in samples[ch_i..]
+                        .iter_mut()
+                        .step_by(num_channels)
+                        .zip(&src_ch.as_ref()[frames.clone()])
+                    {
+                        *dst = *src;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn interleaved(&self) -> &[T] {
+        self.assert_interleaved();
+
+        &self.samples[..self.num_frames * self.num_channels]
+    }
+
+    fn append_range(&mut self, num_frames: usize) -> Range<usize> {
+        let start = self.num_frames;
+        self.set_num_frames(start + num_frames);
+
+        start..start + num_frames
+    }
+
+    fn assert_planar(&self) {
+        assert_eq!(
+            self.layout,
+            BlockLayout::Planar,
+            "the channels of an interleaved block cannot be accessed as slices"
+        );
+    }
+
+    fn assert_interleaved(&self) {
+        assert_eq!(
+            self.layout,
+            BlockLayout::Interleaved,
+            "a planar block cannot be accessed as interleaved samples"
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Builders that derive the buffer sizes of streams from a latency budget instead of
+//! raw block counts.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::read::{Decoder, ReadStreamOptions};
+use crate::write::{Encoder, WriteStreamOptions};
+
+/// An error in the options given to a [`ReadStreamOptionsBuilder`] or a
+/// [`WriteStreamOptionsBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamOptionsError {
+    /// The sample rate is `0`.
+    ZeroSampleRate,
+    /// The number of channels is `0`.
+    ZeroChannels,
+    /// The block size is `0`.
+    ZeroBlockSize,
+    /// A duration is negative, NaN, or infinite.
+    InvalidDuration { secs: f64 },
+    /// The decode speed is not a positive number.
+    InvalidDecodeSpeed { speed: f64 },
+    /// The buffer is too short to cover the worst-case latency. The buffer must hold at
+    /// least one block more than the latency, since the block that is currently being
+    /// played or recorded cannot be refilled or written yet.
+    BufferTooShort {
+        buffer_secs: f64,
+        min_buffer_secs: f64,
+    },
+}
+
+impl Error for StreamOptionsError {}
+
+impl fmt::Display for StreamOptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamOptionsError::ZeroSampleRate => write!(f, "The sample rate is 0"),
+            StreamOptionsError::ZeroChannels => write!(f, "The number of channels is 0"),
+            StreamOptionsError::ZeroBlockSize => write!(f, "The block size is 0"),
+            StreamOptionsError::InvalidDuration { secs } => {
+                write!(f, "Invalid duration of {} seconds", secs)
+            }
+            StreamOptionsError::InvalidDecodeSpeed { speed } => {
+                write!(f, "Invalid decode speed of {}", speed)
+            }
+            StreamOptionsError::BufferTooShort {
+                buffer_secs,
+                min_buffer_secs,
+            } => {
+                write!(
+                    f,
+                    "A buffer of {} seconds is too short for the worst-case latency, it must be at least {} seconds",
+                    buffer_secs, min_buffer_secs
+                )
+            }
+        }
+    }
+}
+
+/// The buffering that results from the options built by a [`ReadStreamOptionsBuilder`]
+/// or a [`WriteStreamOptionsBuilder`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferInfo {
+    /// The number of seconds of audio that is buffered ahead of the playhead, or that can
+    /// be recorded while the IO server is stalled. This is the desired buffer rounded up
+    /// to a whole number of blocks.
+    pub buffer_secs: f64,
+
+    /// The most memory in bytes that the stream uses for audio data, once every cache
+    /// is filled.
+    pub memory_bytes: usize,
+
+    /// The worst-case time in seconds that playback has to wait after a seek that misses
+    /// every cache before it can resume. The IO server fills a whole cache and its
+    /// look-ahead before playback resumes, so this is the worst-case latency plus the time
+    /// it takes to decode that many blocks at the decode speed given to the builder.
+    ///
+    /// This is `None` for write streams.
+    pub seek_recovery_secs: Option<f64>,
+}
+
+/// Builds [`ReadStreamOptions`] from the number of seconds to buffer ahead of the playhead
+/// and the worst-case latency of the disk.
+///
+/// Create this with `ReadStreamOptions::builder()`. Any options that are not set by the
+/// builder are left at their defaults, and can be changed on the built options.
+#[derive(Debug, Clone)]
+pub struct ReadStreamOptionsBuilder<D: Decoder> {
+    sample_rate: u32,
+    buffer_secs: f64,
+    worst_case_latency_secs: f64,
+    num_channels: u16,
+    block_size: usize,
+    cache_secs: Option<f64>,
+    num_caches: usize,
+    decode_speed: f64,
+    additional_opts: D::AdditionalOpts,
+}
+
+impl<D: Decoder> ReadStreamOptionsBuilder<D> {
+    pub(crate) fn new(sample_rate: u32, buffer_secs: f64, worst_case_latency_secs: f64) -> Self {
+        Self {
+            sample_rate,
+            buffer_secs,
+            worst_case_latency_secs,
+            num_channels: 2,
+            block_size: D::DEFAULT_BLOCK_SIZE,
+            cache_secs: None,
+            num_caches: 1,
+            decode_speed: 1.0,
+            additional_opts: Default::default(),
+        }
+    }
+
+    /// The number of channels of the file. This is only used to report the memory use.
+    ///
+    /// The default is `2`.
+    pub fn num_channels(mut self, num_channels: u16) -> Self {
+        self.num_channels = num_channels;
+        self
+    }
+
+    /// The number of frames in a prefetch block.
+    ///
+    /// The default is the default block size of the decoder.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// The number of seconds of audio that each cache holds before its look-ahead.
+    ///
+    /// The default is the default number of cache blocks of the decoder.
+    pub fn cache_secs(mut self, cache_secs: f64) -> Self {
+        self.cache_secs = Some(cache_secs);
+        self
+    }
+
+    /// The maximum number of caches that can be active in the stream.
+    ///
+    /// The default is `1`.
+    pub fn num_caches(mut self, num_caches: usize) -> Self {
+        self.num_caches = num_caches;
+        self
+    }
+
+    /// How many times faster than realtime the decoder decodes the file on the target
+    /// machine. This is only used to report the seek recovery time.
+    ///
+    /// The default is `1.0`, which assumes the decoder only just keeps up with playback.
+    pub fn decode_speed(mut self, decode_speed: f64) -> Self {
+        self.decode_speed = decode_speed;
+        self
+    }
+
+    /// Any additional decoder-specific options.
+    pub fn additional_opts(mut self, additional_opts: D::AdditionalOpts) -> Self {
+        self.additional_opts = additional_opts;
+        self
+    }
+
+    /// Build the options, along with info about the resulting buffering.
+    pub fn build(self) -> Result<(ReadStreamOptions<D>, BufferInfo), StreamOptionsError> {
+        let layout = BufferLayout::new(
+            self.sample_rate,
+            self.num_channels,
+            self.block_size,
+            self.buffer_secs,
+            self.worst_case_latency_secs,
+        )?;
+
+        if !self.decode_speed.is_finite() || self.decode_speed <= 0.0 {
+            return Err(StreamOptionsError::InvalidDecodeSpeed {
+                speed: self.decode_speed,
+            });
+        }
+
+        let num_cache_blocks = match self.cache_secs {
+            Some(cache_secs) => layout.num_blocks(cache_secs)?,
+            None => D::DEFAULT_NUM_CACHE_BLOCKS,
+        };
+        let num_prefetch_blocks = num_cache_blocks + layout.num_buffer_blocks;
+
+        // Every cache (including the two temporary ones) and the prefetch buffer hold
+        // the cache blocks and the look-ahead, plus the read and fade buffers.
+        let num_blocks = ((self.num_caches + 3) * num_prefetch_blocks) + 2;
+
+        let info = BufferInfo {
+            buffer_secs: layout.buffer_secs(),
+            memory_bytes: num_blocks * layout.block_bytes::<D::T>(),
+            seek_recovery_secs: Some(
+                self.worst_case_latency_secs
+                    + (layout.secs(num_prefetch_blocks) / self.decode_speed),
+            ),
+        };
+
+        let opts = ReadStreamOptions {
+            num_cache_blocks,
+            num_caches: self.num_caches,
+            additional_opts: self.additional_opts,
+            num_look_ahead_blocks: layout.num_buffer_blocks,
+            block_size: self.block_size,
+            ..Default::default()
+        };
+
+        Ok((opts, info))
+    }
+}
+
+/// Builds [`WriteStreamOptions`] from the number of seconds that can be recorded while
+/// the IO server is stalled and the worst-case latency of the disk.
+///
+/// Create this with `WriteStreamOptions::builder()`. Any options that are not set by the
+/// builder are left at their defaults, and can be changed on the built options.
+#[derive(Debug, Clone)]
+pub struct WriteStreamOptionsBuilder<E: Encoder> {
+    sample_rate: u32,
+    buffer_secs: f64,
+    worst_case_latency_secs: f64,
+    num_channels: u16,
+    block_size: usize,
+    additional_opts: E::AdditionalOpts,
+}
+
+impl<E: Encoder> WriteStreamOptionsBuilder<E> {
+    pub(crate) fn new(sample_rate: u32, buffer_secs: f64, worst_case_latency_secs: f64) -> Self {
+        Self {
+            sample_rate,
+            buffer_secs,
+            worst_case_latency_secs,
+            num_channels: 2,
+            block_size: E::DEFAULT_BLOCK_SIZE,
+            additional_opts: Default::default(),
+        }
+    }
+
+    /// The number of channels of the file. This is only used to report the memory use.
+    ///
+    /// The default is `2`.
+    pub fn num_channels(mut self, num_channels: u16) -> Self {
+        self.num_channels = num_channels;
+        self
+    }
+
+    /// The number of frames in a write block.
+    ///
+    /// The default is the default block size of the encoder.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Any additional encoder-specific options.
+    pub fn additional_opts(mut self, additional_opts: E::AdditionalOpts) -> Self {
+        self.additional_opts = additional_opts;
+        self
+    }
+
+    /// Build the options, along with info about the resulting buffering.
+    pub fn build(self) -> Result<(WriteStreamOptions<E>, BufferInfo), StreamOptionsError> {
+        let layout = BufferLayout::new(
+            self.sample_rate,
+            self.num_channels,
+            self.block_size,
+            self.buffer_secs,
+            self.worst_case_latency_secs,
+        )?;
+
+        let info = BufferInfo {
+            buffer_secs: layout.buffer_secs(),
+            memory_bytes: layout.num_buffer_blocks * layout.block_bytes::<E::T>(),
+            seek_recovery_secs: None,
+        };
+
+        let opts = WriteStreamOptions {
+            additional_opts: self.additional_opts,
+            num_write_blocks: layout.num_buffer_blocks,
+            block_size: self.block_size,
+            server_msg_channel_size: None,
+        };
+
+        Ok((opts, info))
+    }
+}
+
+/// The number of blocks needed to buffer a number of seconds.
+struct BufferLayout {
+    sample_rate: u32,
+    num_channels: u16,
+    block_size: usize,
+    num_buffer_blocks: usize,
+}
+
+impl BufferLayout {
+    fn new(
+        sample_rate: u32,
+        num_channels: u16,
+        block_size: usize,
+        buffer_secs: f64,
+        worst_case_latency_secs: f64,
+    ) -> Result<Self, StreamOptionsError> {
+        if sample_rate == 0 {
+            return Err(StreamOptionsError::ZeroSampleRate);
+        }
+        if num_channels == 0 {
+            return Err(StreamOptionsError::ZeroChannels);
+        }
+        if block_size == 0 {
+            return Err(StreamOptionsError::ZeroBlockSize);
+        }
+
+        let mut layout = Self {
+            sample_rate,
+            num_channels,
+            block_size,
+            num_buffer_blocks: 0,
+        };
+
+        layout.num_buffer_blocks = layout.num_blocks(buffer_secs)?;
+        let num_latency_blocks = layout.num_blocks(worst_case_latency_secs)?;
+
+        if layout.num_buffer_blocks < num_latency_blocks + 1 {
+            return Err(StreamOptionsError::BufferTooShort {
+                buffer_secs,
+                min_buffer_secs: layout.secs(num_latency_blocks + 1),
+            });
+        }
+
+        Ok(layout)
+    }
+
+    /// The number of whole blocks that hold at least the given number of seconds.
+    fn num_blocks(&self, secs: f64) -> Result<usize, StreamOptionsError> {
+        if !secs.is_finite() || secs < 0.0 {
+            return Err(StreamOptionsError::InvalidDuration { secs });
+        }
+
+        // The float to int cast saturates.
+        let frames = (secs * f64::from(self.sample_rate)).ceil() as usize;
+
+        let num_blocks = frames / self.block_size;
+        if num_blocks * self.block_size < frames {
+            Ok(num_blocks + 1)
+        } else {
+            Ok(num_blocks)
+        }
+    }
+
+    fn secs(&self, num_blocks: usize) -> f64 {
+        (num_blocks * self.block_size) as f64 / f64::from(self.sample_rate)
+    }
+
+    fn buffer_secs(&self) -> f64 {
+        self.secs(self.num_buffer_blocks)
+    }
+
+    fn block_bytes<T>(&self) -> usize {
+        usize::from(self.num_channels) * self.block_size * std::mem::size_of::<T>()
+    }
+}
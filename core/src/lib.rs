@@ -7,11 +7,35 @@
 
 use std::time;
 
+mod block;
+pub mod latency;
+pub mod memory;
+mod open;
 pub mod read;
+pub mod stream_file;
 pub mod write;
 
-pub use read::{DataBlock, Decoder, ReadDiskStream, ReadStreamOptions, SeekMode};
-pub use write::{Encoder, WriteBlock, WriteDiskStream, WriteStatus, WriteStreamOptions};
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+#[cfg(test)]
+mod tests;
+
+pub use block::BlockLayout;
+pub use latency::{
+    BufferInfo, ReadStreamOptionsBuilder, StreamOptionsError, WriteStreamOptionsBuilder,
+};
+pub use memory::{MemoryDecoder, MemoryEncoder};
+pub use open::{OpenStream, PendingDiskStream};
+pub use read::{
+    AdaptiveLookAhead, CacheInfo, CacheReadyCallback, DataBlock, DecodeResult, Decoder, FadeSample,
+    HeadCache, MediaSource, MemoryBudget, MultiFileDecoder, PendingReadDiskStream, ReadDiskStream,
+    ReadStreamOptions, SeekFade, SeekMode, SequenceReadStream, SourceProvider,
+};
+pub use stream_file::{FileIoOptions, PageCacheAdvice, StreamFile};
+pub use write::{
+    Encoder, PendingWriteDiskStream, WriteBlock, WriteDiskStream, WriteStatus, WriteStreamOptions,
+};
 
 const SERVER_WAIT_TIME: time::Duration = time::Duration::from_millis(1);
 
@@ -0,0 +1,198 @@
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::{MemoryOpenError, MemorySample};
+use crate::read::decode_into_block;
+use crate::{DataBlock, DecodeResult, Decoder, FileInfo};
+
+/// The samples served by a [`MemoryDecoder`].
+///
+/// The samples are reference-counted, so the same source can be shared between many
+/// streams without copying it.
+#[derive(Clone)]
+pub enum MemorySource<T: MemorySample> {
+    /// De-interleaved samples, one `Vec` per channel.
+    Channels(Arc<[Vec<T>]>),
+    /// Interleaved samples stored as little-endian bytes.
+    InterleavedBytes { bytes: Arc<[u8]>, num_channels: u16 },
+}
+
+impl<T: MemorySample> MemorySource<T> {
+    fn num_channels(&self) -> usize {
+        match self {
+            MemorySource::Channels(channels) => channels.len(),
+            MemorySource::InterleavedBytes { num_channels, .. } => usize::from(*num_channels),
+        }
+    }
+}
+
+impl<T: MemorySample> Default for MemorySource<T> {
+    fn default() -> Self {
+        MemorySource::Channels(Arc::new([]))
+    }
+}
+
+impl<T: MemorySample> std::fmt::Debug for MemorySource<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't print every sample.
+        match self {
+            MemorySource::Channels(channels) => f
+                .debug_struct("Channels")
+                .field("num_channels", &channels.len())
+                .field("num_frames", &channels.first().map(|ch| ch.len()))
+                .finish(),
+            MemorySource::InterleavedBytes {
+                bytes,
+                num_channels,
+            } => f
+                .debug_struct("InterleavedBytes")
+                .field("len", &bytes.len())
+                .field("num_channels", num_channels)
+                .finish(),
+        }
+    }
+}
+
+impl<T: MemorySample> From<Vec<Vec<T>>> for MemorySource<T> {
+    fn from(channels: Vec<Vec<T>>) -> Self {
+        MemorySource::Channels(channels.into())
+    }
+}
+
+/// Options for opening a [`MemoryDecoder`].
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDecoderOpts<T: MemorySample> {
+    /// The samples to serve.
+    pub source: MemorySource<T>,
+    /// The sample rate reported in the `FileInfo` of the stream.
+    pub sample_rate: Option<u32>,
+}
+
+/// A decoder that serves frames from memory.
+///
+/// The samples are given in the `additional_opts` of the stream, and the file path
+/// given to the stream is ignored.
+pub struct MemoryDecoder<T: MemorySample> {
+    source: MemorySource<T>,
+    num_frames: usize,
+    block_size: usize,
+    playhead_frame: usize,
+}
+
+impl<T: MemorySample> Decoder for MemoryDecoder<T> {
+    type T = T;
+    type AdditionalOpts = MemoryDecoderOpts<T>;
+    type FileParams = ();
+    type OpenError = MemoryOpenError;
+    type FatalError = Infallible;
+
+    const DEFAULT_BLOCK_SIZE: usize = 16384;
+    const DEFAULT_NUM_CACHE_BLOCKS: usize = 0;
+    // Reading from memory never stalls, so very little look-ahead is needed.
+    const DEFAULT_NUM_LOOK_AHEAD_BLOCKS: usize = 2;
+
+    fn new(
+        _file: PathBuf,
+        start_frame: usize,
+        block_size: usize,
+        additional_opts: Self::AdditionalOpts,
+    ) -> Result<(Self, FileInfo<Self::FileParams>), Self::OpenError> {
+        let MemoryDecoderOpts {
+            source,
+            sample_rate,
+        } = additional_opts;
+
+        let num_channels = source.num_channels();
+        if num_channels == 0 {
+            return Err(MemoryOpenError::NoChannels);
+        }
+
+        let num_frames = match &source {
+            MemorySource::Channels(channels) => {
+                let num_frames = channels[0].len();
+                if channels.iter().any(|ch| ch.len() != num_frames) {
+                    return Err(MemoryOpenError::MismatchedChannelLengths);
+                }
+                num_frames
+            }
+            MemorySource::InterleavedBytes { bytes, .. } => {
+                let bytes_per_frame = num_channels * T::NUM_BYTES;
+                if bytes.len() % bytes_per_frame != 0 {
+                    return Err(MemoryOpenError::InvalidByteLength {
+                        len: bytes.len(),
+                        bytes_per_frame,
+                    });
+                }
+                bytes.len() / bytes_per_frame
+            }
+        };
+
+        Ok((
+            Self {
+                source,
+                num_frames,
+                block_size,
+                playhead_frame: start_frame.min(num_frames),
+            },
+            FileInfo {
+                num_frames,
+                num_channels: num_channels as u16,
+                sample_rate,
+                params: (),
+            },
+        ))
+    }
+
+    fn seek(&mut self, frame: usize) -> Result<(), Self::FatalError> {
+        self.playhead_frame = frame.min(self.num_frames);
+        Ok(())
+    }
+
+    fn decode(&mut self, data_block: &mut DataBlock<Self::T>) -> Result<(), Self::FatalError> {
+        decode_into_block(self, data_block).map(|_| ())
+    }
+
+    fn decode_into(
+        &mut self,
+        channels: &mut [&mut [Self::T]],
+    ) -> Result<DecodeResult, Self::FatalError> {
+        let start = self.playhead_frame;
+        let end = (start + self.block_size).min(self.num_frames);
+        let frames = end - start;
+
+        match &self.source {
+            MemorySource::Channels(src_channels) => {
+                for (dst_ch, src_ch) in channels.iter_mut().zip(src_channels.iter()) {
+                    dst_ch[..frames].copy_from_slice(&src_ch[start..end]);
+                }
+            }
+            MemorySource::InterleavedBytes { bytes, .. } => {
+                let num_channels = channels.len();
+                let bytes_per_frame = num_channels * T::NUM_BYTES;
+                let bytes = &bytes[start * bytes_per_frame..end * bytes_per_frame];
+
+                for (ch_i, dst_ch) in channels.iter_mut().enumerate() {
+                    let samples = bytes
+                        .chunks_exact(T::NUM_BYTES)
+                        .skip(ch_i)
+                        .step_by(num_channels);
+                    for (dst, sample) in dst_ch.iter_mut().zip(samples) {
+                        *dst = T::from_le_bytes(sample);
+                    }
+                }
+            }
+        }
+
+        self.playhead_frame = end;
+
+        Ok(DecodeResult {
+            frames,
+            reached_end_of_file: end == self.num_frames,
+        })
+    }
+
+    fn current_frame(&self) -> usize {
+        self.playhead_frame
+    }
+}
@@ -0,0 +1,219 @@
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::MemorySample;
+use crate::{Encoder, FileInfo, WriteBlock, WriteStatus};
+
+struct SinkState<T: MemorySample> {
+    files: Vec<Vec<Vec<T>>>,
+    finished: bool,
+}
+
+/// Collects the frames written by a [`MemoryEncoder`].
+///
+/// This is a handle to shared data. Keep a clone of it before passing it to the write
+/// stream to read back the written frames.
+pub struct MemorySink<T: MemorySample> {
+    state: Arc<Mutex<SinkState<T>>>,
+}
+
+impl<T: MemorySample> MemorySink<T> {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SinkState {
+                files: Vec::new(),
+                finished: false,
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SinkState<T>> {
+        // The state is always valid, so it is fine to ignore poisoning.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Return a copy of the frames written to each file, one `Vec` per channel.
+    ///
+    /// There is more than one file if `MemoryEncoderOpts::max_frames_per_file` was
+    /// reached, and there are no files if the stream was discarded.
+    pub fn files(&self) -> Vec<Vec<Vec<T>>> {
+        self.lock().files.clone()
+    }
+
+    /// Return the number of files that have been written to.
+    pub fn num_files(&self) -> usize {
+        self.lock().files.len()
+    }
+
+    /// Return `true` if the file has been finished.
+    pub fn is_finished(&self) -> bool {
+        self.lock().finished
+    }
+}
+
+impl<T: MemorySample> Default for MemorySink<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: MemorySample> Clone for MemorySink<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+impl<T: MemorySample> std::fmt::Debug for MemorySink<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.lock();
+        f.debug_struct("MemorySink")
+            .field("num_files", &state.files.len())
+            .field("finished", &state.finished)
+            .finish()
+    }
+}
+
+/// Options for creating a [`MemoryEncoder`].
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEncoderOpts<T: MemorySample> {
+    /// Where the written frames are collected.
+    pub sink: MemorySink<T>,
+    /// The maximum number of frames in a single file. Once a file is about to reach
+    /// this size, a new file is started and `WriteStatus::ReachedMaxSize` is returned.
+    ///
+    /// Set this to `None` to never start a new file.
+    pub max_frames_per_file: Option<usize>,
+}
+
+/// An encoder that collects written frames in memory.
+///
+/// The frames are collected in the [`MemorySink`] given in the `additional_opts` of the
+/// stream, and the file path given to the stream is ignored.
+pub struct MemoryEncoder<T: MemorySample> {
+    sink: MemorySink<T>,
+    num_channels: usize,
+    block_size: usize,
+    max_frames_per_file: Option<usize>,
+    active: bool,
+}
+
+impl<T: MemorySample> MemoryEncoder<T> {
+    fn empty_file(&self) -> Vec<Vec<T>> {
+        (0..self.num_channels).map(|_| Vec::new()).collect()
+    }
+}
+
+impl<T: MemorySample> Encoder for MemoryEncoder<T> {
+    type T = T;
+    type AdditionalOpts = MemoryEncoderOpts<T>;
+    type FileParams = ();
+    type OpenError = Infallible;
+    type FatalError = Infallible;
+
+    const DEFAULT_BLOCK_SIZE: usize = 32768;
+    const DEFAULT_NUM_WRITE_BLOCKS: usize = 8;
+
+    fn new(
+        _file: PathBuf,
+        num_channels: u16,
+        sample_rate: u32,
+        block_size: usize,
+        _num_write_blocks: usize,
+        additional_opts: Self::AdditionalOpts,
+    ) -> Result<(Self, FileInfo<Self::FileParams>), Self::OpenError> {
+        let MemoryEncoderOpts {
+            sink,
+            max_frames_per_file,
+        } = additional_opts;
+
+        let encoder = Self {
+            sink,
+            num_channels: usize::from(num_channels),
+            block_size,
+            max_frames_per_file,
+            active: true,
+        };
+
+        {
+            let mut state = encoder.sink.lock();
+            state.files = vec![encoder.empty_file()];
+            state.finished = false;
+        }
+
+        Ok((
+            encoder,
+            FileInfo {
+                num_frames: 0,
+                num_channels,
+                sample_rate: Some(sample_rate),
+                params: (),
+            },
+        ))
+    }
+
+    fn encode(
+        &mut self,
+        write_block: &WriteBlock<Self::T>,
+    ) -> Result<WriteStatus, Self::FatalError> {
+        let written_frames = write_block.written_frames();
+        if !self.active || written_frames == 0 {
+            return Ok(WriteStatus::Ok);
+        }
+
+        let new_file = self.empty_file();
+        let mut state = self.sink.lock();
+
+        let file_frames = match state.files.last_mut() {
+            Some(file) => {
+                for (file_ch, block_ch) in file.iter_mut().zip(write_block.channels()) {
+                    file_ch.extend_from_slice(block_ch);
+                }
+                file.first().map_or(0, Vec::len)
+            }
+            None => return Ok(WriteStatus::Ok),
+        };
+
+        if let Some(max_frames) = self.max_frames_per_file {
+            // Start a new file if the next block would not fit.
+            if file_frames + self.block_size > max_frames {
+                state.files.push(new_file);
+
+                return Ok(WriteStatus::ReachedMaxSize {
+                    num_files: state.files.len() as u32,
+                });
+            }
+        }
+
+        Ok(WriteStatus::Ok)
+    }
+
+    fn finish_file(&mut self) -> Result<(), Self::FatalError> {
+        if self.active {
+            self.active = false;
+            self.sink.lock().finished = true;
+        }
+
+        Ok(())
+    }
+
+    fn discard_file(&mut self) -> Result<(), Self::FatalError> {
+        if self.active {
+            self.active = false;
+            self.sink.lock().files.clear();
+        }
+
+        Ok(())
+    }
+
+    fn discard_and_restart(&mut self) -> Result<(), Self::FatalError> {
+        if self.active {
+            let new_file = self.empty_file();
+            self.sink.lock().files = vec![new_file];
+        }
+
+        Ok(())
+    }
+}
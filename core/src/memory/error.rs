@@ -0,0 +1,36 @@
+/// An error opening a [`MemoryDecoder`].
+///
+/// [`MemoryDecoder`]: super::MemoryDecoder
+#[derive(Debug)]
+pub enum MemoryOpenError {
+    /// The source does not contain any channels.
+    NoChannels,
+    /// The channels in the source do not all have the same number of frames.
+    MismatchedChannelLengths,
+    /// The number of bytes in the source is not a multiple of the number of bytes
+    /// in a single frame.
+    InvalidByteLength { len: usize, bytes_per_frame: usize },
+}
+
+impl std::error::Error for MemoryOpenError {}
+
+impl std::fmt::Display for MemoryOpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MemoryOpenError::NoChannels => write!(f, "The source does not contain any channels"),
+            MemoryOpenError::MismatchedChannelLengths => {
+                write!(f, "The channels in the source have different lengths")
+            }
+            MemoryOpenError::InvalidByteLength {
+                len,
+                bytes_per_frame,
+            } => {
+                write!(
+                    f,
+                    "The source length of {} bytes is not a multiple of the frame size of {} bytes",
+                    len, bytes_per_frame
+                )
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! A decoder and an encoder that read from and write to memory instead of files.
+//!
+//! These can be used to stream small assets that are embedded in the binary, or to
+//! test code that uses [`ReadDiskStream`] and [`WriteDiskStream`] without any disk IO.
+//!
+//! [`ReadDiskStream`]: crate::ReadDiskStream
+//! [`WriteDiskStream`]: crate::WriteDiskStream
+
+mod decoder;
+mod encoder;
+
+pub mod error;
+
+#[cfg(test)]
+mod tests;
+
+pub use decoder::{MemoryDecoder, MemoryDecoderOpts, MemorySource};
+pub use encoder::{MemoryEncoder, MemoryEncoderOpts, MemorySink};
+pub use error::MemoryOpenError;
+
+/// A sample type that can be served from memory.
+pub trait MemorySample: Copy + Clone + Default + Send + Sync + std::fmt::Debug + 'static {
+    /// The number of bytes in a single sample.
+    const NUM_BYTES: usize;
+
+    /// Read a sample from `NUM_BYTES` little-endian bytes.
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_memory_sample {
+    ($($t:ty),*) => {
+        $(
+            impl MemorySample for $t {
+                const NUM_BYTES: usize = std::mem::size_of::<$t>();
+
+                fn from_le_bytes(bytes: &[u8]) -> Self {
+                    let mut buf = [0; std::mem::size_of::<$t>()];
+                    buf.copy_from_slice(bytes);
+                    <$t>::from_le_bytes(buf)
+                }
+            }
+        )*
+    };
+}
+
+impl_memory_sample!(u8, i8, u16, i16, u32, i32, f32, f64);
@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use super::*;
+use crate::{testing, Decoder, Encoder, WriteBlock, WriteDiskStream, WriteStreamOptions};
+
+fn ramp_frames(num_channels: usize, num_frames: usize) -> Vec<Vec<f32>> {
+    (0..num_channels)
+        .map(|ch| {
+            (0..num_frames)
+                .map(|i| (i as f32 * 0.001) + ch as f32)
+                .collect()
+        })
+        .collect()
+}
+
+fn interleaved_bytes(frames: &[Vec<f32>]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for i in 0..frames[0].len() {
+        for ch in frames.iter() {
+            bytes.extend_from_slice(&ch[i].to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[test]
+fn decoder_conformance() {
+    let file = Path::new("memory");
+    let block_size = 1000;
+
+    // More than 64 channels are passed to `decode_into()` in an allocated list.
+    for (num_channels, num_frames) in [
+        (1, 1),
+        (1, 999),
+        (2, 1000),
+        (2, 5432),
+        (3, 10001),
+        (70, 2500),
+    ] {
+        let frames = ramp_frames(num_channels, num_frames);
+
+        let channels = MemorySource::from(frames.clone());
+        let opts = || MemoryDecoderOpts {
+            source: channels.clone(),
+            sample_rate: Some(44100),
+        };
+        let (file_info, linear) =
+            testing::check_decoder_with_opts::<MemoryDecoder<f32>>(file, block_size, opts);
+        assert_eq!(file_info.sample_rate, Some(44100));
+        assert_eq!(linear, frames);
+        testing::check_read_stream_with_opts::<MemoryDecoder<f32>>(
+            file,
+            block_size,
+            &linear,
+            opts(),
+        );
+
+        let bytes = MemorySource::InterleavedBytes {
+            bytes: interleaved_bytes(&frames).into(),
+            num_channels: num_channels as u16,
+        };
+        let opts = || MemoryDecoderOpts {
+            source: bytes.clone(),
+            sample_rate: None,
+        };
+        let (_, linear) =
+            testing::check_decoder_with_opts::<MemoryDecoder<f32>>(file, block_size, opts);
+        assert_eq!(linear, frames);
+    }
+}
+
+#[test]
+fn decoder_open_errors() {
+    let open = |source| {
+        MemoryDecoder::<i16>::new(
+            "memory".into(),
+            0,
+            1024,
+            MemoryDecoderOpts {
+                source,
+                sample_rate: None,
+            },
+        )
+    };
+
+    assert!(matches!(
+        open(MemorySource::default()),
+        Err(MemoryOpenError::NoChannels)
+    ));
+    assert!(matches!(
+        open(vec![vec![0; 4], vec![0; 3]].into()),
+        Err(MemoryOpenError::MismatchedChannelLengths)
+    ));
+    assert!(matches!(
+        open(MemorySource::InterleavedBytes {
+            bytes: vec![0; 6].into(),
+            num_channels: 2,
+        }),
+        Err(MemoryOpenError::InvalidByteLength {
+            len: 6,
+            bytes_per_frame: 4,
+        })
+    ));
+}
+
+fn write_to_sink(
+    frames: &[Vec<f32>],
+    block_size: usize,
+    max_frames_per_file: Option<usize>,
+) -> (MemorySink<f32>, u32) {
+    let sink = MemorySink::new();
+
+    let mut write_stream = WriteDiskStream::<MemoryEncoder<f32>>::new(
+        "memory",
+        frames.len() as u16,
+        44100,
+        WriteStreamOptions {
+            additional_opts: MemoryEncoderOpts {
+                sink: sink.clone(),
+                max_frames_per_file,
+            },
+            block_size,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    for frame in (0..frames[0].len()).step_by(block_size / 2) {
+        let end = (frame + (block_size / 2)).min(frames[0].len());
+        let buffer: Vec<&[f32]> = frames.iter().map(|ch| &ch[frame..end]).collect();
+
+        write_stream.block_until_ready().unwrap();
+        write_stream.write(&buffer).unwrap();
+    }
+
+    write_stream.finish_and_close().unwrap();
+    while !write_stream.finish_complete() {
+        write_stream.poll().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    assert!(sink.is_finished());
+
+    (sink, write_stream.num_files())
+}
+
+#[test]
+fn encoder_round_trip() {
+    let block_size = 1000;
+    let frames = ramp_frames(2, 4567);
+
+    let (sink, _) = write_to_sink(&frames, block_size, None);
+    let files = sink.files();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0], frames);
+
+    let opts = || MemoryDecoderOpts {
+        source: files[0].clone().into(),
+        sample_rate: None,
+    };
+    let (_, linear) = testing::check_decoder_with_opts::<MemoryDecoder<f32>>(
+        Path::new("memory"),
+        block_size,
+        opts,
+    );
+    assert_eq!(linear, frames);
+}
+
+#[test]
+fn encoder_max_frames_per_file() {
+    let block_size = 1000;
+    let frames = ramp_frames(1, 4500);
+
+    // A new file is started once the next block would not fit into 2500 frames.
+    let (sink, num_files) = write_to_sink(&frames, block_size, Some(2500));
+    assert_eq!(num_files, 3);
+    let files = sink.files();
+    assert_eq!(files.len(), 3);
+    assert_eq!(files[0][0], frames[0][0..2000]);
+    assert_eq!(files[1][0], frames[0][2000..4000]);
+    assert_eq!(files[2][0], frames[0][4000..4500]);
+}
+
+#[test]
+fn encoder_discard() {
+    let sink = MemorySink::<f32>::new();
+    let (mut encoder, _) = MemoryEncoder::<f32>::new(
+        "memory".into(),
+        1,
+        44100,
+        4,
+        2,
+        MemoryEncoderOpts {
+            sink: sink.clone(),
+            max_frames_per_file: None,
+        },
+    )
+    .unwrap();
+
+    let mut block = WriteBlock::new(1, 4);
+    block.append_from_channels(&[[1.0, 2.0]], 0..2);
+
+    encoder.encode(&block).unwrap();
+    assert_eq!(sink.files(), vec![vec![vec![1.0, 2.0]]]);
+
+    encoder.discard_and_restart().unwrap();
+    assert_eq!(sink.files(), vec![vec![Vec::<f32>::new()]]);
+
+    encoder.encode(&block).unwrap();
+    encoder.discard_file().unwrap();
+    assert_eq!(sink.num_files(), 0);
+    assert!(!sink.is_finished());
+
+    // Nothing is written after the file has been discarded.
+    encoder.encode(&block).unwrap();
+    assert_eq!(sink.num_files(), 0);
+}
@@ -0,0 +1,193 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::SERVER_WAIT_TIME;
+
+/// Sends the result of opening a file from the IO server thread.
+pub(crate) struct OpenResultTx<R> {
+    tx: Producer<R>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+/// Receives the result of opening a file on the client side.
+pub(crate) struct OpenResultRx<R> {
+    rx: Consumer<R>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+pub(crate) fn open_result_channel<R>() -> (OpenResultTx<R>, OpenResultRx<R>) {
+    let (tx, rx) = RingBuffer::<R>::new(1);
+    let waker = Arc::new(Mutex::new(None));
+
+    (
+        OpenResultTx {
+            tx,
+            waker: Arc::clone(&waker),
+        },
+        OpenResultRx { rx, waker },
+    )
+}
+
+impl<R> OpenResultTx<R> {
+    pub(crate) fn send(mut self, res: R) {
+        // Push cannot fail because only one message is ever sent.
+        let _ = self.tx.push(res);
+
+        // The waker is taken after the result is pushed so that a waker registered
+        // before the client checks for the result is never missed.
+        let waker = self.waker.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<R> OpenResultRx<R> {
+    pub(crate) fn try_recv(&mut self) -> Option<R> {
+        self.rx.pop().ok()
+    }
+
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<R> {
+        // Register the waker before checking for the result so that a result sent in
+        // between is never missed.
+        *self.waker.lock().unwrap_or_else(|e| e.into_inner()) = Some(cx.waker().clone());
+
+        match self.try_recv() {
+            Some(res) => Poll::Ready(res),
+            None => Poll::Pending,
+        }
+    }
+
+    pub(crate) fn recv_blocking(&mut self) -> R {
+        loop {
+            if let Some(res) = self.try_recv() {
+                return res;
+            }
+
+            std::thread::sleep(SERVER_WAIT_TIME);
+        }
+    }
+}
+
+/// A stream that is opened on its IO server thread.
+///
+/// This is implemented by [`ReadDiskStream`] and [`WriteDiskStream`], and cannot be
+/// implemented outside of this crate.
+///
+/// [`ReadDiskStream`]: crate::ReadDiskStream
+/// [`WriteDiskStream`]: crate::WriteDiskStream
+pub trait OpenStream: Sized + sealed::Sealed {
+    /// The result of opening the file on the server.
+    type Opened;
+    /// The error type while opening the file.
+    type OpenError;
+    /// The parts of the stream that are created before the file has been opened.
+    type Options;
+
+    /// Create the stream once the file has been opened.
+    fn finish_open(opts: Self::Options, opened: Self::Opened) -> Self;
+
+    /// Tell the server to close once it has opened the file, because the stream will
+    /// never be created.
+    fn cancel_open(opts: Self::Options);
+}
+
+pub(crate) mod sealed {
+    /// Keeps `OpenStream` from being implemented outside of this crate.
+    pub trait Sealed {}
+}
+
+/// A stream that is still being opened on its IO server thread.
+///
+/// Use `PendingDiskStream::poll_open()` to check if the file has been opened without
+/// blocking, or await this as a `Future`.
+pub struct PendingDiskStream<S: OpenStream> {
+    open_rx: OpenResultRx<Result<S::Opened, S::OpenError>>,
+    opts: Option<S::Options>,
+}
+
+impl<S: OpenStream> PendingDiskStream<S> {
+    pub(crate) fn new(
+        open_rx: OpenResultRx<Result<S::Opened, S::OpenError>>,
+        opts: S::Options,
+    ) -> Self {
+        Self {
+            open_rx,
+            opts: Some(opts),
+        }
+    }
+
+    /// Check if the file has been opened.
+    ///
+    /// Returns `None` if the file is still being opened, or the opened stream (or the
+    /// error that occurred while opening the file) otherwise.
+    ///
+    /// This does not block the calling thread.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if called again after the stream has been returned.
+    pub fn poll_open(&mut self) -> Option<Result<S, S::OpenError>> {
+        self.assert_not_returned();
+
+        let res = self.open_rx.try_recv()?;
+        Some(self.finish(res))
+    }
+
+    /// Block the calling thread until the file has been opened.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if called after the stream has already been returned from
+    /// `PendingDiskStream::poll_open()`.
+    pub fn wait(mut self) -> Result<S, S::OpenError> {
+        self.assert_not_returned();
+
+        let res = self.open_rx.recv_blocking();
+        self.finish(res)
+    }
+
+    fn assert_not_returned(&self) {
+        assert!(self.opts.is_some(), "the stream has already been returned");
+    }
+
+    fn finish(&mut self, res: Result<S::Opened, S::OpenError>) -> Result<S, S::OpenError> {
+        let opts = self
+            .opts
+            .take()
+            .expect("the stream has already been returned");
+
+        res.map(|opened| S::finish_open(opts, opened))
+    }
+}
+
+// No fields are ever pinned.
+impl<S: OpenStream> Unpin for PendingDiskStream<S> {}
+
+impl<S: OpenStream> Future for PendingDiskStream<S> {
+    type Output = Result<S, S::OpenError>;
+
+    /// # Panics
+    ///
+    /// This will panic if polled again after the stream has been returned.
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.assert_not_returned();
+
+        match self.open_rx.poll_recv(cx) {
+            Poll::Ready(res) => Poll::Ready(self.finish(res)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<S: OpenStream> Drop for PendingDiskStream<S> {
+    fn drop(&mut self) {
+        if let Some(opts) = self.opts.take() {
+            S::cancel_open(opts);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// A memory budget shared by the prefetch blocks and caches of any number of
+/// [`ReadDiskStream`]s.
+///
+/// Pass a clone of the same budget to every stream with `ReadStreamOptions::memory_budget`
+/// to put an upper limit on the memory used by all of them together. Whenever the IO server
+/// of a stream needs to allocate more memory than the budget allows, the least recently used
+/// caches of any stream sharing this budget are evicted. The memory of an evicted cache is
+/// freed once its stream is no longer playing from it. Seeking to a frame in an evicted cache
+/// is a cache miss, so the stream buffers as if the cache was never filled.
+///
+/// Prefetch blocks are never evicted since a stream cannot play without them. If the budget
+/// is smaller than the prefetch blocks of all streams, then memory is still allocated for
+/// them, and every cache is evicted.
+///
+/// Cloning a budget only clones a reference to the same budget.
+///
+/// [`ReadDiskStream`]: struct.ReadDiskStream.html
+#[derive(Clone)]
+pub struct MemoryBudget {
+    inner: Arc<BudgetInner>,
+}
+
+struct BudgetInner {
+    max_bytes: AtomicUsize,
+    used_bytes: AtomicUsize,
+    /// Used to find the least recently used caches.
+    clock: AtomicU64,
+    caches: Mutex<Vec<Weak<CacheUsage>>>,
+}
+
+impl MemoryBudget {
+    /// Create a new budget of `max_bytes` bytes.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            inner: Arc::new(BudgetInner {
+                max_bytes: AtomicUsize::new(max_bytes),
+                used_bytes: AtomicUsize::new(0),
+                clock: AtomicU64::new(0),
+                caches: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// The maximum number of bytes in this budget.
+    pub fn max_bytes(&self) -> usize {
+        self.inner.max_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Change the maximum number of bytes in this budget.
+    ///
+    /// If the new budget is smaller than the memory currently in use, then caches are
+    /// evicted the next time an IO server allocates memory.
+    pub fn set_max_bytes(&self, max_bytes: usize) {
+        self.inner.max_bytes.store(max_bytes, Ordering::Relaxed);
+    }
+
+    /// The number of bytes currently allocated for prefetch blocks and caches in all
+    /// streams that share this budget.
+    pub fn used_bytes(&self) -> usize {
+        self.inner.used_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Account for the prefetch blocks of an IO server. These are never evicted.
+    pub(crate) fn block_usage(&self) -> BlockUsage {
+        BlockUsage {
+            budget: Arc::clone(&self.inner),
+            bytes: 0,
+        }
+    }
+
+    /// Account for a new cache, evicting the least recently used caches if needed.
+    pub(crate) fn alloc_cache(&self, bytes: usize) -> Arc<CacheUsage> {
+        self.inner.alloc(bytes);
+
+        let usage = Arc::new(CacheUsage {
+            budget: Arc::clone(&self.inner),
+            bytes,
+            last_used: AtomicU64::new(self.inner.tick()),
+            evicted: AtomicBool::new(false),
+        });
+
+        self.inner
+            .caches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::downgrade(&usage));
+
+        usage
+    }
+}
+
+impl fmt::Debug for MemoryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBudget")
+            .field("max_bytes", &self.max_bytes())
+            .field("used_bytes", &self.used_bytes())
+            .finish()
+    }
+}
+
+impl BudgetInner {
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Evict the least recently used caches until `bytes` more bytes fit in the budget,
+    /// and then account for them.
+    fn alloc(&self, bytes: usize) {
+        self.make_room(bytes);
+        self.used_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn make_room(&self, bytes: usize) {
+        let max_bytes = self.max_bytes.load(Ordering::Relaxed);
+        let mut caches = self.caches.lock().unwrap_or_else(|e| e.into_inner());
+
+        caches.retain(|cache| cache.strong_count() > 0);
+
+        let mut live: Vec<Arc<CacheUsage>> = caches.iter().filter_map(Weak::upgrade).collect();
+
+        // Memory of caches that were already evicted will be freed soon.
+        let mut projected_bytes = self.used_bytes.load(Ordering::Relaxed) + bytes;
+        for cache in live.iter().filter(|cache| cache.is_evicted()) {
+            projected_bytes = projected_bytes.saturating_sub(cache.bytes);
+        }
+
+        if projected_bytes <= max_bytes {
+            return;
+        }
+
+        live.retain(|cache| !cache.is_evicted());
+        live.sort_by_key(|cache| cache.last_used.load(Ordering::Relaxed));
+
+        for cache in live {
+            if projected_bytes <= max_bytes {
+                break;
+            }
+
+            cache.evicted.store(true, Ordering::Relaxed);
+            projected_bytes = projected_bytes.saturating_sub(cache.bytes);
+        }
+    }
+}
+
+/// The memory used by a cache. The memory is returned to the budget when this is dropped.
+pub(crate) struct CacheUsage {
+    budget: Arc<BudgetInner>,
+    bytes: usize,
+    last_used: AtomicU64,
+    evicted: AtomicBool,
+}
+
+impl CacheUsage {
+    /// Mark the cache as recently used.
+    ///
+    /// This is realtime-safe.
+    pub(crate) fn touch(&self) {
+        self.last_used.store(self.budget.tick(), Ordering::Relaxed);
+    }
+
+    /// Returns true if the cache should be freed to stay within the budget.
+    ///
+    /// This is realtime-safe.
+    pub(crate) fn is_evicted(&self) -> bool {
+        self.evicted.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for CacheUsage {
+    fn drop(&mut self) {
+        self.budget
+            .used_bytes
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// The memory used by the prefetch blocks of an IO server. The memory is returned to the
+/// budget when this is dropped.
+pub(crate) struct BlockUsage {
+    budget: Arc<BudgetInner>,
+    bytes: usize,
+}
+
+impl BlockUsage {
+    /// Account for another prefetch block.
+    pub(crate) fn add(&mut self, bytes: usize) {
+        self.budget.alloc(bytes);
+        self.bytes += bytes;
+    }
+
+    /// Return the memory of a prefetch block that was freed.
+    pub(crate) fn remove(&mut self, bytes: usize) {
+        self.budget.used_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.bytes -= bytes;
+    }
+}
+
+impl Drop for BlockUsage {
+    fn drop(&mut self) {
+        self.budget
+            .used_bytes
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
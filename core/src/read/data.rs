@@ -1,51 +1,200 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use super::budget::{CacheUsage, MemoryBudget};
+use super::sequence::ItemBoundary;
+use crate::block::{BlockLayout, SampleBuffer};
+
 /// A prefetch data block.
+///
+/// The samples of every channel are stored one after another in a single allocation of
+/// `num_channels * block_size` samples, which is never reallocated. The channels all hold
+/// the same number of frames, which starts at zero and grows up to the block size as the
+/// block is filled.
 pub struct DataBlock<T: Copy + Clone + Default + Send> {
-    pub block: Vec<Vec<T>>,
+    samples: SampleBuffer<T>,
 }
 
 impl<T: Copy + Clone + Default + Send> DataBlock<T> {
     pub fn new(num_channels: usize, block_size: usize) -> Self {
         DataBlock {
-            block: (0..num_channels)
-                .map(|_| Vec::with_capacity(block_size))
-                .collect(),
+            samples: SampleBuffer::new(num_channels, block_size, BlockLayout::Planar),
         }
     }
 
+    /// The number of channels in the block.
+    pub fn num_channels(&self) -> usize {
+        self.samples.num_channels()
+    }
+
+    /// The maximum number of frames the block can hold.
+    pub fn block_size(&self) -> usize {
+        self.samples.block_size()
+    }
+
+    /// The number of frames that have been written to the block.
+    pub fn num_frames(&self) -> usize {
+        self.samples.num_frames()
+    }
+
+    /// Set the number of frames in the block. Any frames that are added hold stale data
+    /// until they are written to.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `num_frames` is greater than the block size.
+    pub fn set_num_frames(&mut self, num_frames: usize) {
+        self.samples.set_num_frames(num_frames);
+    }
+
+    /// Remove every frame from the block. This does not free any memory.
     pub fn clear(&mut self) {
-        for ch in self.block.iter_mut() {
-            ch.clear();
-        }
+        self.samples.clear();
+    }
+
+    /// The frames of the given channel.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `channel` is out of range.
+    pub fn channel(&self, channel: usize) -> &[T] {
+        self.samples.channel(channel)
+    }
+
+    /// The frames of the given channel.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `channel` is out of range.
+    pub fn channel_mut(&mut self, channel: usize) -> &mut [T] {
+        self.samples.channel_mut(channel)
+    }
+
+    /// The frames of every channel.
+    pub fn channels(&self) -> impl Iterator<Item = &[T]> {
+        self.samples.channels()
+    }
+
+    /// The frames of every channel.
+    pub fn channels_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.samples.channels_mut()
+    }
+
+    /// Add `num_frames` frames to the end of the block, and return the slice of each
+    /// channel to write them into. This is how a `Decoder` fills the block.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the frames do not fit into the block.
+    pub fn append_frames(&mut self, num_frames: usize) -> impl Iterator<Item = &mut [T]> {
+        self.samples.append_frames(num_frames)
+    }
+
+    /// Write to all of the memory of the block once, so that the OS maps it in now
+    /// instead of when the block is first filled.
+    pub(crate) fn fault_in(&mut self) {
+        self.samples.fault_in();
     }
 }
 
 pub(crate) struct DataBlockCache<T: Copy + Clone + Default + Send> {
-    pub blocks: Vec<DataBlock<T>>,
+    /// The blocks may be shared with other streams through a `HeadCache`.
+    pub blocks: Arc<[DataBlock<T>]>,
+    /// This is `Some` if the cache counts towards a `MemoryBudget`.
+    pub usage: Option<Arc<CacheUsage>>,
 }
 
 impl<T: Copy + Clone + Default + Send> DataBlockCache<T> {
-    pub(crate) fn new(num_channels: usize, num_prefetch_blocks: usize, block_size: usize) -> Self {
+    pub(crate) fn new(
+        num_channels: usize,
+        num_prefetch_blocks: usize,
+        block_size: usize,
+        budget: Option<&MemoryBudget>,
+    ) -> Self {
+        let usage = budget.map(|budget| {
+            budget.alloc_cache(
+                num_prefetch_blocks * num_channels * block_size * std::mem::size_of::<T>(),
+            )
+        });
+
         Self {
             blocks: (0..num_prefetch_blocks)
                 .map(|_| DataBlock::new(num_channels, block_size))
                 .collect(),
+            usage,
         }
     }
+
+    /// Mark the cache as recently used.
+    ///
+    /// This is realtime-safe.
+    pub(crate) fn touch(&self) {
+        if let Some(usage) = &self.usage {
+            usage.touch();
+        }
+    }
+
+    /// Returns true if the cache was evicted to stay within its memory budget.
+    ///
+    /// This is realtime-safe.
+    pub(crate) fn is_evicted(&self) -> bool {
+        self.usage
+            .as_ref()
+            .map_or(false, |usage| usage.is_evicted())
+    }
+
+    /// Returns true if the blocks are shared with other streams, in which case they
+    /// must not be written to.
+    pub(crate) fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.blocks) > 1
+    }
+
+    /// Returns the blocks to be filled, or `None` if they are shared.
+    pub(crate) fn blocks_mut(&mut self) -> Option<&mut [DataBlock<T>]> {
+        Arc::get_mut(&mut self.blocks)
+    }
 }
 
 pub(crate) struct DataBlockEntry<T: Copy + Clone + Default + Send> {
     pub use_cache_index: Option<usize>,
     pub block: Option<DataBlock<T>>,
     pub wanted_start_frame: usize,
+    /// When the block was requested from the IO server. This is only measured when the
+    /// look-ahead is adaptive.
+    pub requested_at: Option<Instant>,
 }
 
 pub(crate) struct DataBlockCacheEntry<T: Copy + Clone + Default + Send> {
     pub cache: Option<DataBlockCache<T>>,
     pub wanted_start_frame: usize,
+    /// Whether the cache has been requested and not released since.
+    pub requested: bool,
+}
+
+impl<T: Copy + Clone + Default + Send> DataBlockCacheEntry<T> {
+    /// Returns true if the cache can be seeked to. An evicted cache is treated as if it
+    /// was never filled.
+    ///
+    /// This is realtime-safe.
+    pub fn is_usable(&self) -> bool {
+        self.cache
+            .as_ref()
+            .map_or(false, |cache| !cache.is_evicted())
+    }
 }
 
 pub(crate) struct HeapData<T: Copy + Clone + Default + Send> {
     pub read_buffer: DataBlock<T>,
+    /// Holds the frames from before a seek while fading to the new position.
+    pub fade_buffer: DataBlock<T>,
+    /// The window of frames around the scrub position.
+    pub scrub_window: Option<DataBlock<T>>,
+    /// A block to reuse for the next scrub window.
+    pub scrub_spare: Option<DataBlock<T>>,
+    /// The last block that was read up to its end without copying. It is kept alive
+    /// while the data returned by `read` borrows it, and then sent back to the server
+    /// in place of the next block that is played.
+    pub lent_block: Option<DataBlock<T>>,
     pub prefetch_buffer: Vec<DataBlockEntry<T>>,
     pub caches: Vec<DataBlockCacheEntry<T>>,
 }
@@ -53,31 +202,51 @@ pub(crate) struct HeapData<T: Copy + Clone + Default + Send> {
 /// The sample data returned by a `ReadClient`.
 pub struct ReadData<'a, T: Copy + Clone + Default + Send> {
     data: &'a DataBlock<T>,
+    /// The frame in `data` this data starts from.
+    offset: usize,
     len: usize,
     reached_end_of_file: bool,
+    item_boundaries: &'a [ItemBoundary],
 }
 
 impl<'a, T: Copy + Clone + Default + Send> ReadData<'a, T> {
     pub(crate) fn new(data: &'a DataBlock<T>, len: usize, reached_end_of_file: bool) -> Self {
+        Self::from_range(data, 0, len, reached_end_of_file)
+    }
+
+    /// Borrow `len` frames of a block starting from the frame `offset`, without copying.
+    pub(crate) fn from_range(
+        data: &'a DataBlock<T>,
+        offset: usize,
+        len: usize,
+        reached_end_of_file: bool,
+    ) -> Self {
         Self {
             data,
+            offset,
             len,
             reached_end_of_file,
+            item_boundaries: &[],
         }
     }
 
+    pub(crate) fn with_item_boundaries(mut self, item_boundaries: &'a [ItemBoundary]) -> Self {
+        self.item_boundaries = item_boundaries;
+        self
+    }
+
     /// Read a single channel of samples.
     ///
     /// Use `ReadData::num_channels()` to get the number of available channels.
     ///
     /// The length of this data will be equal to `ReadData::num_frames()`.
     pub fn read_channel(&self, channel: usize) -> &[T] {
-        &self.data.block[channel][0..self.len]
+        &self.data.channel(channel)[self.offset..self.offset + self.len]
     }
 
     /// Return the number of channels in this data.
     pub fn num_channels(&self) -> usize {
-        self.data.block.len()
+        self.data.num_channels()
     }
 
     /// Return the number of samples in a single channel of data.
@@ -90,4 +259,14 @@ impl<'a, T: Copy + Clone + Default + Send> ReadData<'a, T> {
     pub fn reached_end_of_file(&self) -> bool {
         self.reached_end_of_file
     }
+
+    /// Return the points in this data where an item in a [`SequenceReadStream`] ended or
+    /// started, in order.
+    ///
+    /// This is always empty for data read from a `ReadDiskStream`.
+    ///
+    /// [`SequenceReadStream`]: super::SequenceReadStream
+    pub fn item_boundaries(&self) -> &[ItemBoundary] {
+        self.item_boundaries
+    }
 }
@@ -1,3 +1,5 @@
+use std::any::Any;
+use std::cell::RefCell;
 use std::path::PathBuf;
 use std::{error::Error, fmt::Debug};
 
@@ -7,7 +9,10 @@ use crate::FileInfo;
 /// A type that decodes a file in a read stream.
 pub trait Decoder: Sized + 'static {
     /// The data type of a single sample. (i.e. `f32`)
-    type T: Copy + Clone + Default + Send;
+    ///
+    /// This must be `Sync` because the blocks of a `HeadCache` are shared between
+    /// streams on different threads.
+    type T: Copy + Clone + Default + Send + Sync;
 
     /// Any additional options for opening a file with this decoder.
     type AdditionalOpts: Send + Default + Debug;
@@ -62,9 +67,133 @@ pub trait Decoder: Sized + 'static {
     /// are left. If the end of the file has already been reached since the last call to
     /// `decode()`, then do nothing.
     ///
-    /// Each channel Vec in `data_block` will have a length of zero.
+    /// The data block will be empty. Use `DataBlock::append_frames()` to add frames to it,
+    /// and write the samples into the slice it returns for each channel.
+    ///
+    /// A decoder that implements `Decoder::decode_into()` can implement this method with
+    /// `decode_into_block()`.
     fn decode(&mut self, data_block: &mut DataBlock<Self::T>) -> Result<(), Self::FatalError>;
 
+    /// Decode data into `channels` starting from your current internal read position.
+    /// This is streaming, meaning the next call should pick up where the previous left off.
+    ///
+    /// There is one slice for every channel in the file, and every slice is exactly
+    /// `block_size` frames long (you should have gotten this value from `Decoder::new()`).
+    /// Write as many frames as fit, or only the frames that are left if the end of the
+    /// file is reached first, and return the number of frames that were written. If the end
+    /// of the file has already been reached, then write nothing and return `0` frames.
+    ///
+    /// The default implementation calls `Decoder::decode()` with a scratch block and
+    /// copies the frames out of it, so new decoders should implement this method directly.
+    /// It also only detects the end of the file once fewer frames than requested are
+    /// decoded.
+    fn decode_into(
+        &mut self,
+        channels: &mut [&mut [Self::T]],
+    ) -> Result<DecodeResult, Self::FatalError> {
+        let requested_frames = channels.first().map_or(0, |ch| ch.len());
+
+        let frames =
+            with_scratch_block::<Self::T, _>(channels.len(), requested_frames, |scratch_block| {
+                self.decode(scratch_block)?;
+
+                for (ch, scratch_ch) in channels.iter_mut().zip(scratch_block.channels()) {
+                    ch[..scratch_ch.len()].copy_from_slice(scratch_ch);
+                }
+
+                Ok(scratch_block.num_frames())
+            })?;
+
+        Ok(DecodeResult {
+            frames,
+            reached_end_of_file: frames < requested_frames,
+        })
+    }
+
     /// Return the current read position.
     fn current_frame(&self) -> usize;
 }
+
+/// The result of a call to `Decoder::decode_into()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeResult {
+    /// The number of frames that were written to the start of every channel.
+    pub frames: usize,
+    /// Whether the end of the file was reached, meaning there are no frames left after
+    /// the ones that were written.
+    pub reached_end_of_file: bool,
+}
+
+/// The number of channels that `decode_into_block()` passes to `Decoder::decode_into()`
+/// without allocating.
+const MAX_STACK_CHANNELS: usize = 64;
+
+/// Clear `data_block` and fill it with `Decoder::decode_into()`.
+///
+/// This is how the read stream decodes every block. It can also be used to implement
+/// `Decoder::decode()` for a decoder that implements `Decoder::decode_into()`.
+///
+/// This does not allocate unless the block has more than 64 channels.
+pub fn decode_into_block<D: Decoder>(
+    decoder: &mut D,
+    data_block: &mut DataBlock<D::T>,
+) -> Result<DecodeResult, D::FatalError> {
+    data_block.clear();
+    let block_size = data_block.block_size();
+    let num_channels = data_block.num_channels();
+
+    let res = if num_channels <= MAX_STACK_CHANNELS {
+        let mut channels: [&mut [D::T]; MAX_STACK_CHANNELS] =
+            std::array::from_fn(|_| Default::default());
+        for (slot, ch) in channels
+            .iter_mut()
+            .zip(data_block.append_frames(block_size))
+        {
+            *slot = ch;
+        }
+
+        decoder.decode_into(&mut channels[..num_channels])
+    } else {
+        // Files with this many channels are rare enough to allocate for.
+        let mut channels: Vec<&mut [D::T]> = data_block.append_frames(block_size).collect();
+        decoder.decode_into(&mut channels)
+    };
+
+    // Only keep the frames that were written.
+    let frames = res.as_ref().map_or(0, |res| res.frames.min(block_size));
+    data_block.set_num_frames(frames);
+
+    res
+}
+
+thread_local! {
+    /// The block used by the default `Decoder::decode_into()`. Every stream decodes on its own
+    /// thread, so this is allocated once per stream instead of once per block.
+    static SCRATCH_BLOCK: RefCell<Option<Box<dyn Any>>> = RefCell::new(None);
+}
+
+/// Call `f` with an empty block taken from `SCRATCH_BLOCK`, allocating a new one if there
+/// is none with the given type and size. The block is put back once `f` returns.
+fn with_scratch_block<T, R>(
+    num_channels: usize,
+    block_size: usize,
+    f: impl FnOnce(&mut DataBlock<T>) -> R,
+) -> R
+where
+    T: Copy + Clone + Default + Send + 'static,
+{
+    // Take the block out instead of borrowing it, so a decoder that calls another decoder
+    // from inside `f` gets a block of its own.
+    let mut block = SCRATCH_BLOCK
+        .with(|scratch| scratch.borrow_mut().take())
+        .and_then(|block| block.downcast::<DataBlock<T>>().ok())
+        .filter(|block| block.num_channels() == num_channels && block.block_size() == block_size)
+        .unwrap_or_else(|| Box::new(DataBlock::new(num_channels, block_size)));
+    block.clear();
+
+    let res = f(&mut block);
+
+    SCRATCH_BLOCK.with(|scratch| *scratch.borrow_mut() = Some(block));
+
+    res
+}
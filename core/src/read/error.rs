@@ -1,4 +1,5 @@
 use std::error::Error;
+use std::path::PathBuf;
 
 /// A fatal error occurred and the stream cannot continue.
 #[derive(Debug)]
@@ -42,6 +43,9 @@ pub enum ReadError<FatalDecoderError: Error> {
     /// If this is returned, then the playhead of the stream did not
     /// advance.
     InvalidBuffer,
+    /// Scrubbing was started on a stream that has no second decoder to decode the
+    /// scrub windows with. See `ReadStreamOptions::cache_decoder_opts`.
+    ScrubUnavailable,
 }
 
 impl<FatalDecoderError: Error> std::error::Error for ReadError<FatalDecoderError> {}
@@ -71,6 +75,43 @@ impl<FatalDecoderError: Error> std::fmt::Display for ReadError<FatalDecoderError
             ReadError::InvalidBuffer => {
                 write!(f, "Fill buffer does not match internal buffer layout")
             }
+            ReadError::ScrubUnavailable => {
+                write!(f, "The stream has no second decoder to scrub with")
+            }
+        }
+    }
+}
+
+/// An error opening a series of files with a [`MultiFileDecoder`].
+///
+/// [`MultiFileDecoder`]: super::MultiFileDecoder
+#[derive(Debug)]
+pub enum MultiFileOpenError<OpenDecoderError: Error> {
+    /// One of the files could not be opened.
+    DecoderError {
+        file: PathBuf,
+        error: OpenDecoderError,
+    },
+    /// The file has a different number of channels or sample rate than the first
+    /// file in the series.
+    MismatchedFormat { file: PathBuf },
+}
+
+impl<OpenDecoderError: Error> std::error::Error for MultiFileOpenError<OpenDecoderError> {}
+
+impl<OpenDecoderError: Error> std::fmt::Display for MultiFileOpenError<OpenDecoderError> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MultiFileOpenError::DecoderError { file, error } => {
+                write!(f, "Could not open file {:?}: {}", file, error)
+            }
+            MultiFileOpenError::MismatchedFormat { file } => {
+                write!(
+                    f,
+                    "File {:?} does not have the same format as the first file in the series",
+                    file
+                )
+            }
         }
     }
 }
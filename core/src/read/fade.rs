@@ -0,0 +1,184 @@
+use super::DataBlock;
+
+/// Options for fading between the old and the new position of the playhead when seeking
+/// with `ReadDiskStream::seek_with_fade()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekFade {
+    /// The length of the fade in frames. This is clamped to the block size of the stream.
+    ///
+    /// The old position fades out while the new position fades in over this many frames.
+    pub frames: usize,
+
+    /// If this is `true` and the seek is a cache miss, then the stream keeps playing from
+    /// the old position while the new position is being cached, and crossfades to the new
+    /// position once it is ready. Until then, `ReadDiskStream::playhead()` and
+    /// `ReadDiskStream::is_ready()` refer to the old position.
+    ///
+    /// If this is `false`, then a cache miss crossfades into the silence returned while the
+    /// stream is buffering, the same as without a fade.
+    pub hold_on_miss: bool,
+}
+
+impl Default for SeekFade {
+    fn default() -> Self {
+        Self {
+            frames: 64,
+            hold_on_miss: false,
+        }
+    }
+}
+
+/// A sample type that can be faded and mixed, which is needed for
+/// `ReadDiskStream::seek_with_fade()`.
+pub trait FadeSample: Copy {
+    /// The value of silence. For unsigned sample types this is the midpoint of the range.
+    const SILENCE: Self;
+
+    /// Mix `a` with a gain of `a_gain` and `b` with a gain of `b_gain`, where both gains
+    /// lie in the range `[0.0, 1.0]`.
+    fn mix(a: Self, a_gain: f32, b: Self, b_gain: f32) -> Self;
+}
+
+impl FadeSample for f32 {
+    const SILENCE: Self = 0.0;
+
+    fn mix(a: Self, a_gain: f32, b: Self, b_gain: f32) -> Self {
+        (a * a_gain) + (b * b_gain)
+    }
+}
+
+impl FadeSample for f64 {
+    const SILENCE: Self = 0.0;
+
+    fn mix(a: Self, a_gain: f32, b: Self, b_gain: f32) -> Self {
+        (a * f64::from(a_gain)) + (b * f64::from(b_gain))
+    }
+}
+
+macro_rules! impl_fade_sample_signed {
+    ($($t:ty),*) => {
+        $(
+            impl FadeSample for $t {
+                const SILENCE: Self = 0;
+
+                fn mix(a: Self, a_gain: f32, b: Self, b_gain: f32) -> Self {
+                    let mixed = (f64::from(a) * f64::from(a_gain)) + (f64::from(b) * f64::from(b_gain));
+                    mixed.round().clamp(f64::from(<$t>::MIN), f64::from(<$t>::MAX)) as $t
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_fade_sample_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl FadeSample for $t {
+                const SILENCE: Self = (<$t>::MAX / 2) + 1;
+
+                fn mix(a: Self, a_gain: f32, b: Self, b_gain: f32) -> Self {
+                    let silence = f64::from(Self::SILENCE);
+                    let mixed = ((f64::from(a) - silence) * f64::from(a_gain))
+                        + ((f64::from(b) - silence) * f64::from(b_gain))
+                        + silence;
+                    mixed.round().clamp(0.0, f64::from(<$t>::MAX)) as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_fade_sample_signed!(i8, i16, i32);
+impl_fade_sample_unsigned!(u8, u16, u32);
+
+/// A seek with `SeekFade::hold_on_miss` that is waiting for the new position to be cached
+/// while the old position keeps playing.
+#[derive(Clone, Copy)]
+pub(crate) struct HeldSeek<T> {
+    /// The frame to seek to once the temporary seek cache is filled.
+    pub frame: usize,
+    /// The fade to start once the seek is done. The old frames are stored then.
+    pub fade: FadeState<T>,
+}
+
+/// The state of a fade after a call to `ReadDiskStream::seek_with_fade()`.
+///
+/// The frames from the old position are stored in `HeapData::fade_buffer`.
+#[derive(Clone, Copy)]
+pub(crate) struct FadeState<T> {
+    /// The length of the fade in frames.
+    pub frames: usize,
+    /// The number of frames from the old position that were stored.
+    pub old_len: usize,
+    /// The number of frames of the old position that have been output.
+    pub old_pos: usize,
+    /// The number of frames of the new position that have been output.
+    pub new_pos: usize,
+
+    // Store the sample operations here so that `ReadDiskStream::read()` does not need any
+    // additional trait bounds.
+    pub mix: fn(T, f32, T, f32) -> T,
+    pub silence: T,
+}
+
+impl<T: FadeSample> FadeState<T> {
+    pub fn new(frames: usize, old_len: usize) -> Self {
+        Self {
+            frames,
+            old_len,
+            old_pos: 0,
+            new_pos: 0,
+            mix: T::mix,
+            silence: T::SILENCE,
+        }
+    }
+}
+
+impl<T: Copy + Default + Send> FadeState<T> {
+    pub fn is_done(&self) -> bool {
+        self.old_pos >= self.frames && self.new_pos >= self.frames
+    }
+
+    /// The gain of the old position at the given frame into the fade.
+    pub fn old_gain(&self, pos: usize) -> f32 {
+        if pos >= self.frames || pos >= self.old_len {
+            0.0
+        } else {
+            1.0 - (pos as f32 / self.frames as f32)
+        }
+    }
+
+    /// The gain of the new position at the given frame into the fade.
+    pub fn new_gain(&self, pos: usize) -> f32 {
+        if pos >= self.frames {
+            1.0
+        } else {
+            pos as f32 / self.frames as f32
+        }
+    }
+
+    /// Mix the old position into the first `frames` frames of the new position in `out`.
+    pub fn mix_into(&mut self, out: &mut DataBlock<T>, old: &DataBlock<T>, frames: usize) {
+        for (out_ch, old_ch) in out.channels_mut().zip(old.channels()) {
+            for (i, new) in out_ch.iter_mut().take(frames).enumerate() {
+                let old_pos = self.old_pos + i;
+                let new_pos = self.new_pos + i;
+                if old_pos >= self.frames && new_pos >= self.frames {
+                    break;
+                }
+
+                let old_gain = self.old_gain(old_pos);
+                let old = if old_gain > 0.0 {
+                    old_ch[old_pos]
+                } else {
+                    self.silence
+                };
+
+                *new = (self.mix)(old, old_gain, *new, self.new_gain(new_pos));
+            }
+        }
+
+        self.old_pos += frames;
+        self.new_pos += frames;
+    }
+}
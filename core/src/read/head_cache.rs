@@ -0,0 +1,96 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::budget::CacheUsage;
+use super::data::{DataBlock, DataBlockCache};
+
+/// The first frames of a file, decoded once and shared between any number of
+/// [`ReadDiskStream`]s of that file.
+///
+/// This is useful when the same file is played by many voices at once (i.e. in a
+/// sampler). Each stream that is opened with this head cache can start playing from
+/// the start of the file instantly without decoding it again, and then streams the rest
+/// of the file independently.
+///
+/// Get a head cache from an existing stream with `ReadDiskStream::head_cache()`, and
+/// pass it to other streams with `ReadStreamOptions::head_cache`. Cloning a head cache
+/// only clones a reference to the same data. The data is deallocated once the last
+/// head cache and the last stream using it are dropped.
+///
+/// [`ReadDiskStream`]: struct.ReadDiskStream.html
+pub struct HeadCache<T: Copy + Clone + Default + Send> {
+    blocks: Arc<[DataBlock<T>]>,
+    usage: Option<Arc<CacheUsage>>,
+    block_size: usize,
+    num_look_ahead_blocks: usize,
+}
+
+impl<T: Copy + Clone + Default + Send> HeadCache<T> {
+    pub(crate) fn new(
+        cache: &DataBlockCache<T>,
+        block_size: usize,
+        num_look_ahead_blocks: usize,
+    ) -> Self {
+        Self {
+            blocks: Arc::clone(&cache.blocks),
+            usage: cache.usage.clone(),
+            block_size,
+            num_look_ahead_blocks,
+        }
+    }
+
+    pub(crate) fn to_cache(&self) -> DataBlockCache<T> {
+        DataBlockCache {
+            blocks: Arc::clone(&self.blocks),
+            usage: self.usage.clone(),
+        }
+    }
+
+    /// The number of frames from the start of the file that a stream using this head
+    /// cache can seek to without buffering.
+    pub fn num_frames(&self) -> usize {
+        self.num_cache_blocks() * self.block_size
+    }
+
+    /// The number of channels in the cached data.
+    pub fn num_channels(&self) -> usize {
+        self.blocks.first().map(|b| b.num_channels()).unwrap_or(0)
+    }
+
+    /// The number of frames in a prefetch block of the stream that created this
+    /// head cache.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// The number of cache blocks in the stream that created this head cache.
+    pub fn num_cache_blocks(&self) -> usize {
+        self.blocks.len() - self.num_look_ahead_blocks
+    }
+
+    /// The number of look-ahead blocks in the stream that created this head cache.
+    pub fn num_look_ahead_blocks(&self) -> usize {
+        self.num_look_ahead_blocks
+    }
+}
+
+impl<T: Copy + Clone + Default + Send> Clone for HeadCache<T> {
+    fn clone(&self) -> Self {
+        Self {
+            blocks: Arc::clone(&self.blocks),
+            usage: self.usage.clone(),
+            block_size: self.block_size,
+            num_look_ahead_blocks: self.num_look_ahead_blocks,
+        }
+    }
+}
+
+impl<T: Copy + Clone + Default + Send> fmt::Debug for HeadCache<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeadCache")
+            .field("num_frames", &self.num_frames())
+            .field("num_channels", &self.num_channels())
+            .field("block_size", &self.block_size)
+            .finish()
+    }
+}
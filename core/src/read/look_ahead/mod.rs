@@ -0,0 +1,121 @@
+use std::time::Instant;
+
+#[cfg(test)]
+mod tests;
+
+/// How much the slowest measured turnaround is allowed to decay every time a block is
+/// received, so that the look-ahead can shrink again after a latency spike.
+const PEAK_DECAY: f64 = 0.98;
+
+/// The weight of a new measurement in the average time it takes to play a block.
+const PERIOD_SMOOTHING: f64 = 1.0 / 8.0;
+
+/// A gap between two blocks that is this many times longer than the average is treated
+/// as a pause in playback, and is not measured.
+const PAUSE_FACTOR: f64 = 8.0;
+
+/// Bounds for adapting the number of look-ahead blocks of a stream at runtime.
+///
+/// The stream measures how long the IO server takes to turn around each request for a
+/// block, and how long it takes to play a block. The look-ahead is grown when it would not
+/// last `safety_factor` times the slowest recent turnaround, and shrunk when it would last
+/// longer than that. Every time the prefetch buffer wraps around, the look-ahead changes by
+/// at most one block.
+///
+/// The caches and the message channels of the stream are sized for `max_blocks`, so keep
+/// in mind that a large maximum uses more memory per cache.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveLookAhead {
+    /// The minimum number of look-ahead blocks. This must be at least `1`.
+    ///
+    /// The default is `2`.
+    pub min_blocks: usize,
+
+    /// The maximum number of look-ahead blocks. This must be at least `min_blocks`.
+    ///
+    /// The default is `32`.
+    pub max_blocks: usize,
+
+    /// How many times the slowest recent turnaround of the IO server the look-ahead should
+    /// be able to cover.
+    ///
+    /// The default is `4.0`.
+    pub safety_factor: f64,
+}
+
+impl Default for AdaptiveLookAhead {
+    fn default() -> Self {
+        Self {
+            min_blocks: 2,
+            max_blocks: 32,
+            safety_factor: 4.0,
+        }
+    }
+}
+
+/// The measurements used to adapt the look-ahead of a stream.
+pub(crate) struct LookAheadState {
+    bounds: AdaptiveLookAhead,
+    /// The slowest recent turnaround of the IO server in seconds.
+    peak_turnaround: f64,
+    /// The average time it takes to play a block in seconds, or `None` if it has not
+    /// been measured yet.
+    block_period: Option<f64>,
+    last_advance: Option<Instant>,
+}
+
+impl LookAheadState {
+    pub fn new(bounds: AdaptiveLookAhead) -> Self {
+        Self {
+            bounds,
+            peak_turnaround: 0.0,
+            block_period: None,
+            last_advance: None,
+        }
+    }
+
+    /// Measure the turnaround of a block from when it was requested to when the IO server
+    /// finished decoding it.
+    ///
+    /// This is realtime-safe.
+    pub fn on_block_received(&mut self, requested_at: Instant, decoded_at: Instant) {
+        let turnaround = decoded_at
+            .saturating_duration_since(requested_at)
+            .as_secs_f64();
+        self.peak_turnaround = turnaround.max(self.peak_turnaround * PEAK_DECAY);
+    }
+
+    /// Measure the time since the stream last advanced to the next block, which it did
+    /// at `now`.
+    ///
+    /// This is realtime-safe.
+    pub fn on_advance(&mut self, now: Instant) {
+        if let Some(last_advance) = self.last_advance {
+            let period = now.duration_since(last_advance).as_secs_f64();
+
+            self.block_period = match self.block_period {
+                None => Some(period),
+                Some(average) if period > average * PAUSE_FACTOR => Some(average),
+                Some(average) => Some(average + ((period - average) * PERIOD_SMOOTHING)),
+            };
+        }
+
+        self.last_advance = Some(now);
+    }
+
+    /// Playback is not continuous across a seek, so the next gap is not measured.
+    pub fn on_seek(&mut self) {
+        self.last_advance = None;
+    }
+
+    /// The number of look-ahead blocks that are wanted, or `None` if there are not enough
+    /// measurements yet.
+    pub fn wanted_blocks(&self) -> Option<usize> {
+        let block_period = self.block_period.filter(|period| *period > 0.0)?;
+
+        let blocks = (self.peak_turnaround * self.bounds.safety_factor / block_period).ceil();
+
+        // The float to int cast saturates.
+        Some((blocks as usize).clamp(self.bounds.min_blocks, self.bounds.max_blocks))
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use super::*;
+
+fn bounds() -> AdaptiveLookAhead {
+    AdaptiveLookAhead {
+        min_blocks: 2,
+        max_blocks: 16,
+        safety_factor: 4.0,
+    }
+}
+
+/// Advance `num_blocks` times, `block_period` apart, with every block taking
+/// `turnaround` to be decoded. Returns the time of the last advance.
+fn play(
+    state: &mut LookAheadState,
+    start: Instant,
+    num_blocks: u32,
+    block_period: Duration,
+    turnaround: Duration,
+) -> Instant {
+    let mut now = start;
+    for _ in 0..num_blocks {
+        now += block_period;
+        state.on_block_received(now, now + turnaround);
+        state.on_advance(now);
+    }
+    now
+}
+
+#[test]
+fn needs_measurements() {
+    let mut state = LookAheadState::new(bounds());
+    assert_eq!(state.wanted_blocks(), None);
+
+    // The first advance has nothing to measure against.
+    state.on_advance(Instant::now());
+    assert_eq!(state.wanted_blocks(), None);
+}
+
+#[test]
+fn covers_slowest_turnaround() {
+    let mut state = LookAheadState::new(bounds());
+    play(
+        &mut state,
+        Instant::now(),
+        10,
+        Duration::from_millis(4),
+        Duration::from_millis(5),
+    );
+
+    // ceil(5ms * 4.0 / 4ms)
+    assert_eq!(state.wanted_blocks(), Some(5));
+}
+
+#[test]
+fn clamped_to_bounds() {
+    let mut state = LookAheadState::new(bounds());
+    let now = play(
+        &mut state,
+        Instant::now(),
+        10,
+        Duration::from_millis(4),
+        Duration::from_micros(10),
+    );
+    assert_eq!(state.wanted_blocks(), Some(2));
+
+    play(
+        &mut state,
+        now,
+        1,
+        Duration::from_millis(4),
+        Duration::from_millis(100),
+    );
+    assert_eq!(state.wanted_blocks(), Some(16));
+}
+
+#[test]
+fn peak_turnaround_decays() {
+    let mut state = LookAheadState::new(bounds());
+    let now = play(
+        &mut state,
+        Instant::now(),
+        10,
+        Duration::from_millis(4),
+        Duration::from_millis(10),
+    );
+    assert_eq!(state.wanted_blocks(), Some(10));
+
+    play(
+        &mut state,
+        now,
+        200,
+        Duration::from_millis(4),
+        Duration::from_micros(10),
+    );
+    assert_eq!(state.wanted_blocks(), Some(2));
+}
+
+#[test]
+fn pauses_and_seeks_are_not_measured() {
+    let mut state = LookAheadState::new(bounds());
+    let now = play(
+        &mut state,
+        Instant::now(),
+        10,
+        Duration::from_millis(4),
+        Duration::from_millis(5),
+    );
+    assert_eq!(state.wanted_blocks(), Some(5));
+
+    // A pause in playback.
+    let now = now + Duration::from_secs(1);
+    state.on_advance(now);
+    assert_eq!(state.wanted_blocks(), Some(5));
+
+    // A seek followed by a long gap.
+    state.on_seek();
+    state.on_advance(now + Duration::from_millis(30));
+    assert_eq!(state.wanted_blocks(), Some(5));
+}
@@ -1,15 +1,37 @@
+mod budget;
 mod data;
 mod decoder;
+mod fade;
+mod head_cache;
+mod look_ahead;
+mod multi_file;
 mod read_stream;
+mod scrub;
+mod sequence;
 mod server;
+mod source;
 
 pub mod error;
 
+pub use budget::MemoryBudget;
 pub use data::{DataBlock, ReadData};
-pub use decoder::Decoder;
-pub use error::{FatalReadError, ReadError};
-pub use read_stream::{ReadDiskStream, SeekMode};
+pub use decoder::{decode_into_block, DecodeResult, Decoder};
+pub use error::{FatalReadError, MultiFileOpenError, ReadError};
+pub use fade::{FadeSample, SeekFade};
+pub use head_cache::HeadCache;
+pub use look_ahead::AdaptiveLookAhead;
+pub use multi_file::{MultiFileDecoder, MultiFileOpts, SplitFile};
+pub use read_stream::{CacheInfo, PendingReadDiskStream, ReadDiskStream, SeekMode};
+pub use sequence::{
+    AppendError, ItemBoundary, ItemId, SequenceHandle, SequenceOptions, SequenceReadStream,
+};
+pub use source::{MediaSource, SourceProvider};
 
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::latency::ReadStreamOptionsBuilder;
 use data::{DataBlockCache, HeapData};
 use server::ReadServer;
 
@@ -18,12 +40,18 @@ pub(crate) enum ServerToClientMsg<D: Decoder> {
         block_index: usize,
         block: DataBlock<D::T>,
         wanted_start_frame: usize,
+        /// When the block finished decoding.
+        decoded_at: Instant,
     },
     CacheRes {
         cache_index: usize,
         cache: DataBlockCache<D::T>,
         wanted_start_frame: usize,
     },
+    ScrubRes {
+        block: DataBlock<D::T>,
+        start_frame: usize,
+    },
     FatalError(D::FatalError),
 }
 
@@ -32,25 +60,44 @@ pub(crate) enum ClientToServerMsg<D: Decoder> {
         block_index: usize,
         block: Option<DataBlock<D::T>>,
         start_frame: usize,
+        /// The seek count at the time of the request. The request is skipped if the
+        /// stream has been seeked since.
+        seek_count: usize,
     },
     DisposeBlock {
         block: DataBlock<D::T>,
     },
+    /// Free a block instead of storing it to be reused. If `block` is `None`, then a block
+    /// from the pool is freed.
+    FreeBlock {
+        block: Option<DataBlock<D::T>>,
+    },
     SeekTo {
         frame: usize,
+        seek_count: usize,
     },
     Cache {
         cache_index: usize,
         cache: Option<DataBlockCache<D::T>>,
         start_frame: usize,
+        /// This is `Some` if the cache is only needed until the next seek.
+        seek_count: Option<usize>,
     },
     DisposeCache {
         cache: DataBlockCache<D::T>,
     },
+    /// Free every pooled block and cache, and free anything that is disposed of until
+    /// `Wake` is received.
+    Hibernate,
+    Wake,
+    Scrub {
+        block: Option<DataBlock<D::T>>,
+        start_frame: usize,
+    },
 }
 
 /// Options for a read stream.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct ReadStreamOptions<D: Decoder> {
     /// The number of prefetch blocks in a cache block. This will cause a cache to be
     /// used whenever the stream is seeked to a frame in the range:
@@ -74,6 +121,9 @@ pub struct ReadStreamOptions<D: Decoder> {
     /// case latency scenario.
     ///
     /// This should be left alone unless you know what you are doing.
+    ///
+    /// If `adaptive_look_ahead` is `Some`, then this is only the number of look-ahead
+    /// blocks the stream starts with, clamped to the bounds given there.
     pub num_look_ahead_blocks: usize,
 
     /// The number of frames in a prefetch block.
@@ -89,6 +139,79 @@ pub struct ReadStreamOptions<D: Decoder> {
     ///
     /// The default is `None`.
     pub server_msg_channel_size: Option<usize>,
+
+    /// If the file has at most this many frames, then the whole file is decoded into the
+    /// cache with index `0` while the stream is being opened. Every seek to a frame in the
+    /// file is then a guaranteed cache hit, and no more data is read from the file.
+    ///
+    /// `num_cache_blocks` is ignored for preloaded files, and moving the cache with index
+    /// `0` with `ReadDiskStream::cache()` will drop the preloaded data. Keep in mind that the
+    /// message channel to the IO server is sized to hold a message for every block in the
+    /// largest preloaded file.
+    ///
+    /// Set this to `None` to never preload the file.
+    ///
+    /// The default is `None`.
+    pub preload_max_frames: Option<usize>,
+
+    /// A shared cache of the start of the file, usually taken from another stream of the
+    /// same file with `ReadDiskStream::head_cache()`. It is used as the cache with index
+    /// `0`, so seeking to any frame in the head cache never buffers and the start of the
+    /// file is never decoded again.
+    ///
+    /// The block size and the number of cache and look-ahead blocks of the head cache are
+    /// used instead of `block_size`, `num_cache_blocks`, and `num_look_ahead_blocks`, and
+    /// `preload_max_frames` is ignored. The head cache is not used if its number of
+    /// channels does not match the file.
+    ///
+    /// The default is `None`.
+    pub head_cache: Option<HeadCache<D::T>>,
+
+    /// A memory budget to share with other streams. See [`MemoryBudget`].
+    ///
+    /// Set this to `None` to not limit the memory used by this stream.
+    ///
+    /// The default is `None`.
+    pub memory_budget: Option<MemoryBudget>,
+
+    /// A callback that is called whenever a cache requested with `ReadDiskStream::cache()`
+    /// has been filled. See [`CacheReadyCallback`].
+    ///
+    /// The default is `None`.
+    pub on_cache_ready: Option<CacheReadyCallback>,
+
+    /// If this is `Some`, then the IO server opens a second decoder with these options
+    /// that is only used to fill caches and to decode scrub windows. The streaming decoder
+    /// then never has to seek away to fill a cache, and a cache that takes a while to fill
+    /// is paused whenever the stream needs more data.
+    ///
+    /// This is required for `ReadDiskStream::scrub_to()`. Keep in mind that this opens the
+    /// file twice.
+    ///
+    /// The default is `None`.
+    pub cache_decoder_opts: Option<D::AdditionalOpts>,
+
+    /// If this is `Some`, then the stream grows and shrinks its number of look-ahead blocks
+    /// at runtime within the given bounds, depending on how fast the IO server turns around
+    /// requests for blocks. See [`AdaptiveLookAhead`].
+    ///
+    /// With a head cache, the maximum is limited to the number of look-ahead blocks of the
+    /// head cache.
+    ///
+    /// The default is `None`.
+    pub adaptive_look_ahead: Option<AdaptiveLookAhead>,
+
+    /// If this is `true`, then every prefetch block and cache the stream can use is
+    /// allocated (and written to once) on the IO server thread while the stream is being
+    /// opened, instead of when it is first needed. This avoids allocating on the IO server
+    /// while playback starts and when a cache is first requested. The number of bytes that
+    /// were allocated is returned from `ReadDiskStream::preallocated_bytes()`.
+    ///
+    /// Keep in mind that caches in a memory budget can still be evicted and then allocated
+    /// again later, and that the window used for scrubbing is still allocated on first use.
+    ///
+    /// The default is `false`.
+    pub preallocate: bool,
 }
 
 impl<D: Decoder> Default for ReadStreamOptions<D> {
@@ -100,6 +223,57 @@ impl<D: Decoder> Default for ReadStreamOptions<D> {
             num_look_ahead_blocks: D::DEFAULT_NUM_LOOK_AHEAD_BLOCKS,
             num_caches: 1,
             server_msg_channel_size: None,
+            preload_max_frames: None,
+            head_cache: None,
+            memory_budget: None,
+            on_cache_ready: None,
+            cache_decoder_opts: None,
+            adaptive_look_ahead: None,
+            preallocate: false,
         }
     }
 }
+
+impl<D: Decoder> ReadStreamOptions<D> {
+    /// Start building options that buffer `buffer_secs` seconds ahead of the playhead at
+    /// the given sample rate, and that can cover a disk latency of up to
+    /// `worst_case_latency_secs` seconds. See [`ReadStreamOptionsBuilder`].
+    pub fn builder(
+        sample_rate: u32,
+        buffer_secs: f64,
+        worst_case_latency_secs: f64,
+    ) -> ReadStreamOptionsBuilder<D> {
+        ReadStreamOptionsBuilder::new(sample_rate, buffer_secs, worst_case_latency_secs)
+    }
+}
+
+/// A callback that is called on the IO server thread whenever it has filled a cache.
+///
+/// The arguments are the index of the cache and the frame in the file the cache starts
+/// from. The stream receives the cache the next time it is polled, so
+/// `ReadDiskStream::is_cache_ready()` returns true from then on unless the cache was moved
+/// or released in the meantime.
+///
+/// This is ***not*** called in a realtime thread, so it may block or allocate (i.e. to
+/// send a message over a channel). Keep in mind that the IO server cannot fetch any data
+/// while it runs.
+#[derive(Clone)]
+pub struct CacheReadyCallback(Arc<dyn Fn(usize, usize) + Send + Sync>);
+
+impl CacheReadyCallback {
+    /// Create a new callback from a closure that takes the index of the cache and the frame
+    /// in the file the cache starts from.
+    pub fn new<F: Fn(usize, usize) + Send + Sync + 'static>(f: F) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, cache_index: usize, start_frame: usize) {
+        (self.0)(cache_index, start_frame);
+    }
+}
+
+impl fmt::Debug for CacheReadyCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CacheReadyCallback").finish()
+    }
+}
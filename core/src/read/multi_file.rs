@@ -0,0 +1,257 @@
+use std::path::{Path, PathBuf};
+
+use super::decoder::decode_into_block;
+use super::error::MultiFileOpenError;
+use super::{DataBlock, DecodeResult, Decoder};
+use crate::write::num_files_to_file_name_extension;
+use crate::FileInfo;
+
+/// Options for opening a series of files with a [`MultiFileDecoder`].
+#[derive(Debug, Clone, Default)]
+pub struct MultiFileOpts<O> {
+    /// The number of files in the series.
+    ///
+    /// Set this to `None` to find every file in the series that exists on the file
+    /// system. Set this when the files are not read from the file system (i.e. with a
+    /// `SourceProvider`).
+    ///
+    /// The default is `None`.
+    pub num_files: Option<u32>,
+
+    /// The options used to open each file with the inner decoder.
+    pub decoder_opts: O,
+}
+
+/// Info about a single file in a series opened with a [`MultiFileDecoder`].
+#[derive(Clone)]
+pub struct SplitFile<FileParams> {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The frame in the series this file starts from.
+    pub start_frame: usize,
+    /// Info about the file.
+    pub info: FileInfo<FileParams>,
+}
+
+/// A decoder that reads a recording that was split into multiple files as if it
+/// was a single file.
+///
+/// An [`Encoder`] splits recordings that reach the maximum size of its format into
+/// multiple files by appending the extension from [`num_files_to_file_name_extension()`]
+/// to the name of the file (i.e. `name.wav`, `name.wav_001`, `name.wav_002`, etc.). Open
+/// the stream with the path of the first file, and the rest of the series is opened with
+/// the same inner decoder `D`. The files are played back to back, and seeking works
+/// across file boundaries.
+///
+/// Every file in the series is opened (and kept open) when the stream is opened, so that
+/// the exact number of frames in the series is known.
+///
+/// [`Encoder`]: crate::Encoder
+/// [`num_files_to_file_name_extension()`]: crate::write::num_files_to_file_name_extension
+pub struct MultiFileDecoder<D: Decoder> {
+    decoders: Vec<D>,
+    /// The frame in the series each file starts from.
+    start_frames: Vec<usize>,
+    /// The number of frames in each file.
+    file_frames: Vec<usize>,
+    num_frames: usize,
+    block_size: usize,
+
+    current_file: usize,
+    current_frame: usize,
+
+    /// Used to decode from a file without overfilling the block.
+    scratch_block: DataBlock<D::T>,
+}
+
+impl<D: Decoder> MultiFileDecoder<D> {
+    /// Return the index of the file that holds the given frame in the series.
+    fn file_at(&self, frame: usize) -> usize {
+        (0..self.decoders.len())
+            .find(|&i| frame < self.start_frames[i] + self.file_frames[i])
+            .unwrap_or(self.decoders.len() - 1)
+    }
+}
+
+impl<D: Decoder> Decoder for MultiFileDecoder<D>
+where
+    D::AdditionalOpts: Clone,
+{
+    type T = D::T;
+    type AdditionalOpts = MultiFileOpts<D::AdditionalOpts>;
+    type FileParams = Vec<SplitFile<D::FileParams>>;
+    type OpenError = MultiFileOpenError<D::OpenError>;
+    type FatalError = D::FatalError;
+
+    const DEFAULT_BLOCK_SIZE: usize = D::DEFAULT_BLOCK_SIZE;
+    const DEFAULT_NUM_CACHE_BLOCKS: usize = D::DEFAULT_NUM_CACHE_BLOCKS;
+    const DEFAULT_NUM_LOOK_AHEAD_BLOCKS: usize = D::DEFAULT_NUM_LOOK_AHEAD_BLOCKS;
+
+    fn new(
+        file: PathBuf,
+        start_frame: usize,
+        block_size: usize,
+        additional_opts: Self::AdditionalOpts,
+    ) -> Result<(Self, FileInfo<Self::FileParams>), Self::OpenError> {
+        let MultiFileOpts {
+            num_files,
+            decoder_opts,
+        } = additional_opts;
+
+        let paths: Vec<PathBuf> = match num_files {
+            Some(num_files) => (1..=num_files.max(1))
+                .map(|i| split_file_path(&file, i))
+                .collect(),
+            None => {
+                let mut paths = vec![file.clone()];
+                let mut i = 2;
+                loop {
+                    let path = split_file_path(&file, i);
+                    if !path.exists() {
+                        break;
+                    }
+                    paths.push(path);
+                    i += 1;
+                }
+                paths
+            }
+        };
+
+        let mut decoders = Vec::with_capacity(paths.len());
+        let mut files: Vec<SplitFile<D::FileParams>> = Vec::with_capacity(paths.len());
+        let mut num_frames = 0;
+
+        for path in paths {
+            // Start every file from where `start_frame` lies relative to it.
+            let file_start_frame = start_frame.saturating_sub(num_frames);
+
+            let (decoder, info) = D::new(
+                path.clone(),
+                file_start_frame,
+                block_size,
+                decoder_opts.clone(),
+            )
+            .map_err(|error| MultiFileOpenError::DecoderError {
+                file: path.clone(),
+                error,
+            })?;
+
+            if let Some(first) = files.first() {
+                if info.num_channels != first.info.num_channels
+                    || info.sample_rate != first.info.sample_rate
+                {
+                    return Err(MultiFileOpenError::MismatchedFormat { file: path });
+                }
+            }
+
+            let file_frames = info.num_frames;
+            decoders.push(decoder);
+            files.push(SplitFile {
+                path,
+                start_frame: num_frames,
+                info,
+            });
+            num_frames += file_frames;
+        }
+
+        let num_channels = files[0].info.num_channels;
+        let sample_rate = files[0].info.sample_rate;
+
+        let mut decoder = Self {
+            decoders,
+            start_frames: files.iter().map(|file| file.start_frame).collect(),
+            file_frames: files.iter().map(|file| file.info.num_frames).collect(),
+            num_frames,
+            block_size,
+            current_file: 0,
+            current_frame: start_frame.min(num_frames),
+            scratch_block: DataBlock::new(usize::from(num_channels), block_size),
+        };
+        decoder.current_file = decoder.file_at(decoder.current_frame);
+
+        Ok((
+            decoder,
+            FileInfo {
+                num_frames,
+                num_channels,
+                sample_rate,
+                params: files,
+            },
+        ))
+    }
+
+    fn seek(&mut self, frame: usize) -> Result<(), Self::FatalError> {
+        let frame = frame.min(self.num_frames);
+        let file = self.file_at(frame);
+
+        self.decoders[file].seek(frame - self.start_frames[file])?;
+
+        self.current_file = file;
+        self.current_frame = frame;
+
+        Ok(())
+    }
+
+    fn decode(&mut self, data_block: &mut DataBlock<Self::T>) -> Result<(), Self::FatalError> {
+        decode_into_block(self, data_block).map(|_| ())
+    }
+
+    fn decode_into(
+        &mut self,
+        channels: &mut [&mut [Self::T]],
+    ) -> Result<DecodeResult, Self::FatalError> {
+        let mut filled = 0;
+
+        while filled < self.block_size && self.current_frame < self.num_frames {
+            let file = self.current_file;
+            let frame_in_file = self.current_frame - self.start_frames[file];
+
+            if frame_in_file >= self.file_frames[file] {
+                // Move on to the next file in the series.
+                self.current_file += 1;
+                self.decoders[self.current_file].seek(0)?;
+                continue;
+            }
+
+            decode_into_block(&mut self.decoders[file], &mut self.scratch_block)?;
+
+            let decoded = self.scratch_block.num_frames();
+            if decoded == 0 {
+                // The file has less frames than it reported, so skip the rest of it.
+                self.current_frame = self.start_frames[file] + self.file_frames[file];
+                continue;
+            }
+
+            let frames = decoded.min(self.block_size - filled);
+            for (dst_ch, scratch_ch) in channels.iter_mut().zip(self.scratch_block.channels()) {
+                dst_ch[filled..filled + frames].copy_from_slice(&scratch_ch[..frames]);
+            }
+
+            if frames < decoded {
+                // Only part of the decoded frames fit, so continue from the first frame
+                // that did not fit next time.
+                self.decoders[file].seek(frame_in_file + frames)?;
+            }
+
+            filled += frames;
+            self.current_frame += frames;
+        }
+
+        Ok(DecodeResult {
+            frames: filled,
+            reached_end_of_file: self.current_frame >= self.num_frames,
+        })
+    }
+
+    fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+}
+
+/// Return the path of the file with the given number (starting from `1`) in a series
+/// of files that starts with `file`.
+fn split_file_path(file: &Path, num_files: u32) -> PathBuf {
+    let mut file_name = file.file_name().unwrap_or_default().to_os_string();
+    file_name.push(num_files_to_file_name_extension(num_files));
+    file.with_file_name(file_name)
+}
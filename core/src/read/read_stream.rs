@@ -1,13 +1,20 @@
 use rtrb::{Consumer, Producer, RingBuffer};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
-use super::data::{DataBlockCacheEntry, DataBlockEntry};
+use super::data::{DataBlockCache, DataBlockCacheEntry, DataBlockEntry};
 use super::error::{FatalReadError, ReadError};
+use super::fade::{FadeSample, FadeState, HeldSeek, SeekFade};
+use super::look_ahead::LookAheadState;
+use super::scrub::{scrub_into, ScrubRequest, ScrubState};
 use super::{
-    ClientToServerMsg, DataBlock, Decoder, HeapData, ReadData, ReadServer, ReadStreamOptions,
-    ServerToClientMsg,
+    AdaptiveLookAhead, ClientToServerMsg, DataBlock, Decoder, HeadCache, HeapData, ReadData,
+    ReadServer, ReadStreamOptions, ServerToClientMsg,
 };
-use crate::read::server::ReadServerOptions;
+use crate::open::{open_result_channel, sealed, OpenStream, PendingDiskStream};
+use crate::read::server::{OpenedFile, ReadServerOptions};
 use crate::{FileInfo, SERVER_WAIT_TIME};
 
 /// Describes how to search for suitable caches when seeking in a [`ReadDiskStream`].
@@ -35,13 +42,48 @@ pub enum SeekMode {
     NoCache,
 }
 
-struct ReadDiskStreamOptions<D: Decoder> {
+/// The state of a cache in a [`ReadDiskStream`], returned from `ReadDiskStream::cache_info()`.
+///
+/// [`ReadDiskStream`]: struct.ReadDiskStream.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheInfo {
+    /// The cache has not been requested, or it has been released.
+    Empty,
+    /// The cache has been requested but the IO server has not filled it yet. Seeking to
+    /// this cache will cause the stream to buffer.
+    Pending {
+        /// The frame in the file the cache starts from.
+        start_frame: usize,
+    },
+    /// The cache is filled and can be seeked to without buffering.
+    Ready {
+        /// The frame in the file the cache starts from.
+        start_frame: usize,
+    },
+}
+
+/// A [`ReadDiskStream`] that is still being opened on its IO server thread.
+///
+/// If this is dropped before the file has been opened, then the IO server thread
+/// closes itself once it is done opening the file.
+pub type PendingReadDiskStream<D> = PendingDiskStream<ReadDiskStream<D>>;
+
+/// The parts of a [`ReadDiskStream`] that are created before the file has been opened.
+pub struct ReadDiskStreamOptions<D: Decoder> {
     start_frame: usize,
     num_cache_blocks: usize,
     num_look_ahead_blocks: usize,
+    initial_num_look_ahead_blocks: usize,
+    adaptive_look_ahead: Option<AdaptiveLookAhead>,
     max_num_caches: usize,
     block_size: usize,
-    file_info: FileInfo<D::FileParams>,
+    head_cache: Option<HeadCache<D::T>>,
+    seek_count: Arc<AtomicUsize>,
+    can_scrub: bool,
+
+    to_server_tx: Producer<ClientToServerMsg<D>>,
+    from_server_rx: Consumer<ServerToClientMsg<D>>,
+    close_signal_tx: Producer<Option<HeapData<D::T>>>,
 }
 
 /// A realtime-safe disk-streaming reader of audio files.
@@ -61,12 +103,29 @@ pub struct ReadDiskStream<D: Decoder> {
     temp_seek_cache_index: usize,
 
     num_prefetch_blocks: usize,
+    /// The number of look-ahead blocks in a cache. This is the maximum number of
+    /// look-ahead blocks when the look-ahead is adaptive.
+    num_look_ahead_blocks: usize,
     prefetch_size: usize,
     cache_size: usize,
     block_size: usize,
 
     file_info: FileInfo<D::FileParams>,
     fatal_error: bool,
+    preloaded: bool,
+    preallocated_bytes: usize,
+    /// The value of silence that `read()` returns while the stream is hibernating, or
+    /// `None` if it is not hibernating.
+    hibernating: Option<D::T>,
+    /// Incremented on every seek so that the IO server can skip requests made before
+    /// the latest seek.
+    seek_count: Arc<AtomicUsize>,
+    fade: Option<FadeState<D::T>>,
+    held_seek: Option<HeldSeek<D::T>>,
+    scrub: Option<ScrubState>,
+    /// Whether the IO server has a second decoder to decode scrub windows with.
+    can_scrub: bool,
+    look_ahead: Option<LookAheadState>,
 }
 
 impl<D: Decoder> ReadDiskStream<D> {
@@ -76,31 +135,93 @@ impl<D: Decoder> ReadDiskStream<D> {
     /// * `start_frame` - The frame in the file to start reading from.
     /// * `stream_opts` - Additional stream options.
     ///
+    /// This blocks the calling thread until the file has been opened. Use
+    /// `ReadDiskStream::open()` to open the file without blocking.
+    ///
     /// # Panics
     ///
     /// This will panic if `stream_block_size`, `stream_num_look_ahead_blocks`,
-    /// or `stream_server_msg_channel_size` is `0`.
+    /// or `stream_server_msg_channel_size` is `0`, or if the bounds of
+    /// `stream_adaptive_look_ahead` are invalid.
     pub fn new<P: Into<PathBuf>>(
         file: P,
         start_frame: usize,
         stream_opts: ReadStreamOptions<D>,
     ) -> Result<ReadDiskStream<D>, D::OpenError> {
+        Self::open(file, start_frame, stream_opts).wait()
+    }
+
+    /// Start opening a new realtime-safe disk-streaming reader without blocking the
+    /// calling thread.
+    ///
+    /// * `file` - The path to the file to open.
+    /// * `start_frame` - The frame in the file to start reading from.
+    /// * `stream_opts` - Additional stream options.
+    ///
+    /// The file is opened on the IO server thread. The returned handle can either be
+    /// polled with `PendingReadDiskStream::poll_open()` or awaited as a `Future`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `stream_block_size`, `stream_num_look_ahead_blocks`,
+    /// or `stream_server_msg_channel_size` is `0`, or if the bounds of
+    /// `stream_adaptive_look_ahead` are invalid.
+    pub fn open<P: Into<PathBuf>>(
+        file: P,
+        start_frame: usize,
+        stream_opts: ReadStreamOptions<D>,
+    ) -> PendingReadDiskStream<D> {
         let ReadStreamOptions {
-            num_cache_blocks,
+            mut num_cache_blocks,
             num_caches,
             additional_opts,
-            num_look_ahead_blocks,
-            block_size,
+            mut num_look_ahead_blocks,
+            mut block_size,
             server_msg_channel_size,
+            mut preload_max_frames,
+            head_cache,
+            memory_budget,
+            on_cache_ready,
+            cache_decoder_opts,
+            mut adaptive_look_ahead,
+            preallocate,
         } = stream_opts;
 
+        // Use the same layout as the stream that created the head cache.
+        if let Some(head_cache) = &head_cache {
+            num_cache_blocks = head_cache.num_cache_blocks();
+            num_look_ahead_blocks = head_cache.num_look_ahead_blocks();
+            block_size = head_cache.block_size();
+            preload_max_frames = None;
+        }
+
         assert_ne!(block_size, 0);
         assert_ne!(num_look_ahead_blocks, 0);
         assert_ne!(server_msg_channel_size, Some(0));
 
+        // The caches hold enough look-ahead blocks for the largest look-ahead.
+        let initial_num_look_ahead_blocks = num_look_ahead_blocks;
+        if let Some(adaptive) = &mut adaptive_look_ahead {
+            assert_ne!(adaptive.min_blocks, 0);
+            assert!(adaptive.min_blocks <= adaptive.max_blocks);
+
+            if head_cache.is_some() {
+                adaptive.max_blocks = num_look_ahead_blocks;
+                adaptive.min_blocks = adaptive.min_blocks.min(num_look_ahead_blocks);
+            }
+
+            num_look_ahead_blocks = adaptive.max_blocks;
+        }
+
+        // A preloaded file uses as many cache blocks as needed to hold the whole file.
+        let max_num_cache_blocks = match preload_max_frames {
+            Some(max_frames) => num_cache_blocks.max((max_frames / block_size) + 1),
+            None => num_cache_blocks,
+        };
+
         // Reserve ample space for the message channels.
         let msg_channel_size = server_msg_channel_size
-            .unwrap_or(((num_cache_blocks + num_look_ahead_blocks) * 4) + (num_caches * 4) + 8);
+            .unwrap_or(((max_num_cache_blocks + num_look_ahead_blocks) * 4) + (num_caches * 4) + 8);
 
         let (to_server_tx, from_client_rx) =
             RingBuffer::<ClientToServerMsg<D>>::new(msg_channel_size);
@@ -110,59 +231,102 @@ impl<D: Decoder> ReadDiskStream<D> {
         // Create dedicated close signal.
         let (close_signal_tx, close_signal_rx) = RingBuffer::<Option<HeapData<D::T>>>::new(1);
 
+        let (open_tx, open_rx) = open_result_channel();
+
         let file: PathBuf = file.into();
 
-        match ReadServer::spawn(
+        let seek_count = Arc::new(AtomicUsize::new(0));
+
+        // Scrub windows are decoded with the cache decoder.
+        let can_scrub = cache_decoder_opts.is_some();
+
+        // The two temporary caches are allocated as well, and a head cache is used instead
+        // of allocating the cache with index `0`.
+        let preallocate_caches =
+            preallocate.then(|| (num_caches + 2).saturating_sub(usize::from(head_cache.is_some())));
+
+        ReadServer::spawn(
             ReadServerOptions {
                 file,
                 start_frame,
                 num_prefetch_blocks: num_cache_blocks + num_look_ahead_blocks,
+                num_look_ahead_blocks,
                 block_size,
+                preload_max_frames,
+                memory_budget,
+                num_caches,
+                on_cache_ready,
+                seek_count: Arc::clone(&seek_count),
                 additional_opts,
+                cache_decoder_opts,
+                preallocate_caches,
             },
             to_client_tx,
             from_client_rx,
             close_signal_rx,
-        ) {
-            Ok(file_info) => {
-                let client = ReadDiskStream::create(
-                    ReadDiskStreamOptions {
-                        start_frame,
-                        num_cache_blocks,
-                        num_look_ahead_blocks,
-                        max_num_caches: num_caches,
-                        block_size,
-                        file_info,
-                    },
-                    to_server_tx,
-                    from_server_rx,
-                    close_signal_tx,
-                );
+            open_tx,
+        );
 
-                Ok(client)
-            }
-            Err(e) => Err(e),
-        }
+        PendingReadDiskStream::new(
+            open_rx,
+            ReadDiskStreamOptions {
+                start_frame,
+                num_cache_blocks,
+                num_look_ahead_blocks,
+                initial_num_look_ahead_blocks,
+                adaptive_look_ahead,
+                max_num_caches: num_caches,
+                block_size,
+                head_cache,
+                seek_count,
+                can_scrub,
+                to_server_tx,
+                from_server_rx,
+                close_signal_tx,
+            },
+        )
     }
 
-    fn create(
-        opts: ReadDiskStreamOptions<D>,
-        to_server_tx: Producer<ClientToServerMsg<D>>,
-        from_server_rx: Consumer<ServerToClientMsg<D>>,
-        close_signal_tx: Producer<Option<HeapData<D::T>>>,
-    ) -> Self {
+    fn create(opts: ReadDiskStreamOptions<D>, opened_file: OpenedFile<D>) -> Self {
         let ReadDiskStreamOptions {
             start_frame,
-            num_cache_blocks,
+            mut num_cache_blocks,
             num_look_ahead_blocks,
+            initial_num_look_ahead_blocks,
+            adaptive_look_ahead,
             max_num_caches,
             block_size,
-            file_info,
+            head_cache,
+            seek_count,
+            can_scrub,
+            to_server_tx,
+            from_server_rx,
+            close_signal_tx,
         } = opts;
+        let OpenedFile {
+            file_info,
+            preload_cache,
+            preallocated_bytes,
+        } = opened_file;
 
-        let num_prefetch_blocks = num_cache_blocks + num_look_ahead_blocks;
+        let head_cache = head_cache
+            .filter(|head_cache| head_cache.num_channels() == usize::from(file_info.num_channels))
+            .map(|head_cache| head_cache.to_cache());
+
+        if let Some(cache) = &preload_cache {
+            num_cache_blocks = cache.blocks.len() - num_look_ahead_blocks;
+        }
+
+        let num_prefetch_blocks = match &adaptive_look_ahead {
+            Some(adaptive) => {
+                num_cache_blocks
+                    + initial_num_look_ahead_blocks.clamp(adaptive.min_blocks, adaptive.max_blocks)
+            }
+            None => num_cache_blocks + num_look_ahead_blocks,
+        };
 
         let read_buffer = DataBlock::new(usize::from(file_info.num_channels), block_size);
+        let fade_buffer = DataBlock::new(usize::from(file_info.num_channels), block_size);
 
         // Reserve the last two caches as temporary caches.
         let max_num_caches = max_num_caches + 2;
@@ -172,20 +336,23 @@ impl<D: Decoder> ReadDiskStream<D> {
             caches.push(DataBlockCacheEntry {
                 cache: None,
                 wanted_start_frame: 0,
+                requested: false,
             });
         }
 
         let temp_cache_index = max_num_caches - 1;
         let temp_seek_cache_index = max_num_caches - 2;
 
+        // Reserve room for the largest look-ahead.
         let mut prefetch_buffer: Vec<DataBlockEntry<D::T>> =
-            Vec::with_capacity(num_prefetch_blocks);
+            Vec::with_capacity(num_cache_blocks + num_look_ahead_blocks);
         let mut wanted_start_frame = start_frame;
         for _ in 0..num_prefetch_blocks {
             prefetch_buffer.push(DataBlockEntry {
                 use_cache_index: None,
                 block: None,
                 wanted_start_frame,
+                requested_at: None,
             });
 
             wanted_start_frame += block_size;
@@ -193,11 +360,15 @@ impl<D: Decoder> ReadDiskStream<D> {
 
         let heap_data = Some(HeapData {
             read_buffer,
+            fade_buffer,
+            scrub_window: None,
+            scrub_spare: None,
+            lent_block: None,
             prefetch_buffer,
             caches,
         });
 
-        Self {
+        let mut stream = Self {
             to_server_tx,
             from_server_rx,
             close_signal_tx,
@@ -213,13 +384,69 @@ impl<D: Decoder> ReadDiskStream<D> {
             temp_seek_cache_index,
 
             num_prefetch_blocks,
+            num_look_ahead_blocks,
             prefetch_size: num_prefetch_blocks * block_size,
             cache_size: num_cache_blocks * block_size,
             block_size,
 
             file_info,
             fatal_error: false,
+            preloaded: preload_cache.is_some(),
+            preallocated_bytes,
+            hibernating: None,
+            seek_count,
+            fade: None,
+            held_seek: None,
+            scrub: None,
+            can_scrub,
+            look_ahead: adaptive_look_ahead.map(LookAheadState::new),
+        };
+
+        if let Some(cache) = preload_cache.or(head_cache) {
+            if let Some(heap) = stream.heap_data.as_mut() {
+                heap.caches[0].cache = Some(cache);
+                heap.caches[0].wanted_start_frame = 0;
+                heap.caches[0].requested = true;
+            }
+
+            // Start reading from the preloaded or shared cache. This cannot fail because the
+            // message channel is large enough to hold a message for every block in the cache.
+            let _ = stream.seek(start_frame, SeekMode::TryOne(0));
+        }
+
+        stream
+    }
+
+    /// Returns true if the whole file was decoded into memory when the stream was opened.
+    /// See `ReadStreamOptions::preload_max_frames`.
+    ///
+    /// This is realtime-safe.
+    pub fn is_preloaded(&self) -> bool {
+        self.preloaded
+    }
+
+    /// Returns a handle to the cache with index `0` that can be shared with other streams of
+    /// the same file, or `None` if that cache does not currently hold the start of the file.
+    /// See [`HeadCache`].
+    ///
+    /// The cache with index `0` holds the start of the file if the file was preloaded, if
+    /// this stream was opened with a head cache, or once it has been filled after calling
+    /// `ReadDiskStream::cache(0, 0)`.
+    ///
+    /// This is realtime-safe, but the returned head cache should not be dropped in a
+    /// realtime thread since that may deallocate the cached data.
+    pub fn head_cache(&self) -> Option<HeadCache<D::T>> {
+        let heap = self.heap_data.as_ref()?;
+        let entry = &heap.caches[0];
+
+        if entry.wanted_start_frame != 0 || !entry.is_usable() {
+            return None;
         }
+
+        entry
+            .cache
+            .as_ref()
+            .map(|cache| HeadCache::new(cache, self.block_size, self.num_look_ahead_blocks))
     }
 
     /// Return the total number of caches available in this stream.
@@ -272,9 +499,9 @@ impl<D: Decoder> ReadDiskStream<D> {
     /// This is realtime-safe.
     ///
     /// * `cache_index` - The index of the cache to use. Use `ReadDiskStream::num_caches()` to see
-    ///    how many caches have been assigned to this stream.
+    ///   how many caches have been assigned to this stream.
     /// * `start_frame` - The frame in the file to start filling in the cache from. If any portion lies
-    ///    outside the end of the file, then that portion will be ignored.
+    ///   outside the end of the file, then that portion will be ignored.
     ///
     /// If the cache already exists, then it will be overwritten. If the cache already starts from this
     /// position, then nothing will be done and (false) will be returned. Otherwise, (true) will be
@@ -317,53 +544,8 @@ impl<D: Decoder> ReadDiskStream<D> {
             }
 
             heap.caches[cache_index].wanted_start_frame = start_frame;
-            let mut cache = heap.caches[cache_index].cache.take();
-
-            // If any blocks are currently using this cache, then set this cache as the
-            // temporary cache and tell each block to use that instead.
-            let mut using_cache = false;
-            let mut using_temp_cache = false;
-            for block in heap.prefetch_buffer.iter_mut() {
-                if let Some(index) = block.use_cache_index {
-                    if index == cache_index {
-                        block.use_cache_index = Some(self.temp_cache_index);
-                        using_cache = true;
-                    } else if index == self.temp_cache_index {
-                        using_temp_cache = true;
-                    }
-                }
-            }
-            if using_cache {
-                if let Some(old_cache) = heap.caches[self.temp_cache_index].cache.take() {
-                    // If any blocks are currently using the old temporary cache, dispose those blocks.
-                    if using_temp_cache {
-                        for block in heap.prefetch_buffer.iter_mut() {
-                            if let Some(index) = block.use_cache_index {
-                                if index == self.temp_cache_index {
-                                    block.use_cache_index = None;
-                                    if let Some(block) = block.block.take() {
-                                        // Tell the server to deallocate the old block.
-                                        // This cannot fail because we made sure that a slot is available in
-                                        // the previous step.
-                                        let _ = self
-                                            .to_server_tx
-                                            .push(ClientToServerMsg::DisposeBlock { block });
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    // Tell the server to deallocate the old temporary cache.
-                    // This cannot fail because we made sure that a slot is available in
-                    // the previous step.
-                    let _ = self
-                        .to_server_tx
-                        .push(ClientToServerMsg::DisposeCache { cache: old_cache });
-                }
-
-                heap.caches[self.temp_cache_index].cache = cache.take();
-            }
+            heap.caches[cache_index].requested = true;
+            let cache = self.take_cache(cache_index);
 
             // This cannot fail because we made sure that a slot is available in
             // the previous step.
@@ -371,6 +553,7 @@ impl<D: Decoder> ReadDiskStream<D> {
                 cache_index,
                 cache,
                 start_frame,
+                seek_count: None,
             });
 
             return Ok(true);
@@ -379,12 +562,183 @@ impl<D: Decoder> ReadDiskStream<D> {
         Ok(false)
     }
 
+    /// Release the cache with the given index and give its memory back to the IO server.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// If the cache is still being filled, then that request is cancelled. If the cache has
+    /// not been requested, then nothing will be done and (false) will be returned. Otherwise,
+    /// (true) will be returned.
+    ///
+    /// In the case where playback is currently relying on this cache, then it will attempt
+    /// to store the cache in a temporary buffer to allow playback to resume seamlessly, the
+    /// same as when the cache is moved with `ReadDiskStream::cache()`.
+    pub fn uncache(&mut self, cache_index: usize) -> Result<bool, ReadError<D::FatalError>> {
+        if self.fatal_error {
+            return Err(ReadError::FatalError(FatalReadError::StreamClosed));
+        }
+
+        let Some(heap) = self.heap_data.as_mut() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return Ok(false);
+        };
+
+        if cache_index >= heap.caches.len() - 2 {
+            return Err(ReadError::CacheIndexOutOfRange {
+                index: cache_index,
+                num_caches: heap.caches.len() - 2,
+            });
+        }
+
+        if !heap.caches[cache_index].requested {
+            return Ok(false);
+        }
+
+        // Check that at-least two message slots are open.
+        if self.to_server_tx.slots() < 2 + self.num_prefetch_blocks {
+            return Err(ReadError::IOServerChannelFull);
+        }
+
+        // Any result from the server for this cache will now be ignored.
+        heap.caches[cache_index].requested = false;
+
+        if let Some(cache) = self.take_cache(cache_index) {
+            // This cannot fail because we made sure that a slot is available in
+            // the previous step.
+            let _ = self
+                .to_server_tx
+                .push(ClientToServerMsg::DisposeCache { cache });
+        }
+
+        Ok(true)
+    }
+
+    /// Release every cache in this stream. See `ReadDiskStream::uncache()`.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// If an error is returned, then some of the caches may not have been released.
+    pub fn clear_caches(&mut self) -> Result<(), ReadError<D::FatalError>> {
+        for cache_index in 0..self.num_caches() {
+            self.uncache(cache_index)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the cache with the given index has been filled and can be seeked to
+    /// without buffering, false otherwise.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// See `ReadStreamOptions::on_cache_ready` to be notified when a cache has been filled
+    /// instead.
+    pub fn is_cache_ready(&mut self, cache_index: usize) -> Result<bool, ReadError<D::FatalError>> {
+        self.poll()?;
+
+        Ok(matches!(
+            self.cache_info(cache_index)?,
+            CacheInfo::Ready { .. }
+        ))
+    }
+
+    /// Return the state of the cache with the given index.
+    ///
+    /// This is realtime-safe.
+    pub fn cache_info(&self, cache_index: usize) -> Result<CacheInfo, ReadError<D::FatalError>> {
+        let Some(heap) = self.heap_data.as_ref() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return Ok(CacheInfo::Empty);
+        };
+
+        if cache_index >= heap.caches.len() - 2 {
+            return Err(ReadError::CacheIndexOutOfRange {
+                index: cache_index,
+                num_caches: heap.caches.len() - 2,
+            });
+        }
+
+        let entry = &heap.caches[cache_index];
+        let start_frame = entry.wanted_start_frame;
+
+        let evicted = entry
+            .cache
+            .as_ref()
+            .map_or(false, |cache| cache.is_evicted());
+
+        Ok(if !entry.requested || evicted {
+            CacheInfo::Empty
+        } else if entry.is_usable() {
+            CacheInfo::Ready { start_frame }
+        } else {
+            CacheInfo::Pending { start_frame }
+        })
+    }
+
+    /// Take the cache with the given index out of its slot.
+    ///
+    /// If any blocks are currently using this cache, then it is moved to the temporary
+    /// cache instead and `None` is returned. The message channel must have at-least two
+    /// slots open, plus one for every prefetch block.
+    fn take_cache(&mut self, cache_index: usize) -> Option<DataBlockCache<D::T>> {
+        let heap = self.heap_data.as_mut()?;
+
+        let mut cache = heap.caches[cache_index].cache.take();
+
+        // If any blocks are currently using this cache, then set this cache as the
+        // temporary cache and tell each block to use that instead.
+        let mut using_cache = false;
+        let mut using_temp_cache = false;
+        for block in heap.prefetch_buffer.iter_mut() {
+            if let Some(index) = block.use_cache_index {
+                if index == cache_index {
+                    block.use_cache_index = Some(self.temp_cache_index);
+                    using_cache = true;
+                } else if index == self.temp_cache_index {
+                    using_temp_cache = true;
+                }
+            }
+        }
+        if using_cache {
+            if let Some(old_cache) = heap.caches[self.temp_cache_index].cache.take() {
+                // If any blocks are currently using the old temporary cache, dispose those blocks.
+                if using_temp_cache {
+                    for block in heap.prefetch_buffer.iter_mut() {
+                        if let Some(index) = block.use_cache_index {
+                            if index == self.temp_cache_index {
+                                block.use_cache_index = None;
+                                if let Some(block) = block.block.take() {
+                                    // Tell the server to deallocate the old block.
+                                    // This cannot fail because the caller made sure that enough
+                                    // slots are available.
+                                    let _ = self
+                                        .to_server_tx
+                                        .push(ClientToServerMsg::DisposeBlock { block });
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Tell the server to deallocate the old temporary cache.
+                // This cannot fail because the caller made sure that a slot is available.
+                let _ = self
+                    .to_server_tx
+                    .push(ClientToServerMsg::DisposeCache { cache: old_cache });
+            }
+
+            heap.caches[self.temp_cache_index].cache = cache.take();
+        }
+
+        cache
+    }
+
     /// Request to seek playback to a new position in the file.
     ///
     /// This is realtime-safe.
     ///
     /// * `frame` - The position in the file to seek to. If this lies outside of the end of
-    ///    the file, then playback will return silence.
+    ///   the file, then playback will return silence.
     /// * `seek_mode` - Describes how to search for a suitable cache to use.
     ///
     /// If a suitable cache is found, then (true) is returned meaning that playback can resume immediately
@@ -401,15 +755,135 @@ impl<D: Decoder> ReadDiskStream<D> {
         }
 
         // Check that enough message slots are open.
-        if self.to_server_tx.slots() < 3 + self.num_prefetch_blocks {
+        if self.to_server_tx.slots()
+            < 3 + self.num_prefetch_blocks + usize::from(self.hibernating.is_some())
+        {
             return Err(ReadError::IOServerChannelFull);
         }
 
+        // Seeking wakes the stream up from hibernation.
+        if self.hibernating.is_some() {
+            // This cannot fail because we made sure that a slot is available in
+            // the previous step.
+            let _ = self.to_server_tx.push(ClientToServerMsg::Wake);
+            self.hibernating = None;
+        }
+
+        // Seeking again cancels any fade or held seek in progress.
+        self.fade = None;
+        self.held_seek = None;
+
+        if let Some(look_ahead) = &mut self.look_ahead {
+            look_ahead.on_seek();
+        }
+        let requested_at = self.look_ahead.as_ref().map(|_| Instant::now());
+
+        // Any blocks requested before this seek are no longer needed.
+        let seek_count = self
+            .seek_count
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1);
+
+        let found_cache = self.find_cache(frame, seek_mode);
+
         let Some(heap) = self.heap_data.as_mut() else {
             // This will never return here because `heap_data` can only be `None` in the destructor.
             return Ok(false);
         };
 
+        if let Some(cache_index) = found_cache {
+            if let Some(cache) = &heap.caches[cache_index].cache {
+                cache.touch();
+            }
+
+            // Find the position in the old cache.
+            let cache_start_frame = heap.caches[cache_index].wanted_start_frame;
+            let mut delta = frame - cache_start_frame;
+            let mut block_i = 0;
+            while delta >= self.block_size {
+                block_i += 1;
+                delta -= self.block_size
+            }
+
+            self.current_block_start_frame = cache_start_frame + (block_i * self.block_size);
+            self.current_frame_in_block = delta;
+            self.current_block_index = block_i;
+            self.next_block_index = block_i + 1;
+            if self.next_block_index >= self.num_prefetch_blocks {
+                self.next_block_index = 0;
+            }
+
+            // Tell remaining blocks to use the cache.
+            for i in block_i..heap.prefetch_buffer.len() {
+                heap.prefetch_buffer[i].use_cache_index = Some(cache_index);
+            }
+
+            // Request the server to start fetching blocks ahead of the cache.
+            // This cannot fail because we made sure that a slot is available in
+            // the previous step.
+            let mut wanted_start_frame = cache_start_frame + self.prefetch_size;
+            let _ = self.to_server_tx.push(ClientToServerMsg::SeekTo {
+                frame: wanted_start_frame,
+                seek_count,
+            });
+
+            // Fetch remaining blocks.
+            for i in 0..block_i {
+                // This cannot fail because we made sure there are enough slots available
+                // in the previous step.
+                let _ = self.to_server_tx.push(ClientToServerMsg::ReadIntoBlock {
+                    block_index: i,
+                    block: heap.prefetch_buffer[i].block.take(),
+                    start_frame: wanted_start_frame,
+                    seek_count,
+                });
+                heap.prefetch_buffer[i].use_cache_index = None;
+                heap.prefetch_buffer[i].wanted_start_frame = wanted_start_frame;
+                heap.prefetch_buffer[i].requested_at = requested_at;
+                wanted_start_frame += self.block_size;
+            }
+
+            Ok(true)
+        } else {
+            // Create a new temporary seek cache.
+            // This cannot fail because we made sure that a slot is available in
+            // the previous step.
+            heap.caches[self.temp_seek_cache_index].wanted_start_frame = frame;
+            heap.caches[self.temp_seek_cache_index].requested = true;
+            let _ = self.to_server_tx.push(ClientToServerMsg::Cache {
+                cache_index: self.temp_seek_cache_index,
+                cache: heap.caches[self.temp_seek_cache_index].cache.take(),
+                start_frame: frame,
+                seek_count: Some(seek_count),
+            });
+
+            // Start from beginning of new cache.
+            self.current_block_start_frame = frame;
+            self.current_frame_in_block = 0;
+            self.current_block_index = 0;
+            self.next_block_index = 1;
+
+            // Request the server to start fetching blocks ahead of the cache.
+            // This cannot fail because we made sure that a slot is available in
+            // the previous step.
+            let _ = self.to_server_tx.push(ClientToServerMsg::SeekTo {
+                frame: self.current_block_start_frame + self.prefetch_size,
+                seek_count,
+            });
+
+            // Tell each prefetch block to use the cache.
+            for block in heap.prefetch_buffer.iter_mut() {
+                block.use_cache_index = Some(self.temp_seek_cache_index);
+            }
+
+            Ok(false)
+        }
+    }
+
+    /// Return the index of a cache that holds `frame`, searched for with `seek_mode`.
+    fn find_cache(&self, frame: usize, seek_mode: SeekMode) -> Option<usize> {
+        let heap = self.heap_data.as_ref()?;
+
         let mut found_cache = None;
 
         if let Some(cache_index) = match seek_mode {
@@ -417,7 +891,7 @@ impl<D: Decoder> ReadDiskStream<D> {
             SeekMode::TryOneThenAuto(cache_index) => Some(cache_index),
             _ => None,
         } {
-            if heap.caches[cache_index].cache.is_some() {
+            if heap.caches[cache_index].is_usable() {
                 let cache_start_frame = heap.caches[cache_index].wanted_start_frame;
                 if frame == cache_start_frame
                     || (frame > cache_start_frame && frame < cache_start_frame + self.cache_size)
@@ -427,106 +901,508 @@ impl<D: Decoder> ReadDiskStream<D> {
             }
         }
 
-        if found_cache.is_none() {
-            let auto_search = match seek_mode {
-                SeekMode::Auto | SeekMode::TryOneThenAuto(_) => true,
-                SeekMode::NoCache | SeekMode::TryOne(_) => false,
-            };
+        if found_cache.is_none() {
+            let auto_search = match seek_mode {
+                SeekMode::Auto | SeekMode::TryOneThenAuto(_) => true,
+                SeekMode::NoCache | SeekMode::TryOne(_) => false,
+            };
+
+            if auto_search {
+                // Check previous caches.
+                for i in 0..heap.caches.len() - 2 {
+                    if heap.caches[i].is_usable() {
+                        let cache_start_frame = heap.caches[i].wanted_start_frame;
+                        if frame == cache_start_frame
+                            || (frame > cache_start_frame
+                                && frame < cache_start_frame + self.cache_size)
+                        {
+                            found_cache = Some(i);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        found_cache
+    }
+
+    /// Request to seek playback to a new position in the file, and fade from the old
+    /// position to the new one to avoid clicks.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// * `frame` - The position in the file to seek to. If this lies outside of the end of
+    ///   the file, then playback will return silence.
+    /// * `seek_mode` - Describes how to search for a suitable cache to use.
+    /// * `fade` - Describes how to fade between the two positions.
+    ///
+    /// This returns the same as `ReadDiskStream::seek()`. The fade is applied to the data
+    /// returned from the next calls to `ReadDiskStream::read()`, and it is cancelled by any
+    /// later seek.
+    ///
+    /// If `fade.hold_on_miss` is `true` and no cache holds `frame`, then the stream is not
+    /// seeked until the new position has been cached. `ReadDiskStream::read()` keeps
+    /// playing from the old position until then, and then crossfades to the new one.
+    pub fn seek_with_fade(
+        &mut self,
+        frame: usize,
+        seek_mode: SeekMode,
+        fade: SeekFade,
+    ) -> Result<bool, ReadError<D::FatalError>>
+    where
+        D::T: FadeSample,
+    {
+        if self.fatal_error {
+            return Err(ReadError::FatalError(FatalReadError::StreamClosed));
+        }
+
+        let fade_frames = fade.frames.min(self.block_size);
+
+        if fade.hold_on_miss
+            && self.hibernating.is_none()
+            && self.find_cache(frame, seek_mode).is_none()
+        {
+            self.hold_seek(frame, FadeState::new(fade_frames, 0))?;
+            return Ok(false);
+        }
+
+        let old_len = self.store_fade_frames(fade_frames);
+
+        let found_cache = self.seek(frame, seek_mode)?;
+
+        if fade_frames > 0 {
+            self.fade = Some(FadeState::new(fade_frames, old_len));
+        }
+
+        Ok(found_cache)
+    }
+
+    /// Request the temporary seek cache at `frame` while the old position keeps playing.
+    /// `ReadDiskStream::read()` seeks to it and starts the fade once it is filled.
+    fn hold_seek(
+        &mut self,
+        frame: usize,
+        fade: FadeState<D::T>,
+    ) -> Result<(), ReadError<D::FatalError>> {
+        // Check that enough message slots are open.
+        if self.to_server_tx.slots() < 3 + self.num_prefetch_blocks {
+            return Err(ReadError::IOServerChannelFull);
+        }
+
+        // A previous seek may still be playing from the temporary seek cache, in which
+        // case it is moved to the temporary cache.
+        let cache = self.take_cache(self.temp_seek_cache_index);
+
+        let Some(heap) = self.heap_data.as_mut() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return Ok(());
+        };
+
+        // The request is skipped if the stream is seeked before it is filled.
+        let seek_count = self.seek_count.load(Ordering::Relaxed);
+
+        // This cannot fail because we made sure that a slot is available in
+        // the previous step.
+        heap.caches[self.temp_seek_cache_index].wanted_start_frame = frame;
+        heap.caches[self.temp_seek_cache_index].requested = true;
+        let _ = self.to_server_tx.push(ClientToServerMsg::Cache {
+            cache_index: self.temp_seek_cache_index,
+            cache,
+            start_frame: frame,
+            seek_count: Some(seek_count),
+        });
+
+        self.held_seek = Some(HeldSeek { frame, fade });
+
+        Ok(())
+    }
+
+    /// If the cache of a held seek has been filled, then seek to it and start the fade.
+    fn finish_held_seek(&mut self) {
+        let Some(held_seek) = self.held_seek else {
+            return;
+        };
+        let Some(heap) = self.heap_data.as_ref() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return;
+        };
+
+        let cache = &heap.caches[self.temp_seek_cache_index];
+        if !cache.is_usable() || cache.wanted_start_frame != held_seek.frame {
+            return;
+        }
+
+        // Make sure the seek cannot fail before storing the old frames.
+        if self.to_server_tx.slots() < 3 + self.num_prefetch_blocks {
+            return;
+        }
+
+        let old_len = self.store_fade_frames(held_seek.fade.frames);
+
+        if self
+            .seek(
+                held_seek.frame,
+                SeekMode::TryOne(self.temp_seek_cache_index),
+            )
+            .is_ok()
+            && held_seek.fade.frames > 0
+        {
+            self.fade = Some(FadeState {
+                old_len,
+                ..held_seek.fade
+            });
+        }
+    }
+
+    /// Store up to `fade_frames` frames that would have been played from the current
+    /// position in the fade buffer, and return the number of frames that were stored.
+    fn store_fade_frames(&mut self, fade_frames: usize) -> usize {
+        let old_len = fade_frames.min(self.file_info.num_frames.saturating_sub(self.playhead()));
+
+        if let Some(heap) = self.heap_data.as_mut() {
+            let HeapData {
+                fade_buffer,
+                prefetch_buffer,
+                caches,
+                ..
+            } = heap;
+
+            fade_buffer.clear();
+
+            let first_len = old_len.min(self.block_size - self.current_frame_in_block);
+            copy_block_into_buffer(
+                fade_buffer,
+                prefetch_buffer,
+                caches,
+                self.current_block_index,
+                self.current_frame_in_block,
+                first_len,
+            );
+            if old_len > first_len {
+                copy_block_into_buffer(
+                    fade_buffer,
+                    prefetch_buffer,
+                    caches,
+                    self.next_block_index,
+                    0,
+                    old_len - first_len,
+                );
+            }
+        }
+
+        old_len
+    }
+
+    /// Move the scrub position to a new frame in the file, starting scrub mode if it is
+    /// not already started.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// In scrub mode, the IO server decodes a window of one block around the scrub position
+    /// as soon as it can, and `ReadDiskStream::read_scrub()` returns audio from that window.
+    /// Only one window is requested from the IO server at a time, and any calls made while
+    /// a window is being decoded are coalesced into a single request for the latest frame.
+    /// This means that this can be called on every drag event of a playhead without ever
+    /// filling up the message channel.
+    ///
+    /// Scrubbing does not move the playhead used by `ReadDiskStream::read()`. Once scrubbing
+    /// is done, call `ReadDiskStream::stop_scrub()` and seek to `ReadDiskStream::scrub_position()`
+    /// to resume playback from there.
+    ///
+    /// The windows are decoded with the second decoder that is opened with
+    /// `ReadStreamOptions::cache_decoder_opts`, so that the decoder that streams from the
+    /// playhead never has to seek away. If the stream was opened without one, then
+    /// `ReadError::ScrubUnavailable` is returned.
+    pub fn scrub_to(&mut self, frame: usize) -> Result<(), ReadError<D::FatalError>> {
+        if self.fatal_error {
+            return Err(ReadError::FatalError(FatalReadError::StreamClosed));
+        }
+        if !self.can_scrub {
+            return Err(ReadError::ScrubUnavailable);
+        }
+
+        match &mut self.scrub {
+            Some(scrub) => scrub.pending_target = Some(frame),
+            None => self.scrub = Some(ScrubState::new(frame)),
+        }
+
+        self.request_scrub_window();
+
+        Ok(())
+    }
+
+    /// Read the next chunk of `frames` in scrub mode. See `ReadDiskStream::scrub_to()`.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// * `frames` - The number of frames to read.
+    /// * `rate` - How many frames the scrub position moves per frame that is read. A rate of
+    ///   `1.0` plays at normal speed, a rate between `0.0` and `1.0` plays slower, and a
+    ///   negative rate plays backwards. Frames in between two frames in the file are
+    ///   linearly interpolated.
+    ///
+    /// When the scrub position gets close to the edge of the window, a new window around it
+    /// is requested so that jogging at a constant rate keeps playing. Any frames that lie
+    /// outside of the window that has been received so far are silent.
+    ///
+    /// If scrub mode has not been started, then this returns silence.
+    ///
+    /// NOTE: If the number of `frames` exceeds the block size of the decoder, then that block size
+    /// will be used instead. This can be retrieved using `ReadDiskStream::block_size()`.
+    pub fn read_scrub(
+        &mut self,
+        mut frames: usize,
+        rate: f64,
+    ) -> Result<ReadData<'_, D::T>, ReadError<D::FatalError>>
+    where
+        D::T: FadeSample,
+    {
+        if self.fatal_error {
+            return Err(ReadError::FatalError(FatalReadError::StreamClosed));
+        }
+
+        frames = frames.min(self.block_size);
+
+        self.poll()?;
+
+        let Some(heap) = self.heap_data.as_mut() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return Err(ReadError::IOServerChannelFull);
+        };
+
+        heap.read_buffer.clear();
+
+        match &mut self.scrub {
+            Some(scrub) => {
+                let position = scrub_into(
+                    &mut heap.read_buffer,
+                    heap.scrub_window.as_ref(),
+                    scrub.window_start,
+                    scrub.position,
+                    rate,
+                    frames,
+                );
+
+                scrub.position = position.clamp(0.0, self.file_info.num_frames as f64);
+            }
+            None => {
+                for ch in heap.read_buffer.append_frames(frames) {
+                    ch.fill(D::T::SILENCE);
+                }
+            }
+        }
+
+        self.request_scrub_window();
+
+        let Some(heap) = self.heap_data.as_ref() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return Err(ReadError::IOServerChannelFull);
+        };
+
+        Ok(ReadData::new(&heap.read_buffer, frames, false))
+    }
+
+    /// Returns true if the window around the frame that was last scrubbed to has been
+    /// received, false otherwise (or if scrub mode has not been started).
+    ///
+    /// This is realtime-safe.
+    pub fn is_scrub_ready(&mut self) -> Result<bool, ReadError<D::FatalError>> {
+        self.poll()?;
+
+        let has_window = self
+            .heap_data
+            .as_ref()
+            .map_or(false, |heap| heap.scrub_window.is_some());
+
+        Ok(self.scrub.map_or(false, |scrub| {
+            // A window that follows the current position does not move the position.
+            let target_in_flight = scrub.in_flight.and_then(|request| request.target);
+
+            has_window && scrub.pending_target.is_none() && target_in_flight.is_none()
+        }))
+    }
+
+    /// Return the current scrub position, or `None` if scrub mode has not been started.
+    ///
+    /// This is realtime-safe.
+    pub fn scrub_position(&self) -> Option<usize> {
+        self.scrub.map(|scrub| scrub.position as usize)
+    }
+
+    /// Stop scrub mode. Any window that is still being decoded is ignored.
+    ///
+    /// This is realtime-safe.
+    pub fn stop_scrub(&mut self) {
+        self.scrub = None;
+    }
+
+    /// Request a new scrub window from the IO server if one is needed and no other window
+    /// is being decoded.
+    ///
+    /// If the message channel is too full, then the request is made the next time the
+    /// stream is polled instead.
+    fn request_scrub_window(&mut self) {
+        let Some(heap) = self.heap_data.as_mut() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return;
+        };
+        let Some(scrub) = self.scrub.as_mut() else {
+            return;
+        };
+
+        if scrub.in_flight.is_some() {
+            return;
+        }
+
+        let (center, target) = if let Some(target) = scrub.pending_target {
+            (target, Some(target))
+        } else {
+            match &heap.scrub_window {
+                Some(window)
+                    if scrub.needs_next_window(
+                        window.num_frames(),
+                        self.block_size,
+                        self.file_info.num_frames,
+                    ) =>
+                {
+                    (scrub.position as usize, None)
+                }
+                _ => return,
+            }
+        };
+
+        // Always leave a slot open for reading and seeking.
+        if self.to_server_tx.slots() < 2 {
+            return;
+        }
+
+        let start_frame = center.saturating_sub(self.block_size / 2);
+
+        // This cannot fail because we made sure that a slot is available in
+        // the previous step.
+        let _ = self.to_server_tx.push(ClientToServerMsg::Scrub {
+            block: heap.scrub_spare.take(),
+            start_frame,
+        });
+
+        scrub.pending_target = None;
+        scrub.in_flight = Some(ScrubRequest {
+            start_frame,
+            target,
+        });
+    }
+
+    /// Put the stream into hibernation to free most of its memory while it is idle.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// * `keep_caches` - The indexes of the caches to keep, i.e. a cache of the start of
+    ///   the file that was shared with `ReadDiskStream::head_cache()`.
+    ///
+    /// Every prefetch block, the scrub window, and every cache that is not kept are given
+    /// back to the IO server to be deallocated, along with any blocks and caches the IO
+    /// server keeps around to be reused. Scrub mode is stopped and any fade or held seek in
+    /// progress is cancelled.
+    ///
+    /// While hibernating, `ReadDiskStream::is_ready()` returns false and
+    /// `ReadDiskStream::read()` returns silence without advancing the playhead. Call
+    /// `ReadDiskStream::wake()` or seek to resume.
+    ///
+    /// If an error is returned, then nothing was done.
+    pub fn hibernate(&mut self, keep_caches: &[usize]) -> Result<(), ReadError<D::FatalError>>
+    where
+        D::T: FadeSample,
+    {
+        if self.fatal_error {
+            return Err(ReadError::FatalError(FatalReadError::StreamClosed));
+        }
+
+        let Some(heap) = self.heap_data.as_mut() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return Ok(());
+        };
 
-            if auto_search {
-                // Check previous caches.
-                for i in 0..heap.caches.len() - 2 {
-                    if heap.caches[i].cache.is_some() {
-                        let cache_start_frame = heap.caches[i].wanted_start_frame;
-                        if frame == cache_start_frame
-                            || (frame > cache_start_frame
-                                && frame < cache_start_frame + self.cache_size)
-                        {
-                            found_cache = Some(i);
-                            break;
-                        }
-                    }
-                }
-            }
+        let num_caches = heap.caches.len() - 2;
+        if let Some(&index) = keep_caches.iter().find(|&&index| index >= num_caches) {
+            return Err(ReadError::CacheIndexOutOfRange { index, num_caches });
         }
 
-        if let Some(cache_index) = found_cache {
-            // Find the position in the old cache.
-            let cache_start_frame = heap.caches[cache_index].wanted_start_frame;
-            let mut delta = frame - cache_start_frame;
-            let mut block_i = 0;
-            while delta >= self.block_size {
-                block_i += 1;
-                delta -= self.block_size
-            }
+        // Check that there is a slot for every block and cache, two scrub blocks, the
+        // lent block, and the hibernate message.
+        if self.to_server_tx.slots() < self.num_prefetch_blocks + heap.caches.len() + 4 {
+            return Err(ReadError::IOServerChannelFull);
+        }
 
-            self.current_block_start_frame = cache_start_frame + (block_i * self.block_size);
-            self.current_frame_in_block = delta;
-            self.current_block_index = block_i;
-            self.next_block_index = block_i + 1;
-            if self.next_block_index >= self.num_prefetch_blocks {
-                self.next_block_index = 0;
-            }
+        // Any blocks that are still requested are no longer needed.
+        self.seek_count.fetch_add(1, Ordering::Relaxed);
+        self.fade = None;
+        self.held_seek = None;
+        self.scrub = None;
+
+        // The blocks are deallocated once the server receives the hibernate message.
+        // This cannot fail because we made sure there are enough slots available
+        // in the previous step.
+        let prefetch_blocks = heap.prefetch_buffer.iter_mut().filter_map(|entry| {
+            entry.use_cache_index = None;
+            entry.block.take()
+        });
+        let scrub_blocks = heap
+            .scrub_window
+            .take()
+            .into_iter()
+            .chain(heap.scrub_spare.take())
+            .chain(heap.lent_block.take());
+        for block in prefetch_blocks.chain(scrub_blocks) {
+            let _ = self
+                .to_server_tx
+                .push(ClientToServerMsg::DisposeBlock { block });
+        }
 
-            // Tell remaining blocks to use the cache.
-            for i in block_i..heap.prefetch_buffer.len() {
-                heap.prefetch_buffer[i].use_cache_index = Some(cache_index);
+        // Release every cache that is not kept, including the temporary caches.
+        for (cache_index, cache_entry) in heap.caches.iter_mut().enumerate() {
+            if keep_caches.contains(&cache_index) {
+                continue;
             }
 
-            // Request the server to start fetching blocks ahead of the cache.
-            // This cannot fail because we made sure that a slot is available in
-            // the previous step.
-            let mut wanted_start_frame = cache_start_frame + self.prefetch_size;
-            let _ = self.to_server_tx.push(ClientToServerMsg::SeekTo {
-                frame: wanted_start_frame,
-            });
+            // Any result from the server for this cache will now be ignored.
+            cache_entry.requested = false;
 
-            // Fetch remaining blocks.
-            for i in 0..block_i {
-                // This cannot fail because we made sure there are enough slots available
-                // in the previous step.
-                let _ = self.to_server_tx.push(ClientToServerMsg::ReadIntoBlock {
-                    block_index: i,
-                    block: heap.prefetch_buffer[i].block.take(),
-                    start_frame: wanted_start_frame,
-                });
-                heap.prefetch_buffer[i].use_cache_index = None;
-                heap.prefetch_buffer[i].wanted_start_frame = wanted_start_frame;
-                wanted_start_frame += self.block_size;
+            if let Some(cache) = cache_entry.cache.take() {
+                let _ = self
+                    .to_server_tx
+                    .push(ClientToServerMsg::DisposeCache { cache });
             }
+        }
 
-            Ok(true)
-        } else {
-            // Create a new temporary seek cache.
-            // This cannot fail because we made sure that a slot is available in
-            // the previous step.
-            heap.caches[self.temp_seek_cache_index].wanted_start_frame = frame;
-            let _ = self.to_server_tx.push(ClientToServerMsg::Cache {
-                cache_index: self.temp_seek_cache_index,
-                cache: heap.caches[self.temp_seek_cache_index].cache.take(),
-                start_frame: frame,
-            });
+        let _ = self.to_server_tx.push(ClientToServerMsg::Hibernate);
+        self.hibernating = Some(D::T::SILENCE);
 
-            // Start from beginning of new cache.
-            self.current_block_start_frame = frame;
-            self.current_frame_in_block = 0;
-            self.current_block_index = 0;
-            self.next_block_index = 1;
+        Ok(())
+    }
 
-            // Request the server to start fetching blocks ahead of the cache.
-            // This cannot fail because we made sure that a slot is available in
-            // the previous step.
-            let _ = self.to_server_tx.push(ClientToServerMsg::SeekTo {
-                frame: self.current_block_start_frame + self.prefetch_size,
-            });
+    /// Wake the stream up from hibernation, and start prefetching from the playhead again.
+    /// See `ReadDiskStream::hibernate()`.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// This returns the same as `ReadDiskStream::seek()`, so (true) is returned if one of the
+    /// kept caches holds the playhead and playback can resume without buffering. If the
+    /// stream is not hibernating, then nothing is done and this returns whether the stream
+    /// is ready.
+    pub fn wake(&mut self) -> Result<bool, ReadError<D::FatalError>> {
+        if self.hibernating.is_none() {
+            return self.is_ready();
+        }
 
-            // Tell each prefetch block to use the cache.
-            for block in heap.prefetch_buffer.iter_mut() {
-                block.use_cache_index = Some(self.temp_seek_cache_index);
-            }
+        self.seek(self.playhead(), SeekMode::Auto)
+    }
 
-            Ok(false)
-        }
+    /// Returns true if the stream is hibernating. See `ReadDiskStream::hibernate()`.
+    ///
+    /// This is realtime-safe.
+    pub fn is_hibernating(&self) -> bool {
+        self.hibernating.is_some()
     }
 
     /// Returns true if the stream is finished buffering and there is data can be read
@@ -543,46 +1419,66 @@ impl<D: Decoder> ReadDiskStream<D> {
             return Ok(false);
         }
 
-        let Some(heap) = self.heap_data.as_mut() else {
-            // This will never return here because `heap_data` can only be `None` in the destructor.
-            return Ok(false);
-        };
+        Ok(self.blocks_ready())
+    }
 
-        // Check if the next two blocks are ready.
+    /// Returns true if the next two blocks have been received.
+    fn blocks_ready(&self) -> bool {
+        self.block_ready(self.current_block_index) && self.block_ready(self.next_block_index)
+    }
 
-        if let Some(cache_index) = heap.prefetch_buffer[self.current_block_index].use_cache_index {
-            // This check should never fail because it can only be `None` in the destructor.
-            if heap.caches[cache_index].cache.is_none() {
-                // Cache has not been received yet.
-                return Ok(false);
-            }
-        } else if heap.prefetch_buffer[self.current_block_index]
-            .block
-            .is_none()
-        {
-            // Block has not been received yet.
+    /// Returns true if the stream is not buffering and the blocks that hold the next
+    /// `frames` frames have been received, so that they can be read right now.
+    ///
+    /// This is realtime-safe.
+    pub(crate) fn frames_ready(&mut self, frames: usize) -> Result<bool, ReadError<D::FatalError>> {
+        self.poll()?;
+
+        if self.to_server_tx.is_full() {
+            return Ok(false);
+        }
+
+        if !self.block_ready(self.current_block_index) {
             return Ok(false);
         }
 
-        if let Some(cache_index) = heap.prefetch_buffer[self.next_block_index].use_cache_index {
+        let frames = frames.min(self.block_size);
+        Ok(self.current_frame_in_block + frames <= self.block_size
+            || self.block_ready(self.next_block_index))
+    }
+
+    /// Returns true if the prefetch block at `index` has been received.
+    fn block_ready(&self, index: usize) -> bool {
+        let Some(heap) = self.heap_data.as_ref() else {
+            // This will never return here because `heap_data` can only be `None` in the destructor.
+            return false;
+        };
+
+        if let Some(cache_index) = heap.prefetch_buffer[index].use_cache_index {
             // This check should never fail because it can only be `None` in the destructor.
             if heap.caches[cache_index].cache.is_none() {
                 // Cache has not been received yet.
-                return Ok(false);
+                return false;
             }
-        } else if heap.prefetch_buffer[self.next_block_index].block.is_none() {
+        } else if heap.prefetch_buffer[index].block.is_none() {
             // Block has not been received yet.
-            return Ok(false);
+            return false;
         }
 
-        Ok(true)
+        true
     }
 
     /// Blocks the current thread until the stream is done buffering.
     ///
     /// NOTE: This is ***not*** realtime-safe. This is only useful
     /// for making sure a stream is ready before sending it to a realtime thread.
+    ///
+    /// If the stream is hibernating, then it is woken up first.
     pub fn block_until_ready(&mut self) -> Result<(), ReadError<D::FatalError>> {
+        if self.hibernating.is_some() {
+            self.wake()?;
+        }
+
         loop {
             if self.is_ready()? {
                 break;
@@ -633,7 +1529,7 @@ impl<D: Decoder> ReadDiskStream<D> {
         while frames_written < buffer_len {
             let mut reached_end_of_file = false;
 
-            while self.is_ready()? {
+            while frames_written < buffer_len && self.is_ready()? {
                 let read_frames = (buffer_len - frames_written).min(self.block_size);
 
                 let read_data = self.read(read_frames)?;
@@ -684,11 +1580,24 @@ impl<D: Decoder> ReadDiskStream<D> {
                         block_index,
                         block,
                         wanted_start_frame,
+                        decoded_at,
                     } => {
-                        let prefetch_block = &mut heap.prefetch_buffer[block_index];
+                        // The block may have been removed from a shrinking look-ahead since
+                        // it was requested, and no blocks are kept while hibernating.
+                        let prefetch_block = heap
+                            .prefetch_buffer
+                            .get_mut(block_index)
+                            .filter(|entry| entry.wanted_start_frame == wanted_start_frame)
+                            .filter(|_| self.hibernating.is_none());
 
                         // Only use results from the latest request.
-                        if wanted_start_frame == prefetch_block.wanted_start_frame {
+                        if let Some(prefetch_block) = prefetch_block {
+                            if let (Some(look_ahead), Some(requested_at)) =
+                                (&mut self.look_ahead, prefetch_block.requested_at.take())
+                            {
+                                look_ahead.on_block_received(requested_at, decoded_at);
+                            }
+
                             if let Some(prefetch_block) = prefetch_block.block.take() {
                                 // Tell the IO server to deallocate the old block.
                                 // This cannot fail because we made sure that a slot is available in
@@ -717,7 +1626,9 @@ impl<D: Decoder> ReadDiskStream<D> {
                         let cache_entry = &mut heap.caches[cache_index];
 
                         // Only use results from the latest request.
-                        if wanted_start_frame == cache_entry.wanted_start_frame {
+                        if cache_entry.requested
+                            && wanted_start_frame == cache_entry.wanted_start_frame
+                        {
                             if let Some(cache_entry) = cache_entry.cache.take() {
                                 // Tell the IO server to deallocate the old cache.
                                 // This cannot fail because we made sure that a slot is available in
@@ -738,6 +1649,40 @@ impl<D: Decoder> ReadDiskStream<D> {
                                 .push(ClientToServerMsg::DisposeCache { cache });
                         }
                     }
+                    ServerToClientMsg::ScrubRes { block, start_frame } => {
+                        // Only use results from the latest request.
+                        let scrub = self.scrub.as_mut().filter(|scrub| {
+                            scrub
+                                .in_flight
+                                .map_or(false, |request| request.start_frame == start_frame)
+                        });
+
+                        let old_block = if let Some(scrub) = scrub {
+                            if let Some(target) = scrub.in_flight.and_then(|r| r.target) {
+                                scrub.position = target as f64;
+                            }
+                            scrub.in_flight = None;
+                            scrub.window_start = start_frame;
+
+                            heap.scrub_window.replace(block)
+                        } else {
+                            Some(block)
+                        };
+
+                        // Keep the old block to reuse for the next window.
+                        if let Some(old_block) = old_block {
+                            if heap.scrub_spare.is_none() && self.hibernating.is_none() {
+                                heap.scrub_spare = Some(old_block);
+                            } else {
+                                // Tell the server to deallocate the block.
+                                // This cannot fail because we made sure that a slot is available in
+                                // a previous step.
+                                let _ = self
+                                    .to_server_tx
+                                    .push(ClientToServerMsg::DisposeBlock { block: old_block });
+                            }
+                        }
+                    }
                     ServerToClientMsg::FatalError(e) => {
                         self.fatal_error = true;
                         return Err(ReadError::FatalError(FatalReadError::DecoderError(e)));
@@ -748,6 +1693,36 @@ impl<D: Decoder> ReadDiskStream<D> {
             }
         }
 
+        // Dispose of any caches that were evicted from the memory budget once playback no
+        // longer relies on them.
+        for cache_index in 0..heap.caches.len() {
+            let evicted = heap.caches[cache_index]
+                .cache
+                .as_ref()
+                .map_or(false, |cache| cache.is_evicted());
+            let in_use = heap
+                .prefetch_buffer
+                .iter()
+                .any(|block| block.use_cache_index == Some(cache_index));
+
+            if evicted && !in_use {
+                if self.to_server_tx.is_full() {
+                    return Err(ReadError::IOServerChannelFull);
+                }
+
+                heap.caches[cache_index].requested = false;
+                if let Some(cache) = heap.caches[cache_index].cache.take() {
+                    // This cannot fail because we made sure that a slot is available in
+                    // the previous step.
+                    let _ = self
+                        .to_server_tx
+                        .push(ClientToServerMsg::DisposeCache { cache });
+                }
+            }
+        }
+
+        self.request_scrub_window();
+
         Ok(())
     }
 
@@ -765,6 +1740,10 @@ impl<D: Decoder> ReadDiskStream<D> {
     /// If the end of a file is reached, then only the amount of frames up to the end will be returned,
     /// and playback will return silence on each subsequent call to `read()`.
     ///
+    /// If the frames lie within a single prefetch block, then the returned data borrows them
+    /// straight from the block without copying. Otherwise (or while fading after a seek), they
+    /// are copied into a buffer first.
+    ///
     /// NOTE: If the number of `frames` exceeds the block size of the decoder, then that block size
     /// will be used instead. This can be retrieved using `ReadDiskStream::block_size()`.
     pub fn read(
@@ -779,11 +1758,28 @@ impl<D: Decoder> ReadDiskStream<D> {
 
         self.poll()?;
 
+        if let Some(silence) = self.hibernating {
+            let Some(heap) = self.heap_data.as_mut() else {
+                // This will never return here because `heap_data` can only be `None` in the destructor.
+                return Err(ReadError::IOServerChannelFull);
+            };
+
+            heap.read_buffer.clear();
+            for ch in heap.read_buffer.append_frames(frames) {
+                ch.fill(silence);
+            }
+
+            return Ok(ReadData::new(&heap.read_buffer, frames, false));
+        }
+
         // Check that there is at-least one slot open for when `advance_to_next_block()` is called.
         if self.to_server_tx.is_full() {
             return Err(ReadError::IOServerChannelFull);
         }
 
+        // Switch to the new position of a held seek once it is ready.
+        self.finish_held_seek();
+
         // Check if the end of the file was reached.
         if self.playhead() >= self.file_info.num_frames {
             return Err(ReadError::EndOfFile);
@@ -818,7 +1814,7 @@ impl<D: Decoder> ReadDiskStream<D> {
                 );
             }
 
-            self.advance_to_next_block()?;
+            self.advance_to_next_block(false)?;
 
             // Copy from second block
             {
@@ -832,6 +1828,58 @@ impl<D: Decoder> ReadDiskStream<D> {
 
             self.current_frame_in_block = second_len;
         } else {
+            // The data can be borrowed straight from the current block if it was filled
+            // and there is no fade to mix in.
+            let block_index = self.current_block_index;
+            let start_frame_in_block = self.current_frame_in_block;
+            let mut borrow_cache_index = None;
+            let can_borrow = match self.heap_data.as_ref() {
+                Some(heap) if self.fade.is_none() => {
+                    borrow_cache_index = heap.prefetch_buffer[block_index].use_cache_index;
+                    current_block(&heap.prefetch_buffer, &heap.caches, block_index)
+                        .map_or(false, |block| block.num_frames() >= end_frame_in_block)
+                }
+                _ => false,
+            };
+
+            if can_borrow {
+                // If the block was read up to its end, then it is lent out while the
+                // stream advances.
+                let lent = end_frame_in_block == self.block_size;
+                if lent {
+                    self.advance_to_next_block(true)?;
+                    self.current_frame_in_block = 0;
+                } else {
+                    self.current_frame_in_block = end_frame_in_block;
+                }
+
+                let Some(heap) = self.heap_data.as_ref() else {
+                    // This will never return here because `heap_data` can only be `None` in the destructor.
+                    return Err(ReadError::IOServerChannelFull);
+                };
+
+                // Caches are not touched by advancing, so a block in a cache stays in place.
+                let block = match borrow_cache_index {
+                    Some(cache_index) => heap.caches[cache_index]
+                        .cache
+                        .as_ref()
+                        .map(|cache| &cache.blocks[block_index]),
+                    None if lent => heap.lent_block.as_ref(),
+                    None => heap.prefetch_buffer[block_index].block.as_ref(),
+                };
+                let Some(block) = block else {
+                    // This will never return here because the block was checked above.
+                    return Err(ReadError::IOServerChannelFull);
+                };
+
+                return Ok(ReadData::from_range(
+                    block,
+                    start_frame_in_block,
+                    frames,
+                    reached_end_of_file,
+                ));
+            }
+
             // Only need to copy from current block.
             {
                 let Some(heap) = self.heap_data.as_mut() else {
@@ -851,7 +1899,7 @@ impl<D: Decoder> ReadDiskStream<D> {
 
             self.current_frame_in_block = end_frame_in_block;
             if self.current_frame_in_block == self.block_size {
-                self.advance_to_next_block()?;
+                self.advance_to_next_block(false)?;
                 self.current_frame_in_block = 0;
             }
         }
@@ -861,6 +1909,13 @@ impl<D: Decoder> ReadDiskStream<D> {
             return Err(ReadError::IOServerChannelFull);
         };
 
+        if let Some(mut fade) = self.fade.take() {
+            fade.mix_into(&mut heap.read_buffer, &heap.fade_buffer, frames);
+            if !fade.is_done() {
+                self.fade = Some(fade);
+            }
+        }
+
         // This check should never fail because it can only be `None` in the destructor.
         Ok(ReadData::new(
             &heap.read_buffer,
@@ -869,7 +1924,30 @@ impl<D: Decoder> ReadDiskStream<D> {
         ))
     }
 
-    fn advance_to_next_block(&mut self) -> Result<(), ReadError<D::FatalError>> {
+    /// Advance to the next block, and request new data for the block that was just played.
+    ///
+    /// If `lend` is `true`, then the block that was just played is kept in the spare
+    /// block of the stream instead, since the data returned by `read` still borrows it.
+    fn advance_to_next_block(&mut self, lend: bool) -> Result<(), ReadError<D::FatalError>> {
+        // The look-ahead can only be resized when the prefetch buffer wraps around, since
+        // the last block in the buffer is then the one that is furthest ahead.
+        let num_look_ahead_blocks = self.num_look_ahead_blocks();
+        let requested_at = self.look_ahead.as_ref().map(|_| Instant::now());
+        let mut grow = false;
+        let mut shrink = false;
+        if let (Some(look_ahead), Some(now)) = (&mut self.look_ahead, requested_at) {
+            look_ahead.on_advance(now);
+
+            if self.current_block_index + 1 >= self.num_prefetch_blocks {
+                if let Some(wanted_blocks) = look_ahead.wanted_blocks() {
+                    // Growing sends an extra message to the server.
+                    grow = wanted_blocks > num_look_ahead_blocks && self.to_server_tx.slots() >= 2;
+                    shrink = wanted_blocks < num_look_ahead_blocks;
+                }
+            }
+        }
+        let seek_count = self.seek_count.load(Ordering::Relaxed);
+
         let Some(heap) = self.heap_data.as_mut() else {
             // This will never return here because `heap_data` can only be `None` in the destructor.
             return Ok(());
@@ -877,34 +1955,83 @@ impl<D: Decoder> ReadDiskStream<D> {
 
         let entry = &mut heap.prefetch_buffer[self.current_block_index];
 
-        // Request a new block of data that is one block ahead of the
-        // latest block in the prefetch buffer.
-        let wanted_start_frame = self.current_block_start_frame + (self.prefetch_size);
+        // A block that is only used through a cache is not borrowed, so it does not need
+        // to be kept.
+        let old_block = if lend && entry.use_cache_index.is_none() {
+            std::mem::replace(&mut heap.lent_block, entry.block.take())
+        } else {
+            entry.block.take()
+        };
 
-        entry.use_cache_index = None;
-        entry.wanted_start_frame = wanted_start_frame;
+        if shrink {
+            // Free the block instead of requesting new data for it. The blocks before it
+            // already hold everything up to the new end of the look-ahead.
+            // This cannot fail because the caller function `read` makes sure there
+            // is at-least one slot open before calling this function.
+            let _ = self
+                .to_server_tx
+                .push(ClientToServerMsg::FreeBlock { block: old_block });
+
+            heap.prefetch_buffer.pop();
+            self.num_prefetch_blocks -= 1;
+        } else {
+            // Request a new block of data that is one block ahead of the
+            // latest block in the prefetch buffer.
+            let wanted_start_frame = self.current_block_start_frame + (self.prefetch_size);
+
+            entry.use_cache_index = None;
+            entry.wanted_start_frame = wanted_start_frame;
+            entry.requested_at = requested_at;
+
+            // This cannot fail because the caller function `read` makes sure there
+            // is at-least one slot open before calling this function.
+            let _ = self.to_server_tx.push(ClientToServerMsg::ReadIntoBlock {
+                block_index: self.current_block_index,
+                // Send block to be re-used by the IO server.
+                block: old_block,
+                start_frame: wanted_start_frame,
+                seek_count,
+            });
+        }
 
-        // This cannot fail because the caller function `read` makes sure there
-        // is at-least one slot open before calling this function.
-        let _ = self.to_server_tx.push(ClientToServerMsg::ReadIntoBlock {
-            block_index: self.current_block_index,
-            // Send block to be re-used by the IO server.
-            block: entry.block.take(),
-            start_frame: wanted_start_frame,
-        });
+        self.current_block_start_frame += self.block_size;
 
         self.current_block_index += 1;
         if self.current_block_index >= self.num_prefetch_blocks {
             self.current_block_index = 0;
         }
 
-        self.next_block_index += 1;
+        if grow {
+            // Add a block to the end of the prefetch buffer, which comes right after the
+            // block that was just requested.
+            let wanted_start_frame = self.current_block_start_frame + self.prefetch_size;
+            let block_index = self.num_prefetch_blocks;
+
+            // This cannot fail because we made sure that a slot is available in
+            // a previous step.
+            let _ = self.to_server_tx.push(ClientToServerMsg::ReadIntoBlock {
+                block_index,
+                block: None,
+                start_frame: wanted_start_frame,
+                seek_count,
+            });
+
+            heap.prefetch_buffer.push(DataBlockEntry {
+                use_cache_index: None,
+                block: None,
+                wanted_start_frame,
+                requested_at,
+            });
+            self.num_prefetch_blocks += 1;
+        }
+
+        self.prefetch_size = self.num_prefetch_blocks * self.block_size;
+
+        self.next_block_index = self.current_block_index + 1;
         if self.next_block_index >= self.num_prefetch_blocks {
             self.next_block_index = 0;
         }
 
-        self.current_block_start_frame += self.block_size;
-
         Ok(())
     }
 
@@ -928,6 +2055,41 @@ impl<D: Decoder> ReadDiskStream<D> {
     pub fn block_size(&self) -> usize {
         self.block_size
     }
+
+    /// Return the number of bytes of prefetch blocks and caches that were allocated while
+    /// the stream was opened. This is `0` unless `ReadStreamOptions::preallocate` is set.
+    ///
+    /// This is realtime-safe.
+    pub fn preallocated_bytes(&self) -> usize {
+        self.preallocated_bytes
+    }
+
+    /// Return the number of look-ahead blocks this stream currently uses. This only
+    /// changes at runtime if `ReadStreamOptions::adaptive_look_ahead` is set.
+    ///
+    /// This is realtime-safe.
+    pub fn num_look_ahead_blocks(&self) -> usize {
+        self.num_prefetch_blocks - (self.cache_size / self.block_size)
+    }
+}
+
+impl<D: Decoder> sealed::Sealed for ReadDiskStream<D> {}
+
+impl<D: Decoder> OpenStream for ReadDiskStream<D> {
+    type Opened = OpenedFile<D>;
+    type OpenError = D::OpenError;
+    type Options = ReadDiskStreamOptions<D>;
+
+    fn finish_open(opts: Self::Options, opened: Self::Opened) -> Self {
+        Self::create(opts, opened)
+    }
+
+    fn cancel_open(mut opts: Self::Options) {
+        // Tell the server to close once it has opened the file. There is no heap
+        // data to deallocate yet.
+        // This cannot fail because this is the only place the signal is ever sent.
+        let _ = opts.close_signal_tx.push(None);
+    }
 }
 
 impl<D: Decoder> Drop for ReadDiskStream<D> {
@@ -944,48 +2106,72 @@ fn copy_block_into_read_buffer<T: Copy + Default + Send>(
     start_frame_in_block: usize,
     frames: usize,
 ) {
-    let block_entry = &heap.prefetch_buffer[block_index];
+    let HeapData {
+        read_buffer,
+        prefetch_buffer,
+        caches,
+        ..
+    } = heap;
+
+    copy_block_into_buffer(
+        read_buffer,
+        prefetch_buffer,
+        caches,
+        block_index,
+        start_frame_in_block,
+        frames,
+    );
+}
 
-    let maybe_block = match block_entry.use_cache_index {
-        Some(cache_index) => heap.caches[cache_index]
-            .cache
-            .as_ref()
-            .map(|cache| &cache.blocks[block_index]),
+/// Return the data of the given prefetch block, either from the block itself or from
+/// the cache it uses.
+fn current_block<'a, T: Copy + Default + Send>(
+    prefetch_buffer: &'a [DataBlockEntry<T>],
+    caches: &'a [DataBlockCacheEntry<T>],
+    block_index: usize,
+) -> Option<&'a DataBlock<T>> {
+    let block_entry = &prefetch_buffer[block_index];
+
+    match block_entry.use_cache_index {
+        Some(cache_index) => caches[cache_index].cache.as_ref().map(|cache| {
+            cache.touch();
+            &cache.blocks[block_index]
+        }),
         None => {
             block_entry.block.as_ref()
 
             // TODO: warn of buffer underflow.
         }
-    };
+    }
+}
 
-    let Some(block) = maybe_block else {
+fn copy_block_into_buffer<T: Copy + Default + Send>(
+    buffer: &mut DataBlock<T>,
+    prefetch_buffer: &[DataBlockEntry<T>],
+    caches: &[DataBlockCacheEntry<T>],
+    block_index: usize,
+    start_frame_in_block: usize,
+    frames: usize,
+) {
+    let Some(block) = current_block(prefetch_buffer, caches, block_index) else {
         // If no block exists, output silence.
-        for buffer_ch in heap.read_buffer.block.iter_mut() {
-            buffer_ch.resize(buffer_ch.len() + frames, Default::default());
+        for buffer_ch in buffer.append_frames(frames) {
+            buffer_ch.fill(Default::default());
         }
 
         return;
     };
 
-    for (buffer_ch, block_ch) in heap.read_buffer.block.iter_mut().zip(block.block.iter()) {
-        // If for some reason the decoder did not fill this block fully,
-        // fill the rest with zeros.
-        if block_ch.len() < start_frame_in_block + frames {
-            if block_ch.len() <= start_frame_in_block {
-                // The block has no more data to copy, fill all frames with zeros.
-                buffer_ch.resize(buffer_ch.len() + frames, Default::default());
-            } else {
-                let copy_frames = block_ch.len() - start_frame_in_block;
-
-                buffer_ch.extend_from_slice(
-                    &block_ch[start_frame_in_block..start_frame_in_block + copy_frames],
-                );
-
-                buffer_ch.resize(buffer_ch.len() + frames - copy_frames, Default::default());
-            }
-        } else {
-            buffer_ch
-                .extend_from_slice(&block_ch[start_frame_in_block..start_frame_in_block + frames]);
-        };
+    // If for some reason the decoder did not fill this block fully,
+    // fill the rest with zeros.
+    let copy_frames = block
+        .num_frames()
+        .saturating_sub(start_frame_in_block)
+        .min(frames);
+
+    for (buffer_ch, block_ch) in buffer.append_frames(frames).zip(block.channels()) {
+        buffer_ch[..copy_frames]
+            .copy_from_slice(&block_ch[start_frame_in_block..start_frame_in_block + copy_frames]);
+        buffer_ch[copy_frames..].fill(Default::default());
     }
 }
@@ -0,0 +1,93 @@
+use super::{DataBlock, FadeSample};
+
+/// The state of a stream while scrubbing with `ReadDiskStream::scrub_to()`.
+///
+/// The window of frames around the scrub position is stored in `HeapData::scrub_window`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScrubState {
+    /// The latest frame that was scrubbed to, if a window around it has not been
+    /// requested yet.
+    pub pending_target: Option<usize>,
+    /// The window that has been requested from the IO server and not yet received.
+    pub in_flight: Option<ScrubRequest>,
+    /// The frame in the file the current window starts from.
+    pub window_start: usize,
+    /// The position in the file that is read from next, in (fractional) frames.
+    pub position: f64,
+}
+
+/// A window that has been requested from the IO server.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScrubRequest {
+    pub start_frame: usize,
+    /// The frame to move the scrub position to once the window is received, or `None`
+    /// if the window was requested to follow the current position.
+    pub target: Option<usize>,
+}
+
+impl ScrubState {
+    pub fn new(target: usize) -> Self {
+        Self {
+            pending_target: Some(target),
+            in_flight: None,
+            window_start: 0,
+            position: target as f64,
+        }
+    }
+
+    /// Returns true if the scrub position is close enough to either edge of the window
+    /// (and not at either end of the file) that the next window should be requested.
+    pub fn needs_next_window(
+        &self,
+        window_len: usize,
+        block_size: usize,
+        num_frames: usize,
+    ) -> bool {
+        let margin = (block_size / 4) as f64;
+        let window_start = self.window_start as f64;
+        let window_end = (self.window_start + window_len) as f64;
+
+        (self.window_start > 0 && self.position < window_start + margin)
+            || (window_end < num_frames as f64 && self.position >= window_end - margin)
+    }
+}
+
+/// Write `frames` frames into `out` starting from `position` (in frames), moving
+/// `rate` frames through `window` per output frame. Samples between two frames are
+/// linearly interpolated, and any frame outside of the window is silent.
+///
+/// Returns the position after the last frame.
+pub(crate) fn scrub_into<T: FadeSample + Default + Send>(
+    out: &mut DataBlock<T>,
+    window: Option<&DataBlock<T>>,
+    window_start: usize,
+    position: f64,
+    rate: f64,
+    frames: usize,
+) -> f64 {
+    for (ch_i, out_ch) in out.append_frames(frames).enumerate() {
+        let window_ch = window.map(|window| window.channel(ch_i));
+
+        for (i, out) in out_ch.iter_mut().enumerate() {
+            let pos = position + (i as f64 * rate);
+
+            let sample = window_ch.and_then(|window_ch| {
+                if pos < window_start as f64 {
+                    return None;
+                }
+
+                let pos_in_window = pos - window_start as f64;
+                let i0 = pos_in_window as usize;
+                let a = *window_ch.get(i0)?;
+                let b = window_ch.get(i0 + 1).copied().unwrap_or(a);
+
+                let t = (pos_in_window - i0 as f64) as f32;
+                Some(T::mix(a, 1.0 - t, b, t))
+            });
+
+            *out = sample.unwrap_or(T::SILENCE);
+        }
+    }
+
+    position + (frames as f64 * rate)
+}
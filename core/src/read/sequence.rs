@@ -0,0 +1,444 @@
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use super::{DataBlock, Decoder, ReadData, ReadDiskStream, ReadError};
+
+/// Options for a [`SequenceReadStream`].
+#[derive(Debug, Clone, Copy)]
+pub struct SequenceOptions {
+    /// The maximum number of items that can be waiting in the queue, not counting the
+    /// item that is currently playing.
+    ///
+    /// The default is `16`.
+    pub max_queued_items: usize,
+
+    /// The maximum number of frames that can be read at once with
+    /// `SequenceReadStream::read()`.
+    ///
+    /// The default is `16384`.
+    pub block_size: usize,
+}
+
+impl Default for SequenceOptions {
+    fn default() -> Self {
+        Self {
+            max_queued_items: 16,
+            block_size: 16384,
+        }
+    }
+}
+
+/// Identifies an item appended to a [`SequenceReadStream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ItemId(pub u64);
+
+/// A point in the data returned from `SequenceReadStream::read()` where one item ended
+/// and/or the next item started. See `ReadData::item_boundaries()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemBoundary {
+    /// The frame in the returned data where the boundary lies.
+    pub frame: usize,
+    /// The item that ended, or `None` if the sequence was idle before this point.
+    pub ended: Option<ItemId>,
+    /// The item that started, or `None` if the queue is empty after this point.
+    pub started: Option<ItemId>,
+    /// Whether the item that ended was cut short because a fatal error occurred in
+    /// its stream.
+    pub failed: bool,
+}
+
+/// An error appending an item to a [`SequenceReadStream`]. The stream is returned back.
+pub enum AppendError<S> {
+    /// The queue already holds `SequenceOptions::max_queued_items` items.
+    QueueFull(S),
+    /// The stream has a different number of channels than the sequence.
+    MismatchedChannels(S),
+}
+
+impl<S> AppendError<S> {
+    /// Return the stream that could not be appended.
+    pub fn into_inner(self) -> S {
+        match self {
+            AppendError::QueueFull(s) | AppendError::MismatchedChannels(s) => s,
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for AppendError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppendError::QueueFull(_) => write!(f, "QueueFull(..)"),
+            AppendError::MismatchedChannels(_) => write!(f, "MismatchedChannels(..)"),
+        }
+    }
+}
+
+impl<S> std::fmt::Display for AppendError<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppendError::QueueFull(_) => write!(f, "The queue of the sequence is full"),
+            AppendError::MismatchedChannels(_) => {
+                write!(
+                    f,
+                    "The stream does not have the same number of channels as the sequence"
+                )
+            }
+        }
+    }
+}
+
+impl<S> std::error::Error for AppendError<S> {}
+
+/// A type-erased `ReadDiskStream`, so that files of different formats can be
+/// played in the same sequence.
+trait SequenceSource<T: Copy + Clone + Default + Send>: Send {
+    /// Receive any data sent from the IO server of the stream. Returns true if the
+    /// stream is ready to be read from.
+    fn poll_ready(&mut self) -> bool;
+
+    /// Append up to `frames` frames into `out`. Nothing is read while the stream is
+    /// buffering.
+    fn read_into(
+        &mut self,
+        out: &mut DataBlock<T>,
+        frames: usize,
+    ) -> Result<SourceRead, SourceError>;
+}
+
+struct SourceRead {
+    frames: usize,
+    reached_end_of_file: bool,
+}
+
+enum SourceError {
+    /// The stream is buffering or cannot be read from right now, but it may be able to
+    /// later.
+    Busy,
+    /// The stream cannot continue.
+    Fatal,
+}
+
+impl<D: Decoder> SequenceSource<D::T> for ReadDiskStream<D>
+where
+    ReadDiskStream<D>: Send,
+{
+    fn poll_ready(&mut self) -> bool {
+        self.is_ready().unwrap_or(false)
+    }
+
+    fn read_into(
+        &mut self,
+        out: &mut DataBlock<D::T>,
+        frames: usize,
+    ) -> Result<SourceRead, SourceError> {
+        // Reading while buffering would advance the playhead through the item and play
+        // silence in its place, so wait until it is ready instead.
+        if self.playhead() < self.info().num_frames {
+            match self.frames_ready(frames) {
+                Ok(true) => {}
+                Ok(false) | Err(ReadError::IOServerChannelFull) => return Err(SourceError::Busy),
+                Err(_) => return Err(SourceError::Fatal),
+            }
+        }
+
+        match self.read(frames) {
+            Ok(data) => {
+                for (i, out_ch) in out.append_frames(data.num_frames()).enumerate() {
+                    out_ch.copy_from_slice(data.read_channel(i));
+                }
+
+                Ok(SourceRead {
+                    frames: data.num_frames(),
+                    reached_end_of_file: data.reached_end_of_file(),
+                })
+            }
+            Err(ReadError::EndOfFile) => Ok(SourceRead {
+                frames: 0,
+                reached_end_of_file: true,
+            }),
+            Err(ReadError::IOServerChannelFull) => Err(SourceError::Busy),
+            Err(_) => Err(SourceError::Fatal),
+        }
+    }
+}
+
+struct Item<T: Copy + Clone + Default + Send> {
+    id: ItemId,
+    source: Box<dyn SequenceSource<T>>,
+}
+
+/// A realtime-safe reader that plays a queue of [`ReadDiskStream`]s back to back
+/// without any gaps.
+///
+/// Items are appended with the [`SequenceHandle`] returned from
+/// `SequenceReadStream::new()`. The streams may use different decoders, as long as
+/// they all decode to the same sample type and have the same number of channels.
+/// Every appended stream keeps prefetching on its own IO server thread while it waits
+/// in the queue, so the next file is ready before the current one ends.
+///
+/// The points where items end and start are returned with the data from
+/// `SequenceReadStream::read()`. See `ReadData::item_boundaries()`.
+///
+/// Sample rates are not converted, so every stream should have the same sample rate.
+pub struct SequenceReadStream<T: Copy + Clone + Default + Send + 'static> {
+    from_handle_rx: Consumer<Item<T>>,
+    /// Sends finished items back to the handle so that they are deallocated outside
+    /// of the realtime thread.
+    to_handle_tx: Producer<Item<T>>,
+
+    current: Option<Item<T>>,
+    next: Option<Item<T>>,
+
+    read_buffer: DataBlock<T>,
+    item_boundaries: Vec<ItemBoundary>,
+    block_size: usize,
+}
+
+impl<T: Copy + Clone + Default + Send + 'static> SequenceReadStream<T> {
+    /// Create a new sequence of streams with `num_channels` channels.
+    ///
+    /// Returns the sequence, which is realtime-safe, and a handle to append streams to it
+    /// from another thread.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `num_channels`, `opts.max_queued_items`, or `opts.block_size`
+    /// is `0`.
+    pub fn new(num_channels: u16, opts: SequenceOptions) -> (Self, SequenceHandle<T>) {
+        assert_ne!(num_channels, 0);
+        assert_ne!(opts.max_queued_items, 0);
+        assert_ne!(opts.block_size, 0);
+
+        let (to_stream_tx, from_handle_rx) = RingBuffer::new(opts.max_queued_items);
+        // Leave room for every item that can be in the sequence at once.
+        let (to_handle_tx, from_stream_rx) = RingBuffer::new(opts.max_queued_items + 2);
+
+        (
+            Self {
+                from_handle_rx,
+                to_handle_tx,
+                current: None,
+                next: None,
+                read_buffer: DataBlock::new(usize::from(num_channels), opts.block_size),
+                // Each read has at most one boundary for every item that can be in the
+                // sequence at once, plus one for starting from idle.
+                item_boundaries: Vec::with_capacity(opts.max_queued_items + 3),
+                block_size: opts.block_size,
+            },
+            SequenceHandle {
+                to_stream_tx,
+                from_stream_rx,
+                num_channels,
+                next_id: 0,
+            },
+        )
+    }
+
+    /// Returns true if the current item (or the next item, if nothing is playing) is
+    /// finished buffering and data can be read right now, false otherwise.
+    ///
+    /// This is realtime-safe.
+    pub fn is_ready(&mut self) -> bool {
+        self.fetch_next();
+
+        if let Some(next) = &mut self.next {
+            // Keep receiving data for the next item while it waits.
+            let next_ready = next.source.poll_ready();
+            if self.current.is_none() {
+                return next_ready;
+            }
+        }
+
+        self.current
+            .as_mut()
+            .map_or(false, |current| current.source.poll_ready())
+    }
+
+    /// Return the item that is currently playing, if any.
+    ///
+    /// This is realtime-safe.
+    pub fn current_item(&self) -> Option<ItemId> {
+        self.current.as_ref().map(|current| current.id)
+    }
+
+    /// Return the item that will play after the current item, if any.
+    ///
+    /// This is realtime-safe.
+    pub fn next_item(&self) -> Option<ItemId> {
+        self.next.as_ref().map(|next| next.id)
+    }
+
+    /// Read the next chunk of `frames` in the sequence.
+    ///
+    /// This is realtime-safe.
+    ///
+    /// When the current item ends, reading continues from the next item in the queue in
+    /// the same call. Silence is returned while the queue is empty or while the current
+    /// item is buffering. An item is never advanced while it is buffering, so no part of
+    /// it is skipped, and it continues from where it left off once it is ready. Use
+    /// `SequenceReadStream::is_ready()` to check for buffering beforehand. An item whose
+    /// stream had a fatal error is skipped.
+    ///
+    /// NOTE: If the number of `frames` exceeds `SequenceOptions::block_size`, then that
+    /// block size will be used instead.
+    pub fn read(&mut self, frames: usize) -> ReadData<'_, T> {
+        let frames = frames.min(self.block_size);
+
+        self.read_buffer.clear();
+        self.item_boundaries.clear();
+
+        let mut filled = 0;
+        while filled < frames {
+            if self.current.is_none() {
+                self.fetch_next();
+
+                let Some(next) = self.next.take() else {
+                    break;
+                };
+
+                self.push_boundary(ItemBoundary {
+                    frame: filled,
+                    ended: None,
+                    started: Some(next.id),
+                    failed: false,
+                });
+                self.current = Some(next);
+                continue;
+            }
+
+            let Some(current) = self.current.as_mut() else {
+                break;
+            };
+
+            match current
+                .source
+                .read_into(&mut self.read_buffer, frames - filled)
+            {
+                Ok(read) => {
+                    filled += read.frames;
+
+                    if read.reached_end_of_file {
+                        self.end_current(filled, false);
+                    }
+                }
+                Err(SourceError::Busy) => break,
+                Err(SourceError::Fatal) => {
+                    // The frames read so far are kept, and the rest of the item is skipped.
+                    self.end_current(filled, true);
+                }
+            }
+        }
+
+        // Fill the rest with silence.
+        let silent_frames = frames.saturating_sub(self.read_buffer.num_frames());
+        for ch in self.read_buffer.append_frames(silent_frames) {
+            ch.fill(Default::default());
+        }
+
+        // Keep receiving data for the next item while it waits.
+        self.fetch_next();
+        if let Some(next) = &mut self.next {
+            next.source.poll_ready();
+        }
+
+        ReadData::new(&self.read_buffer, frames, false).with_item_boundaries(&self.item_boundaries)
+    }
+
+    /// Take the next item out of the queue if there is no next item yet.
+    fn fetch_next(&mut self) {
+        if self.next.is_none() {
+            self.next = self.from_handle_rx.pop().ok();
+        }
+    }
+
+    /// End the current item and start the next one at `frame` in the read buffer.
+    fn end_current(&mut self, frame: usize, failed: bool) {
+        let Some(ended) = self.current.take() else {
+            return;
+        };
+
+        self.fetch_next();
+        self.current = self.next.take();
+
+        self.push_boundary(ItemBoundary {
+            frame,
+            ended: Some(ended.id),
+            started: self.current.as_ref().map(|current| current.id),
+            failed,
+        });
+
+        // Send the item back to the handle to be deallocated. If the handle has not
+        // collected the previous items, then there is no choice but to drop it here.
+        let _ = self.to_handle_tx.push(ended);
+    }
+
+    fn push_boundary(&mut self, boundary: ItemBoundary) {
+        // Never allocate. More boundaries than there is room for can only happen if
+        // items are appended while reading, and those are dropped.
+        if self.item_boundaries.len() < self.item_boundaries.capacity() {
+            self.item_boundaries.push(boundary);
+        }
+    }
+}
+
+/// A handle to append items to a [`SequenceReadStream`] from another thread.
+///
+/// Items that have finished playing are deallocated by the handle, so `append()` or
+/// `collect_garbage()` should be called every once in a while.
+pub struct SequenceHandle<T: Copy + Clone + Default + Send + 'static> {
+    to_stream_tx: Producer<Item<T>>,
+    from_stream_rx: Consumer<Item<T>>,
+    num_channels: u16,
+    next_id: u64,
+}
+
+impl<T: Copy + Clone + Default + Send + 'static> SequenceHandle<T> {
+    /// Append a stream to the end of the queue.
+    ///
+    /// The stream should already be seeked to where playback of the item should start
+    /// (i.e. with `ReadDiskStream::seek(0, SeekMode::Auto)`), so that it can prefetch while
+    /// it waits in the queue. It plays until the end of its file.
+    ///
+    /// This never blocks the thread that reads from the sequence. It is not realtime-safe
+    /// itself, since it allocates the item and deallocates finished items.
+    pub fn append<D: Decoder<T = T>>(
+        &mut self,
+        stream: ReadDiskStream<D>,
+    ) -> Result<ItemId, AppendError<Box<ReadDiskStream<D>>>>
+    where
+        ReadDiskStream<D>: Send,
+    {
+        self.collect_garbage();
+
+        let stream = Box::new(stream);
+
+        if stream.info().num_channels != self.num_channels {
+            return Err(AppendError::MismatchedChannels(stream));
+        }
+        if self.to_stream_tx.is_full() {
+            return Err(AppendError::QueueFull(stream));
+        }
+
+        let id = ItemId(self.next_id);
+        self.next_id += 1;
+
+        // This cannot fail because we made sure that a slot is available in the
+        // previous step.
+        let _ = self.to_stream_tx.push(Item { id, source: stream });
+
+        Ok(id)
+    }
+
+    /// Return the number of items waiting in the queue, not counting the item that is
+    /// currently playing or the item after it.
+    pub fn num_queued_items(&self) -> usize {
+        self.to_stream_tx.buffer().capacity() - self.to_stream_tx.slots()
+    }
+
+    /// Deallocate any items that have finished playing.
+    pub fn collect_garbage(&mut self) {
+        while let Ok(item) = self.from_stream_rx.pop() {
+            // Drop the item here.
+            let _ = item;
+        }
+    }
+}
@@ -1,17 +1,45 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 
-use rtrb::{Consumer, Producer, RingBuffer};
+use rtrb::{Consumer, Producer};
 
+use crate::open::OpenResultTx;
 use crate::{FileInfo, SERVER_WAIT_TIME};
 
-use super::{ClientToServerMsg, DataBlock, DataBlockCache, Decoder, HeapData, ServerToClientMsg};
+use super::budget::BlockUsage;
+use super::decoder::decode_into_block;
+use super::{
+    CacheReadyCallback, ClientToServerMsg, DataBlock, DataBlockCache, Decoder, HeapData,
+    MemoryBudget, ServerToClientMsg,
+};
 
 pub(crate) struct ReadServerOptions<D: Decoder> {
     pub file: PathBuf,
     pub start_frame: usize,
     pub num_prefetch_blocks: usize,
+    pub num_look_ahead_blocks: usize,
     pub block_size: usize,
+    pub preload_max_frames: Option<usize>,
+    pub memory_budget: Option<MemoryBudget>,
+    pub num_caches: usize,
+    pub on_cache_ready: Option<CacheReadyCallback>,
+    pub seek_count: Arc<AtomicUsize>,
     pub additional_opts: D::AdditionalOpts,
+    pub cache_decoder_opts: Option<D::AdditionalOpts>,
+    /// If this is `Some`, then all prefetch blocks and this many caches are allocated
+    /// before the stream is opened.
+    pub preallocate_caches: Option<usize>,
+}
+
+/// The result of opening a file on the server.
+pub struct OpenedFile<D: Decoder> {
+    pub(crate) file_info: FileInfo<D::FileParams>,
+    /// The whole file, if it was preloaded.
+    pub(crate) preload_cache: Option<DataBlockCache<D::T>>,
+    /// The number of bytes of blocks and caches that were allocated up front.
+    pub(crate) preallocated_bytes: usize,
 }
 
 pub(crate) struct ReadServer<D: Decoder> {
@@ -20,6 +48,9 @@ pub(crate) struct ReadServer<D: Decoder> {
     close_signal_rx: Consumer<Option<HeapData<D::T>>>,
 
     decoder: D,
+    /// A second decoder that is only used to fill caches and decode scrub windows, if
+    /// enabled.
+    cache_decoder: Option<D>,
 
     block_pool: Vec<DataBlock<D::T>>,
     cache_pool: Vec<DataBlockCache<D::T>>,
@@ -28,6 +59,22 @@ pub(crate) struct ReadServer<D: Decoder> {
     num_prefetch_blocks: usize,
     block_size: usize,
 
+    memory_budget: Option<MemoryBudget>,
+    block_usage: Option<BlockUsage>,
+
+    /// The number of caches the client can request. The caches after these are the
+    /// client's temporary caches.
+    num_caches: usize,
+    on_cache_ready: Option<CacheReadyCallback>,
+
+    /// The number of times the client has seeked. Requests made before the latest seek
+    /// are skipped.
+    seek_count: Arc<AtomicUsize>,
+
+    /// Whether the client is hibernating, in which case disposed blocks and caches are
+    /// freed instead of being stored to be reused.
+    hibernating: bool,
+
     run: bool,
     client_closed: bool,
 }
@@ -38,54 +85,92 @@ impl<D: Decoder> ReadServer<D> {
         to_client_tx: Producer<ServerToClientMsg<D>>,
         from_client_rx: Consumer<ClientToServerMsg<D>>,
         close_signal_rx: Consumer<Option<HeapData<D::T>>>,
-    ) -> Result<FileInfo<D::FileParams>, D::OpenError> {
+        open_tx: OpenResultTx<Result<OpenedFile<D>, D::OpenError>>,
+    ) {
         let ReadServerOptions {
             file,
             start_frame,
             num_prefetch_blocks,
+            num_look_ahead_blocks,
             block_size,
+            preload_max_frames,
+            memory_budget,
+            num_caches,
+            on_cache_ready,
+            seek_count,
             additional_opts,
+            cache_decoder_opts,
+            preallocate_caches,
         } = opts;
 
-        let (mut open_tx, mut open_rx) =
-            RingBuffer::<Result<FileInfo<D::FileParams>, D::OpenError>>::new(1);
-
         std::thread::spawn(move || {
+            let cache_decoder = match cache_decoder_opts {
+                Some(opts) => match D::new(file.clone(), start_frame, block_size, opts) {
+                    Ok((cache_decoder, _)) => Some(cache_decoder),
+                    Err(e) => {
+                        open_tx.send(Err(e));
+                        return;
+                    }
+                },
+                None => None,
+            };
+
             match D::new(file, start_frame, block_size, additional_opts) {
                 Ok((decoder, file_info)) => {
-                    let num_channels = file_info.num_channels;
-
-                    // Push cannot fail because only one message is ever sent.
-                    let _ = open_tx.push(Ok(file_info));
-
-                    ReadServer::run(Self {
+                    let mut server = Self {
                         to_client_tx,
                         from_client_rx,
                         close_signal_rx,
                         decoder,
+                        cache_decoder,
                         block_pool: Vec::new(),
                         cache_pool: Vec::new(),
-                        num_channels: usize::from(num_channels),
+                        num_channels: usize::from(file_info.num_channels),
                         num_prefetch_blocks,
                         block_size,
+                        block_usage: memory_budget.as_ref().map(MemoryBudget::block_usage),
+                        memory_budget,
+                        num_caches,
+                        on_cache_ready,
+                        seek_count,
+                        hibernating: false,
                         run: true,
                         client_closed: false,
-                    });
+                    };
+
+                    let preload_cache = match preload_max_frames {
+                        Some(max_frames) if file_info.num_frames <= max_frames => {
+                            // Use a cache that is large enough to hold the whole file. This
+                            // matches the size the client reserved for its channel.
+                            let num_cache_blocks = file_info.num_frames / block_size + 1;
+                            server.num_prefetch_blocks = num_cache_blocks + num_look_ahead_blocks;
+
+                            server.preload()
+                        }
+                        _ => None,
+                    };
+
+                    let preallocated_bytes = match preallocate_caches {
+                        // The preloaded file already uses one of the caches.
+                        Some(num_caches) => server.preallocate(
+                            num_caches.saturating_sub(usize::from(preload_cache.is_some())),
+                        ),
+                        None => 0,
+                    };
+
+                    open_tx.send(Ok(OpenedFile {
+                        file_info,
+                        preload_cache,
+                        preallocated_bytes,
+                    }));
+
+                    ReadServer::run(server);
                 }
                 Err(e) => {
-                    // Push cannot fail because only one message is ever sent.
-                    let _ = open_tx.push(Err(e));
+                    open_tx.send(Err(e));
                 }
             }
         });
-
-        loop {
-            if let Ok(res) = open_rx.pop() {
-                return res;
-            }
-
-            std::thread::sleep(SERVER_WAIT_TIME);
-        }
     }
 
     fn run(mut self) {
@@ -93,6 +178,9 @@ impl<D: Decoder> ReadServer<D> {
             cache_index: usize,
             cache: Option<DataBlockCache<D::T>>,
             start_frame: usize,
+            seek_count: Option<usize>,
+            /// The number of blocks that have been filled by the cache decoder so far.
+            filled_blocks: usize,
         }
 
         let mut cache_requests: Vec<CacheRequest<D>> = Vec::new();
@@ -115,25 +203,36 @@ impl<D: Decoder> ReadServer<D> {
                         block_index,
                         block,
                         start_frame,
+                        seek_count,
                     } => {
-                        let mut block = block.unwrap_or(
+                        // Skip the request if the client has seeked since, in which case
+                        // it would discard the block anyway.
+                        if self.is_stale(seek_count) {
+                            if let Some(block) = block {
+                                self.recycle_block(block);
+                            }
+                            continue;
+                        }
+
+                        let mut block = match block {
+                            Some(block) => block,
                             // Try using one in the pool if it exists.
-                            self.block_pool.pop().unwrap_or(
+                            None => match self.block_pool.pop() {
+                                Some(block) => block,
                                 // No blocks in pool. Create a new one.
-                                DataBlock::new(self.num_channels, self.block_size),
-                            ),
-                        );
-
-                        block.clear();
+                                None => self.new_block(),
+                            },
+                        };
 
-                        let decode_res = self.decoder.decode(&mut block);
+                        let decode_res = decode_into_block(&mut self.decoder, &mut block);
 
                         match decode_res {
-                            Ok(()) => {
+                            Ok(_) => {
                                 self.send_msg(ServerToClientMsg::ReadIntoBlockRes {
                                     block_index,
                                     block,
                                     wanted_start_frame: start_frame,
+                                    decoded_at: Instant::now(),
                                 });
                             }
                             Err(e) => {
@@ -146,9 +245,21 @@ impl<D: Decoder> ReadServer<D> {
                     }
                     ClientToServerMsg::DisposeBlock { block } => {
                         // Store the block to be reused.
-                        self.block_pool.push(block);
+                        self.recycle_block(block);
                     }
-                    ClientToServerMsg::SeekTo { frame } => {
+                    ClientToServerMsg::FreeBlock { block } => {
+                        // The client needs one less block, so its memory is freed instead
+                        // of keeping it in the pool.
+                        if let Some(block) = block.or_else(|| self.block_pool.pop()) {
+                            self.free_block(block);
+                        }
+                    }
+                    ClientToServerMsg::SeekTo { frame, seek_count } => {
+                        // A later seek is still in the queue.
+                        if self.is_stale(seek_count) {
+                            continue;
+                        }
+
                         if let Err(e) = self.decoder.seek(frame) {
                             self.send_msg(ServerToClientMsg::FatalError(e));
                             self.run = false;
@@ -160,64 +271,182 @@ impl<D: Decoder> ReadServer<D> {
                         cache_index,
                         cache,
                         start_frame,
+                        seek_count,
                     } => {
                         // Prioritize read blocks over caching.
                         cache_requests.push(CacheRequest {
                             cache_index,
                             cache,
                             start_frame,
+                            seek_count,
+                            filled_blocks: 0,
                         });
                     }
                     ClientToServerMsg::DisposeCache { cache } => {
                         // Store the cache to be reused.
-                        self.cache_pool.push(cache);
+                        self.recycle_cache(cache);
+                    }
+                    ClientToServerMsg::Hibernate => {
+                        // Free everything in the pools, and anything the client disposes of
+                        // until it wakes up again.
+                        self.hibernating = true;
+
+                        while let Some(block) = self.block_pool.pop() {
+                            self.free_block(block);
+                        }
+                        self.cache_pool.clear();
+                    }
+                    ClientToServerMsg::Wake => {
+                        self.hibernating = false;
+                    }
+                    ClientToServerMsg::Scrub { block, start_frame } => {
+                        // Scrub windows are decoded right away to keep the latency low.
+                        let mut block = match block {
+                            Some(block) => block,
+                            // Try using one in the pool if it exists.
+                            None => match self.block_pool.pop() {
+                                Some(block) => block,
+                                // No blocks in pool. Create a new one.
+                                None => self.new_block(),
+                            },
+                        };
+
+                        // The client only scrubs if there is a second decoder, so the
+                        // streaming decoder never has to seek away from its position. A
+                        // cache that is being filled continues from where it left off.
+                        let Some(scrub_decoder) = self.cache_decoder.as_mut() else {
+                            self.recycle_block(block);
+                            continue;
+                        };
+
+                        let decode_res = scrub_decoder
+                            .seek(start_frame)
+                            .and_then(|()| decode_into_block(scrub_decoder, &mut block));
+
+                        match decode_res {
+                            Ok(_) => {
+                                self.send_msg(ServerToClientMsg::ScrubRes { block, start_frame });
+                            }
+                            Err(e) => {
+                                self.send_msg(ServerToClientMsg::FatalError(e));
+                                self.run = false;
+                                do_sleep = false;
+                                break;
+                            }
+                        }
                     }
                 }
             }
 
-            while let Some(request) = cache_requests.pop() {
-                let mut cache = request.cache.unwrap_or(
+            // Free the memory of any pooled caches that were evicted from the memory budget.
+            self.cache_pool.retain(|cache| !cache.is_evicted());
+
+            while let Some(mut request) = cache_requests.pop() {
+                // Skip filling a temporary seek cache if the client has seeked since.
+                if request
+                    .seek_count
+                    .map_or(false, |count| self.is_stale(count))
+                {
+                    if let Some(cache) = request.cache {
+                        self.recycle_cache(cache);
+                    }
+                    continue;
+                }
+
+                let mut cache = match request.cache.take() {
+                    // A cache that is shared with other streams cannot be written to, and
+                    // an evicted cache is about to be freed.
+                    Some(cache) if !cache.is_shared() && !cache.is_evicted() => cache,
                     // Try using one in the pool if it exists.
-                    self.cache_pool.pop().unwrap_or(
-                        // No caches in pool. Create a new one.
-                        DataBlockCache::new(
-                            self.num_channels,
-                            self.num_prefetch_blocks,
-                            self.block_size,
-                        ),
-                    ),
-                );
+                    _ => {
+                        // Any blocks that were already filled are lost.
+                        request.filled_blocks = 0;
+
+                        match self.cache_pool.pop() {
+                            Some(cache) => cache,
+                            // No caches in pool. Create a new one.
+                            None => self.new_cache(),
+                        }
+                    }
+                };
+                cache.touch();
+
+                if let Some(cache_decoder) = &mut self.cache_decoder {
+                    // Fill the cache with the dedicated cache decoder, so the streaming
+                    // decoder never has to seek away from its position.
+                    let mut interrupted = false;
+                    let mut fill_res = Ok(());
+
+                    // Continue from where a previous fill of this cache left off.
+                    let frame = request.start_frame + (request.filled_blocks * self.block_size);
+                    if request.filled_blocks == 0 || cache_decoder.current_frame() != frame {
+                        fill_res = cache_decoder.seek(frame);
+                    }
 
-                let current_frame = self.decoder.current_frame();
+                    if fill_res.is_ok() {
+                        let blocks = cache.blocks_mut().unwrap_or_default();
+                        let num_blocks = blocks.len();
 
-                // Seek to the position the client wants to cache.
-                if let Err(e) = self.decoder.seek(request.start_frame) {
-                    self.send_msg(ServerToClientMsg::FatalError(e));
-                    self.run = false;
-                    do_sleep = false;
-                    break;
-                }
+                        for block in blocks.iter_mut().skip(request.filled_blocks) {
+                            if let Err(e) = decode_into_block(cache_decoder, block) {
+                                fill_res = Err(e);
+                                break;
+                            }
+                            request.filled_blocks += 1;
 
-                // Fill the cache
-                for block in cache.blocks.iter_mut() {
-                    block.clear();
+                            // Serve any new requests for the streaming decoder before
+                            // filling the rest of the cache.
+                            if request.filled_blocks < num_blocks && !self.from_client_rx.is_empty()
+                            {
+                                interrupted = true;
+                                break;
+                            }
+                        }
+                    }
+
+                    if let Err(e) = fill_res {
+                        self.send_msg(ServerToClientMsg::FatalError(e));
+                        self.run = false;
+                        do_sleep = false;
+                        break;
+                    }
 
-                    let decode_res = self.decoder.decode(block);
+                    if interrupted {
+                        request.cache = Some(cache);
+                        cache_requests.push(request);
+                        do_sleep = false;
+                        break;
+                    }
+                } else {
+                    let current_frame = self.decoder.current_frame();
 
-                    if let Err(e) = decode_res {
+                    // Seek to the position the client wants to cache.
+                    if let Err(e) = self.decoder.seek(request.start_frame) {
                         self.send_msg(ServerToClientMsg::FatalError(e));
                         self.run = false;
                         do_sleep = false;
                         break;
                     }
-                }
 
-                // Seek back to the previous position.
-                if let Err(e) = self.decoder.seek(current_frame) {
-                    self.send_msg(ServerToClientMsg::FatalError(e));
-                    self.run = false;
-                    do_sleep = false;
-                    break;
+                    // Fill the cache
+                    for block in cache.blocks_mut().into_iter().flatten() {
+                        let decode_res = decode_into_block(&mut self.decoder, block);
+
+                        if let Err(e) = decode_res {
+                            self.send_msg(ServerToClientMsg::FatalError(e));
+                            self.run = false;
+                            do_sleep = false;
+                            break;
+                        }
+                    }
+
+                    // Seek back to the previous position.
+                    if let Err(e) = self.decoder.seek(current_frame) {
+                        self.send_msg(ServerToClientMsg::FatalError(e));
+                        self.run = false;
+                        do_sleep = false;
+                        break;
+                    }
                 }
 
                 self.send_msg(ServerToClientMsg::CacheRes {
@@ -226,6 +455,12 @@ impl<D: Decoder> ReadServer<D> {
                     wanted_start_frame: request.start_frame,
                 });
 
+                if self.run && request.cache_index < self.num_caches {
+                    if let Some(on_cache_ready) = &self.on_cache_ready {
+                        on_cache_ready.call(request.cache_index, request.start_frame);
+                    }
+                }
+
                 // If any new messages have been received while caching, prioritize those
                 // over filling any additional caches.
                 if !self.from_client_rx.is_empty() {
@@ -253,6 +488,102 @@ impl<D: Decoder> ReadServer<D> {
         }
     }
 
+    /// Decode the whole file into a new cache.
+    ///
+    /// If a fatal error occurs, then `None` is returned and the error is sent to the
+    /// client once the stream has been opened.
+    fn preload(&mut self) -> Option<DataBlockCache<D::T>> {
+        let mut cache = self.new_cache();
+
+        let fill_res = self.decoder.seek(0).and_then(|()| {
+            for block in cache.blocks_mut().into_iter().flatten() {
+                decode_into_block(&mut self.decoder, block)?;
+            }
+            Ok(())
+        });
+
+        match fill_res {
+            Ok(()) => Some(cache),
+            Err(e) => {
+                // The message channel is empty at this point, so this will not block.
+                self.send_msg(ServerToClientMsg::FatalError(e));
+                self.run = false;
+                None
+            }
+        }
+    }
+
+    /// Fill the pools with every prefetch block the client can use (including the block
+    /// it lends out from `read`) and the given number of caches, and return the number
+    /// of bytes that were allocated.
+    fn preallocate(&mut self, num_caches: usize) -> usize {
+        for _ in 0..self.num_prefetch_blocks + 1 {
+            let mut block = self.new_block();
+            block.fault_in();
+            self.block_pool.push(block);
+        }
+
+        for _ in 0..num_caches {
+            let mut cache = self.new_cache();
+            for block in cache.blocks_mut().into_iter().flatten() {
+                block.fault_in();
+            }
+            self.cache_pool.push(cache);
+        }
+
+        let block_bytes = self.num_channels * self.block_size * std::mem::size_of::<D::T>();
+        (((num_caches + 1) * self.num_prefetch_blocks) + 1) * block_bytes
+    }
+
+    /// Returns true if a request made at the given seek count has been superseded by a
+    /// later seek.
+    fn is_stale(&self, seek_count: usize) -> bool {
+        seek_count != self.seek_count.load(Ordering::Relaxed)
+    }
+
+    fn new_block(&mut self) -> DataBlock<D::T> {
+        if let Some(block_usage) = &mut self.block_usage {
+            block_usage.add(self.num_channels * self.block_size * std::mem::size_of::<D::T>());
+        }
+
+        DataBlock::new(self.num_channels, self.block_size)
+    }
+
+    /// Store a block to be reused, or free it if the client is hibernating.
+    fn recycle_block(&mut self, block: DataBlock<D::T>) {
+        if self.hibernating {
+            self.free_block(block);
+        } else {
+            self.block_pool.push(block);
+        }
+    }
+
+    /// Store a cache to be reused. A cache that is shared with other streams or that was
+    /// evicted from the memory budget is dropped instead, as is any cache while the client
+    /// is hibernating.
+    fn recycle_cache(&mut self, cache: DataBlockCache<D::T>) {
+        if !self.hibernating && !cache.is_shared() && !cache.is_evicted() {
+            self.cache_pool.push(cache);
+        }
+    }
+
+    fn free_block(&mut self, block: DataBlock<D::T>) {
+        if let Some(block_usage) = &mut self.block_usage {
+            block_usage.remove(self.num_channels * self.block_size * std::mem::size_of::<D::T>());
+        }
+
+        drop(block);
+    }
+
+    fn new_cache(&self) -> DataBlockCache<D::T> {
+        DataBlockCache::new(
+            self.num_channels,
+            self.num_prefetch_blocks,
+            self.block_size,
+            self.memory_budget.as_ref(),
+        )
+    }
+
     fn send_msg(&mut self, msg: ServerToClientMsg<D>) {
         // Do nothing if stream has been closed.
         if !self.run {
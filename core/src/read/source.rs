@@ -0,0 +1,33 @@
+use std::io::{self, Read, Seek};
+use std::path::Path;
+
+/// A source of bytes that a decoder can read a file from.
+///
+/// This is implemented for every type that is `Read + Seek + Send`.
+pub trait MediaSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> MediaSource for T {}
+
+/// Opens the [`MediaSource`] of a file, for example from an archive, a custom virtual
+/// file system, or an encrypted sample library.
+///
+/// A provider is shared between streams and is called from the IO server thread of
+/// each stream it is used with.
+///
+/// This is implemented for every `Fn(&Path) -> io::Result<Box<dyn MediaSource>>`
+/// closure that is `Send + Sync`.
+pub trait SourceProvider: Send + Sync {
+    /// Open the source of the file with the given path.
+    ///
+    /// The path is the one given to the stream. It does not have to exist on disk.
+    fn open(&self, file: &Path) -> io::Result<Box<dyn MediaSource>>;
+}
+
+impl<F> SourceProvider for F
+where
+    F: Fn(&Path) -> io::Result<Box<dyn MediaSource>> + Send + Sync,
+{
+    fn open(&self, file: &Path) -> io::Result<Box<dyn MediaSource>> {
+        (self)(file)
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+
+use super::PageCacheAdvice;
+
+/// Pages behind the position are advised `POSIX_FADV_DONTNEED` in steps of at least
+/// this many bytes, to keep the number of system calls low.
+const DONT_NEED_STEP: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+enum Hint {
+    Sequential,
+    WillNeed,
+    DontNeed,
+}
+
+/// Gives the OS page cache advice about a file as it is streamed.
+pub(super) struct Advisor {
+    advice: PageCacheAdvice,
+    /// The end of the range that was last advised `POSIX_FADV_WILLNEED`.
+    will_need_end: u64,
+    /// The range that was last advised `POSIX_FADV_DONTNEED`.
+    dont_need_start: u64,
+    dont_need_end: u64,
+}
+
+impl Advisor {
+    pub fn new(file: &File, advice: PageCacheAdvice) -> Self {
+        advise(file, 0, 0, Hint::Sequential);
+
+        Self {
+            advice,
+            will_need_end: 0,
+            dont_need_start: 0,
+            dont_need_end: 0,
+        }
+    }
+
+    pub fn on_read(&mut self, file: &File, pos: u64) {
+        let will_need_bytes = self.advice.will_need_bytes;
+        if will_need_bytes > 0 {
+            let will_need_start = self.will_need_end.saturating_sub(will_need_bytes);
+
+            // Advise the next range once half of the last one has been read, or once the
+            // position has moved out of it.
+            if pos < will_need_start || pos + (will_need_bytes / 2) >= self.will_need_end {
+                advise(file, pos, will_need_bytes, Hint::WillNeed);
+                self.will_need_end = pos + will_need_bytes;
+            }
+        }
+
+        self.drop_behind(file, pos);
+    }
+
+    pub fn on_write(&mut self, file: &File, pos: u64) {
+        self.drop_behind(file, pos);
+    }
+
+    fn drop_behind(&mut self, file: &File, pos: u64) {
+        let keep_behind_bytes = match self.advice.keep_behind_bytes {
+            Some(keep_behind_bytes) => keep_behind_bytes,
+            None => return,
+        };
+
+        let end = pos.saturating_sub(keep_behind_bytes);
+        if end < self.dont_need_end + DONT_NEED_STEP {
+            return;
+        }
+
+        // Dirty pages are only written back (and not dropped) the first time they are
+        // advised, so the last range is advised again to drop it once it is clean.
+        advise(
+            file,
+            self.dont_need_start,
+            end - self.dont_need_start,
+            Hint::DontNeed,
+        );
+
+        self.dont_need_start = self.dont_need_end;
+        self.dont_need_end = end;
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn advise(file: &File, offset: u64, len: u64, hint: Hint) {
+    use rustix::fs::Advice;
+
+    let advice = match hint {
+        Hint::Sequential => Advice::Sequential,
+        Hint::WillNeed => Advice::WillNeed,
+        Hint::DontNeed => Advice::DontNeed,
+    };
+
+    // The advice is only a hint, so failing to give it is not an error.
+    let _ = rustix::fs::fadvise(file, offset, len, advice);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise(_file: &File, _offset: u64, _len: u64, _hint: Hint) {}
@@ -0,0 +1,227 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use super::{DIRECT_IO_ALIGNMENT, DIRECT_IO_BUFFER_SIZE};
+
+#[cfg(target_os = "linux")]
+pub(super) fn set_direct_flag(open_opts: &mut OpenOptions) {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    open_opts.custom_flags(rustix::fs::OFlags::DIRECT.bits() as i32);
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(super) fn set_direct_flag(_open_opts: &mut OpenOptions) {}
+
+/// A buffer whose start is aligned to [`DIRECT_IO_ALIGNMENT`].
+struct AlignedBuf {
+    data: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl AlignedBuf {
+    fn new(len: usize) -> Self {
+        // The allocation never moves, since the vec is never resized.
+        let data = vec![0; len + DIRECT_IO_ALIGNMENT];
+        let misalignment = data.as_ptr() as usize % DIRECT_IO_ALIGNMENT;
+        let offset = (DIRECT_IO_ALIGNMENT - misalignment) % DIRECT_IO_ALIGNMENT;
+
+        Self { data, offset, len }
+    }
+
+    fn get(&self) -> &[u8] {
+        &self.data[self.offset..self.offset + self.len]
+    }
+
+    fn get_mut(&mut self) -> &mut [u8] {
+        &mut self.data[self.offset..self.offset + self.len]
+    }
+}
+
+/// An aligned window into a file that is opened for direct IO. All reads and writes
+/// go through the window, and only whole aligned blocks are read from and written to
+/// the file.
+pub(super) struct DirectBuffer {
+    window: AlignedBuf,
+    /// A single aligned block, used to write to the part of the file before the window.
+    scratch: AlignedBuf,
+    /// The offset in the file the window starts from. This is always aligned.
+    start: u64,
+    /// The number of bytes in the window that hold data. The rest of the window is
+    /// always zeroed.
+    len: usize,
+    /// The range of bytes in the window that have not been written to the file yet.
+    dirty: Option<Range<usize>>,
+    /// The length of the file, including the data that has not been written to it yet.
+    file_len: u64,
+}
+
+impl DirectBuffer {
+    pub fn new(file: &File) -> io::Result<Self> {
+        let mut buffer = Self {
+            window: AlignedBuf::new(DIRECT_IO_BUFFER_SIZE),
+            scratch: AlignedBuf::new(DIRECT_IO_ALIGNMENT),
+            start: 0,
+            len: 0,
+            dirty: None,
+            file_len: file.metadata()?.len(),
+        };
+        buffer.load(file, 0)?;
+
+        Ok(buffer)
+    }
+
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    pub fn read(&mut self, file: &File, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if pos < self.start || pos >= self.start + self.len as u64 {
+            if pos >= self.file_len {
+                return Ok(0);
+            }
+
+            self.load(file, pos)?;
+        }
+
+        let offset = (pos - self.start) as usize;
+        let n = buf.len().min(self.len.saturating_sub(offset));
+        buf[..n].copy_from_slice(&self.window.get()[offset..offset + n]);
+
+        Ok(n)
+    }
+
+    pub fn write(&mut self, file: &File, pos: u64, buf: &[u8]) -> io::Result<usize> {
+        if pos < self.start {
+            return self.write_before_window(file, pos, buf);
+        }
+
+        if pos >= self.start + DIRECT_IO_BUFFER_SIZE as u64 {
+            self.load(file, pos)?;
+        }
+
+        let offset = (pos - self.start) as usize;
+        let n = buf.len().min(DIRECT_IO_BUFFER_SIZE - offset);
+        self.window.get_mut()[offset..offset + n].copy_from_slice(&buf[..n]);
+
+        self.len = self.len.max(offset + n);
+        self.file_len = self.file_len.max(pos + n as u64);
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(offset)..dirty.end.max(offset + n),
+            None => offset..offset + n,
+        });
+
+        Ok(n)
+    }
+
+    /// Write any data in the window that has not been written to the file yet.
+    pub fn flush(&mut self, file: &File) -> io::Result<()> {
+        if let Some(dirty) = &self.dirty {
+            let start = align_down(dirty.start as u64) as usize;
+            let end = align_up(dirty.end);
+
+            // The window is zeroed after its data, so the last block is padded with zeros.
+            write_at(
+                file,
+                self.start + start as u64,
+                &self.window.get()[start..end],
+            )?;
+
+            // Remove the padding that was written past the end of the file.
+            if self.start + end as u64 > self.file_len {
+                file.set_len(self.file_len)?;
+            }
+
+            self.dirty = None;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_len(&mut self, file: &File, size: u64) -> io::Result<()> {
+        self.flush(file)?;
+
+        file.set_len(size)?;
+        self.file_len = size;
+
+        // Part of the window may have been cut off.
+        self.load(file, self.start)
+    }
+
+    /// Move the window to the aligned block that holds `pos`.
+    fn load(&mut self, file: &File, pos: u64) -> io::Result<()> {
+        self.flush(file)?;
+
+        self.start = align_down(pos);
+        self.len = read_at(file, self.start, self.window.get_mut())?;
+        self.window.get_mut()[self.len..].fill(0);
+
+        Ok(())
+    }
+
+    /// Merge data into the aligned block of the file it is written to, without moving
+    /// the window. This is used to update the header of a file while it is recorded.
+    fn write_before_window(&mut self, file: &File, pos: u64, buf: &[u8]) -> io::Result<usize> {
+        let block_start = align_down(pos);
+        let offset = (pos - block_start) as usize;
+        let n = buf
+            .len()
+            .min(DIRECT_IO_ALIGNMENT - offset)
+            .min((self.start - pos) as usize);
+
+        let block = self.scratch.get_mut();
+        let block_len = read_at(file, block_start, block)?;
+        block[block_len..].fill(0);
+        block[offset..offset + n].copy_from_slice(&buf[..n]);
+
+        write_at(file, block_start, block)?;
+
+        self.file_len = self.file_len.max(pos + n as u64);
+        if block_start + DIRECT_IO_ALIGNMENT as u64 > self.file_len {
+            file.set_len(self.file_len)?;
+        }
+
+        Ok(n)
+    }
+}
+
+fn align_down(n: u64) -> u64 {
+    n - (n % DIRECT_IO_ALIGNMENT as u64)
+}
+
+fn align_up(n: usize) -> usize {
+    match n % DIRECT_IO_ALIGNMENT {
+        0 => n,
+        rem => n + DIRECT_IO_ALIGNMENT - rem,
+    }
+}
+
+/// Read as many bytes as fit into `buf` from the file at `offset`, and return the
+/// number of bytes that were read.
+fn read_at(mut file: &File, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+
+        // Direct IO only reads a partial block at the end of the file.
+        if filled % DIRECT_IO_ALIGNMENT != 0 {
+            break;
+        }
+    }
+
+    Ok(filled)
+}
+
+fn write_at(mut file: &File, offset: u64, buf: &[u8]) -> io::Result<()> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(buf)
+}
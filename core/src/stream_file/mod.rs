@@ -0,0 +1,243 @@
+//! A file that gives the OS hints about how it is streamed, or that bypasses the OS
+//! page cache entirely.
+//!
+//! Streaming large files through the page cache evicts data that other parts of the
+//! application (like a sample library) want to keep cached, and recording to disk
+//! accumulates dirty pages that are then flushed in bursts. [`StreamFile`] can be used
+//! by decoders and encoders to avoid both on Linux. See [`FileIoOptions`].
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+mod advice;
+mod direct;
+
+#[cfg(test)]
+mod tests;
+
+use advice::Advisor;
+use direct::DirectBuffer;
+
+/// The alignment of the file offsets, lengths, and buffers used for direct IO.
+pub const DIRECT_IO_ALIGNMENT: usize = 4096;
+
+/// The number of bytes in the buffer that direct IO goes through. This must be a
+/// multiple of [`DIRECT_IO_ALIGNMENT`].
+const DIRECT_IO_BUFFER_SIZE: usize = 256 * 1024;
+
+/// Options for how a stream accesses its file on disk.
+///
+/// These options only have an effect on Linux, and are ignored on other platforms.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileIoOptions {
+    /// Hints to the OS page cache about which parts of the file will be needed next and
+    /// which ones will not be needed again. See [`PageCacheAdvice`].
+    ///
+    /// This is ignored when `direct_io` is `true`.
+    ///
+    /// The default is `None`.
+    pub advice: Option<PageCacheAdvice>,
+
+    /// Open the file with `O_DIRECT`, so that reads and writes bypass the OS page cache
+    /// entirely. All IO goes through a buffer of 256KiB that is aligned to
+    /// [`DIRECT_IO_ALIGNMENT`].
+    ///
+    /// Not every file system supports direct IO, in which case opening the file fails.
+    ///
+    /// The default is `false`.
+    pub direct_io: bool,
+}
+
+/// Hints that are given to the OS page cache with `posix_fadvise` while a file is
+/// streamed.
+///
+/// The whole file is advised `POSIX_FADV_SEQUENTIAL` when it is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCacheAdvice {
+    /// The number of bytes ahead of the read position that are advised
+    /// `POSIX_FADV_WILLNEED`, so that the OS reads them into the page cache before they
+    /// are needed. This is not used when writing.
+    ///
+    /// Set this to `0` to not give this advice.
+    ///
+    /// The default is `1MiB`.
+    pub will_need_bytes: u64,
+
+    /// The number of bytes behind the read or write position to keep in the page
+    /// cache. Everything further behind is advised `POSIX_FADV_DONTNEED`. When writing,
+    /// this also starts writing the dirty pages behind the position back to disk early,
+    /// instead of in a single burst later on.
+    ///
+    /// Set this to `None` to keep everything in the page cache.
+    ///
+    /// The default is `Some(1MiB)`.
+    pub keep_behind_bytes: Option<u64>,
+}
+
+impl Default for PageCacheAdvice {
+    fn default() -> Self {
+        Self {
+            will_need_bytes: 1024 * 1024,
+            keep_behind_bytes: Some(1024 * 1024),
+        }
+    }
+}
+
+/// A file that is streamed from or to with the given [`FileIoOptions`].
+///
+/// Without any options this behaves exactly like a [`File`].
+pub struct StreamFile {
+    file: File,
+    /// The current position in the file.
+    pos: u64,
+    advisor: Option<Advisor>,
+    direct: Option<DirectBuffer>,
+}
+
+impl StreamFile {
+    /// Open a file in read-only mode.
+    pub fn open<P: AsRef<Path>>(path: P, opts: FileIoOptions) -> io::Result<Self> {
+        let mut open_opts = OpenOptions::new();
+        open_opts.read(true);
+
+        Self::new(open_opts, path.as_ref(), opts)
+    }
+
+    /// Open a file in write-only mode, creating it if it does not exist and truncating
+    /// it if it does.
+    ///
+    /// With direct IO, the file is also opened for reading so that writes that do not
+    /// line up with [`DIRECT_IO_ALIGNMENT`] can be merged with the existing data.
+    pub fn create<P: AsRef<Path>>(path: P, opts: FileIoOptions) -> io::Result<Self> {
+        let mut open_opts = OpenOptions::new();
+        open_opts.write(true).create(true).truncate(true);
+
+        Self::new(open_opts, path.as_ref(), opts)
+    }
+
+    fn new(mut open_opts: OpenOptions, path: &Path, opts: FileIoOptions) -> io::Result<Self> {
+        let direct_io = opts.direct_io && cfg!(target_os = "linux");
+
+        if direct_io {
+            open_opts.read(true);
+            direct::set_direct_flag(&mut open_opts);
+        }
+
+        let file = open_opts.open(path)?;
+
+        let direct = if direct_io {
+            Some(DirectBuffer::new(&file)?)
+        } else {
+            None
+        };
+
+        let advisor = match opts.advice {
+            Some(advice) if !direct_io => Some(Advisor::new(&file, advice)),
+            _ => None,
+        };
+
+        Ok(Self {
+            file,
+            pos: 0,
+            advisor,
+            direct,
+        })
+    }
+
+    /// Truncate or extend the file to the given number of bytes.
+    ///
+    /// The position in the file is not changed.
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        match &mut self.direct {
+            Some(direct) => direct.set_len(&self.file, size),
+            None => self.file.set_len(size),
+        }
+    }
+
+    /// The underlying file.
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl Read for StreamFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = match &mut self.direct {
+            Some(direct) => direct.read(&self.file, self.pos, buf)?,
+            None => self.file.read(buf)?,
+        };
+        self.pos += n as u64;
+
+        if let Some(advisor) = &mut self.advisor {
+            advisor.on_read(&self.file, self.pos);
+        }
+
+        Ok(n)
+    }
+}
+
+impl Write for StreamFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = match &mut self.direct {
+            Some(direct) => direct.write(&self.file, self.pos, buf)?,
+            None => self.file.write(buf)?,
+        };
+        self.pos += n as u64;
+
+        if let Some(advisor) = &mut self.advisor {
+            advisor.on_write(&self.file, self.pos);
+        }
+
+        Ok(n)
+    }
+
+    /// With direct IO, this writes any buffered data to the file, so that the file is
+    /// complete even if the stream is never closed.
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.direct {
+            Some(direct) => direct.flush(&self.file),
+            None => self.file.flush(),
+        }
+    }
+}
+
+impl Seek for StreamFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = match &self.direct {
+            // Direct IO never uses the cursor of the file.
+            Some(direct) => {
+                let (base, offset) = match pos {
+                    SeekFrom::Start(pos) => (pos, 0),
+                    SeekFrom::End(offset) => (direct.file_len(), offset),
+                    SeekFrom::Current(offset) => (self.pos, offset),
+                };
+
+                let new_pos = if offset >= 0 {
+                    base.checked_add(offset.unsigned_abs())
+                } else {
+                    base.checked_sub(offset.unsigned_abs())
+                };
+
+                new_pos.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )
+                })?
+            }
+            None => self.file.seek(pos)?,
+        };
+
+        Ok(self.pos)
+    }
+}
+
+impl Drop for StreamFile {
+    fn drop(&mut self) {
+        // Like `BufWriter`, errors are ignored here. Call `flush()` to handle them.
+        if let Some(direct) = &mut self.direct {
+            let _ = direct.flush(&self.file);
+        }
+    }
+}
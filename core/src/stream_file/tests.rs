@@ -0,0 +1,111 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use super::*;
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("creek_stream_file_{}_{}", std::process::id(), name))
+}
+
+fn test_bytes(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+/// Write `bytes` in small chunks, and rewrite a "header" at the start of the file after
+/// every chunk like `WavEncoder` does.
+fn write_with_header(file: &mut StreamFile, bytes: &[u8]) {
+    let header_len = 44;
+    file.write_all(&[0; 44]).unwrap();
+
+    for (i, chunk) in bytes[header_len..].chunks(1000).enumerate() {
+        file.write_all(chunk).unwrap();
+
+        let pos = file.stream_position().unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(&[i as u8; 44]).unwrap();
+        file.seek(SeekFrom::Start(pos)).unwrap();
+        file.flush().unwrap();
+    }
+
+    // Write the final header.
+    file.seek(SeekFrom::Start(0)).unwrap();
+    file.write_all(&bytes[..header_len]).unwrap();
+    file.flush().unwrap();
+}
+
+fn read_in_chunks(file: &mut StreamFile, chunk_len: usize) -> Vec<u8> {
+    let mut read = Vec::new();
+    let mut chunk = vec![0; chunk_len];
+    loop {
+        let n = file.read(&mut chunk).unwrap();
+        if n == 0 {
+            return read;
+        }
+        read.extend_from_slice(&chunk[..n]);
+    }
+}
+
+fn check_file(opts: FileIoOptions, name: &str) {
+    let path = temp_file(name);
+    let bytes = test_bytes((DIRECT_IO_BUFFER_SIZE * 2) + 12345);
+
+    let mut file = StreamFile::create(&path, opts).unwrap();
+    write_with_header(&mut file, &bytes);
+
+    // The file is complete before the stream is dropped.
+    assert!(std::fs::read(&path).unwrap() == bytes);
+    drop(file);
+    assert!(std::fs::read(&path).unwrap() == bytes);
+
+    let mut file = StreamFile::open(&path, opts).unwrap();
+    assert!(read_in_chunks(&mut file, 3000) == bytes);
+
+    // Seek around the file.
+    for &pos in &[300_000, 10, DIRECT_IO_BUFFER_SIZE * 2, bytes.len() - 5] {
+        file.seek(SeekFrom::Start(pos as u64)).unwrap();
+        let mut buf = vec![0; 100];
+        let n = file.read(&mut buf).unwrap();
+        assert!(n > 0);
+        assert_eq!(buf[..n], bytes[pos..pos + n]);
+    }
+    assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), bytes.len() as u64);
+    assert_eq!(file.read(&mut [0; 10]).unwrap(), 0);
+    drop(file);
+
+    // Truncate the file and write it again.
+    let mut file = StreamFile::create(&path, opts).unwrap();
+    file.write_all(&bytes[..5000]).unwrap();
+    file.set_len(0).unwrap();
+    file.seek(SeekFrom::Start(0)).unwrap();
+    write_with_header(&mut file, &bytes[..70_000]);
+    drop(file);
+    assert!(std::fs::read(&path).unwrap() == bytes[..70_000]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn buffered_io() {
+    check_file(FileIoOptions::default(), "buffered");
+}
+
+#[test]
+fn page_cache_advice() {
+    let opts = FileIoOptions {
+        advice: Some(PageCacheAdvice {
+            will_need_bytes: 64 * 1024,
+            keep_behind_bytes: Some(0),
+        }),
+        direct_io: false,
+    };
+    check_file(opts, "advice");
+}
+
+#[test]
+fn direct_io() {
+    let opts = FileIoOptions {
+        advice: None,
+        direct_io: true,
+    };
+    check_file(opts, "direct");
+}
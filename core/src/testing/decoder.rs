@@ -0,0 +1,404 @@
+use std::fmt::Debug;
+use std::path::Path;
+
+use crate::read::decode_into_block;
+use crate::read::ReadError;
+use crate::{DataBlock, Decoder, FileInfo, ReadDiskStream, ReadStreamOptions, SeekMode};
+
+use super::assert_frames_eq;
+
+/// Decode the whole file from the start with the decoder's default additional options.
+///
+/// While decoding, this checks that:
+/// * `Decoder::current_frame()` starts at `0` and tracks every decoded frame.
+/// * `Decoder::decode_into()` fills every channel with exactly `block_size` frames,
+///   except for the last block which contains however many frames are left, and reports
+///   the end of the file once fewer frames are left.
+/// * The total number of decoded frames is exactly `FileInfo::num_frames`.
+/// * Calling `Decoder::decode_into()` after the end of the file has been reached writes
+///   nothing.
+///
+/// Returns the info of the file and every decoded frame.
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal decoder error occurs,
+/// or if the decoder does not honour one of the above invariants.
+pub fn decode_linear<D: Decoder>(
+    file: &Path,
+    block_size: usize,
+) -> (FileInfo<D::FileParams>, Vec<Vec<D::T>>) {
+    decode_linear_with_opts::<D>(file, block_size, Default::default)
+}
+
+/// The same as [`decode_linear`], except the decoder is opened with the additional
+/// options returned from `opts`.
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal decoder error occurs,
+/// or if the decoder does not honour one of the invariants checked in [`decode_linear`].
+pub fn decode_linear_with_opts<D: Decoder>(
+    file: &Path,
+    block_size: usize,
+    opts: impl Fn() -> D::AdditionalOpts,
+) -> (FileInfo<D::FileParams>, Vec<Vec<D::T>>) {
+    let (mut decoder, file_info) = match D::new(file.into(), 0, block_size, opts()) {
+        Ok(res) => res,
+        Err(e) => panic!("{:?}: failed to open file: {}", file, e),
+    };
+    let num_channels = usize::from(file_info.num_channels);
+
+    assert_eq!(decoder.current_frame(), 0, "{:?}: initial frame", file);
+
+    let mut samples: Vec<Vec<D::T>> = (0..num_channels)
+        .map(|_| Vec::with_capacity(file_info.num_frames))
+        .collect();
+    let mut data_block = DataBlock::new(num_channels, block_size);
+
+    while decoder.current_frame() < file_info.num_frames {
+        let res = decode_into_block(&mut decoder, &mut data_block).unwrap();
+
+        let decoded_frames = data_block.num_frames();
+
+        let remaining_frames = file_info.num_frames - samples[0].len();
+        assert_eq!(
+            decoded_frames,
+            block_size.min(remaining_frames),
+            "{:?}: block at frame {} was not filled with `block_size` frames",
+            file,
+            samples[0].len()
+        );
+        if decoded_frames < block_size {
+            assert!(
+                res.reached_end_of_file,
+                "{:?}: the end of the file was not reported at frame {}",
+                file,
+                samples[0].len()
+            );
+        }
+
+        for (dst_ch, src_ch) in samples.iter_mut().zip(data_block.channels()) {
+            dst_ch.extend_from_slice(src_ch);
+        }
+
+        assert_eq!(
+            decoder.current_frame(),
+            samples[0].len(),
+            "{:?}: current frame does not match the number of decoded frames",
+            file
+        );
+    }
+
+    assert_eq!(
+        samples[0].len(),
+        file_info.num_frames,
+        "{:?}: number of decoded frames",
+        file
+    );
+
+    // Decoding past the end of the file should do nothing.
+    let res = decode_into_block(&mut decoder, &mut data_block).unwrap();
+    assert!(
+        res.frames == 0 && res.reached_end_of_file,
+        "{:?}: decoded data after the end of the file",
+        file
+    );
+    assert_eq!(
+        decoder.current_frame(),
+        file_info.num_frames,
+        "{:?}: current frame after the end of the file",
+        file
+    );
+
+    (file_info, samples)
+}
+
+/// The positions used when checking seeks in a file with `num_frames` frames.
+fn seek_positions(num_frames: usize, block_size: usize) -> Vec<usize> {
+    let mut positions = vec![
+        0,
+        1,
+        block_size.saturating_sub(1),
+        block_size,
+        block_size + 1,
+        num_frames / 3,
+        (num_frames / 2) + 17,
+        num_frames.saturating_sub(block_size + 1),
+        num_frames.saturating_sub(block_size / 2),
+        num_frames.saturating_sub(1),
+    ];
+
+    positions.retain(|&frame| frame < num_frames);
+    positions
+}
+
+/// Check that a decoder honours the contract of the [`Decoder`] trait.
+///
+/// This decodes the whole file with [`decode_linear`] and then checks that:
+/// * `Decoder::seek()` followed by `Decoder::decode_into()` produces the exact same frames as
+///   the linear decode, and that `Decoder::current_frame()` reports the seeked frame.
+/// * `Decoder::seek()` to a frame past the end of the file sets the read position to
+///   the end of the file.
+/// * `Decoder::new()` with a `start_frame` produces the exact same frames as seeking to
+///   that frame.
+///
+/// The decoder's default additional options are used. Returns the info of the file and
+/// every frame from the linear decode, which can be passed on to [`check_read_stream`].
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal decoder error occurs,
+/// or if the decoder does not honour one of the above invariants.
+pub fn check_decoder<D: Decoder>(
+    file: &Path,
+    block_size: usize,
+) -> (FileInfo<D::FileParams>, Vec<Vec<D::T>>)
+where
+    D::T: PartialEq + Debug,
+{
+    check_decoder_with_opts::<D>(file, block_size, Default::default)
+}
+
+/// The same as [`check_decoder`], except every decoder is opened with the additional
+/// options returned from `opts`.
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal decoder error occurs,
+/// or if the decoder does not honour one of the invariants checked in [`check_decoder`].
+pub fn check_decoder_with_opts<D: Decoder>(
+    file: &Path,
+    block_size: usize,
+    opts: impl Fn() -> D::AdditionalOpts,
+) -> (FileInfo<D::FileParams>, Vec<Vec<D::T>>)
+where
+    D::T: PartialEq + Debug,
+{
+    let (file_info, linear) = decode_linear_with_opts::<D>(file, block_size, &opts);
+
+    let num_channels = usize::from(file_info.num_channels);
+    let num_frames = file_info.num_frames;
+
+    let (mut decoder, _) = D::new(file.into(), 0, block_size, opts()).unwrap();
+    let mut data_block = DataBlock::new(num_channels, block_size);
+
+    for frame in seek_positions(num_frames, block_size) {
+        let context = format!("{:?}: seek to frame {}", file, frame);
+
+        decoder.seek(frame).unwrap();
+        assert_eq!(decoder.current_frame(), frame, "{}", context);
+
+        decode_into_block(&mut decoder, &mut data_block).unwrap();
+
+        let expected_frames = block_size.min(num_frames - frame);
+        assert_eq!(data_block.num_frames(), expected_frames, "{}", context);
+        let channels: Vec<&[D::T]> = data_block.channels().collect();
+        assert_frames_eq(&context, &channels, &linear, frame, expected_frames);
+    }
+
+    // Seeking past the end of the file should clamp to the end.
+    decoder.seek(num_frames + block_size).unwrap();
+    assert_eq!(
+        decoder.current_frame(),
+        num_frames,
+        "{:?}: seek past the end of the file",
+        file
+    );
+    decode_into_block(&mut decoder, &mut data_block).unwrap();
+    assert!(
+        data_block.num_frames() == 0,
+        "{:?}: decoded data after seeking past the end of the file",
+        file
+    );
+
+    // Opening the decoder at a given frame should be the same as seeking to it.
+    for frame in seek_positions(num_frames, block_size) {
+        let context = format!("{:?}: open at frame {}", file, frame);
+
+        let (mut decoder, _) = D::new(file.into(), frame, block_size, opts()).unwrap();
+        assert_eq!(decoder.current_frame(), frame, "{}", context);
+
+        decode_into_block(&mut decoder, &mut data_block).unwrap();
+
+        let expected_frames = block_size.min(num_frames - frame);
+        assert_eq!(data_block.num_frames(), expected_frames, "{}", context);
+        let channels: Vec<&[D::T]> = data_block.channels().collect();
+        assert_frames_eq(&context, &channels, &linear, frame, expected_frames);
+    }
+
+    (file_info, linear)
+}
+
+/// Blocks the current thread until the cache with the given index has been filled.
+fn block_until_cached<D: Decoder>(
+    read_stream: &mut ReadDiskStream<D>,
+    cache_index: usize,
+    start_frame: usize,
+) {
+    read_stream.cache(cache_index, start_frame).unwrap();
+
+    while !read_stream.is_cache_ready(cache_index).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
+/// Check that reading a file through a [`ReadDiskStream`] produces the exact same frames
+/// as `linear`, both when reading from the start and after seeking with and without a
+/// cache.
+///
+/// `linear` is every frame in the file, as returned from [`check_decoder`] or
+/// [`decode_linear`].
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal error occurs, or if the
+/// stream does not produce the expected frames.
+pub fn check_read_stream<D: Decoder>(file: &Path, block_size: usize, linear: &[Vec<D::T>])
+where
+    D::T: PartialEq + Debug,
+{
+    check_read_stream_with_opts::<D>(file, block_size, linear, Default::default())
+}
+
+/// The same as [`check_read_stream`], except the stream is opened with the given
+/// additional decoder options.
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal error occurs, or if the
+/// stream does not produce the expected frames.
+pub fn check_read_stream_with_opts<D: Decoder>(
+    file: &Path,
+    block_size: usize,
+    linear: &[Vec<D::T>],
+    opts: D::AdditionalOpts,
+) where
+    D::T: PartialEq + Debug,
+{
+    let num_cache_blocks = 4;
+
+    let mut read_stream = match ReadDiskStream::<D>::new(
+        file,
+        0,
+        ReadStreamOptions {
+            block_size,
+            num_cache_blocks,
+            additional_opts: opts,
+            ..Default::default()
+        },
+    ) {
+        Ok(read_stream) => read_stream,
+        Err(e) => panic!("{:?}: failed to open read stream: {}", file, e),
+    };
+
+    let num_channels = usize::from(read_stream.info().num_channels);
+    let num_frames = read_stream.info().num_frames;
+
+    assert_eq!(num_channels, linear.len(), "{:?}: number of channels", file);
+    assert_eq!(num_frames, linear[0].len(), "{:?}: number of frames", file);
+
+    read_stream.cache(0, 0).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+
+    // Read the whole file. The buffer is longer than the file to check that reading
+    // stops at the end of the file.
+    let mut buffer: Vec<Vec<D::T>> = (0..num_channels)
+        .map(|_| vec![Default::default(); num_frames + block_size])
+        .collect();
+    let read_frames = read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+    let context = format!("{:?}: read stream from the start", file);
+    assert_eq!(read_frames, num_frames, "{}", context);
+    assert_eq!(read_stream.playhead(), num_frames, "{}", context);
+    assert_frames_eq(&context, &buffer, linear, 0, num_frames);
+
+    assert!(
+        matches!(read_stream.read(1), Err(ReadError::EndOfFile)),
+        "{:?}: read stream did not return `EndOfFile`",
+        file
+    );
+
+    let mut buffer: Vec<Vec<D::T>> = (0..num_channels)
+        .map(|_| vec![Default::default(); (block_size * 3) + 5])
+        .collect();
+
+    // Seeking into the cached range should not need to buffer.
+    block_until_cached(&mut read_stream, 0, 0);
+    let cached_frames = (block_size * num_cache_blocks).min(num_frames);
+    assert!(
+        read_stream.seek(cached_frames / 2, SeekMode::Auto).unwrap(),
+        "{:?}: seek into the cached range missed the cache",
+        file
+    );
+
+    // Seek without the cache and read across multiple blocks.
+    for frame in seek_positions(num_frames, block_size) {
+        let context = format!("{:?}: read stream after seeking to frame {}", file, frame);
+
+        read_stream.seek(frame, SeekMode::NoCache).unwrap();
+        assert_eq!(read_stream.playhead(), frame, "{}", context);
+        read_stream.block_until_ready().unwrap();
+
+        let read_frames = read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+        assert_eq!(
+            read_frames,
+            buffer[0].len().min(num_frames - frame),
+            "{}",
+            context
+        );
+        assert_frames_eq(&context, &buffer, linear, frame, read_frames);
+    }
+
+    // Seek using the cache.
+    for frame in [0, 1, block_size + 3, cached_frames - 1] {
+        if frame >= cached_frames {
+            continue;
+        }
+
+        let context = format!(
+            "{:?}: read stream after seeking to cached frame {}",
+            file, frame
+        );
+
+        assert!(
+            read_stream.seek(frame, SeekMode::TryOne(0)).unwrap(),
+            "{}",
+            context
+        );
+        read_stream.block_until_ready().unwrap();
+
+        let read_frames = read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+        assert_eq!(
+            read_frames,
+            buffer[0].len().min(num_frames - frame),
+            "{}",
+            context
+        );
+        assert_frames_eq(&context, &buffer, linear, frame, read_frames);
+    }
+}
+
+/// Check that decoding a file produces the exact same frames as `expected`. This is
+/// useful for checking the output of an encoder.
+///
+/// # Panics
+///
+/// This will panic if the file could not be opened, if a fatal decoder error occurs,
+/// if the decoder does not honour the invariants checked in [`decode_linear`], or if
+/// the decoded frames differ from `expected`.
+pub fn check_file_frames<D: Decoder>(file: &Path, block_size: usize, expected: &[Vec<D::T>])
+where
+    D::T: PartialEq + Debug,
+{
+    let (file_info, decoded) = decode_linear::<D>(file, block_size);
+
+    let context = format!("{:?}: decoded file", file);
+    assert_eq!(
+        file_info.num_frames,
+        expected[0].len(),
+        "{}: number of frames",
+        context
+    );
+    assert_frames_eq(&context, &decoded, expected, 0, expected[0].len());
+}
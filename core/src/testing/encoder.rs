@@ -0,0 +1,261 @@
+use std::path::Path;
+
+use crate::write::WriteError;
+use crate::{Encoder, WriteBlock, WriteDiskStream, WriteStreamOptions};
+
+/// Fill `block` with up to `block_size` frames from `frames` starting at `start_frame`.
+/// Returns the number of frames copied.
+fn fill_write_block<T: Copy + Clone + Default + Send>(
+    block: &mut WriteBlock<T>,
+    frames: &[Vec<T>],
+    start_frame: usize,
+    block_size: usize,
+) -> usize {
+    let num_frames = block_size.min(frames[0].len() - start_frame);
+
+    block.clear();
+    block.append_from_channels(frames, start_frame..start_frame + num_frames);
+
+    num_frames
+}
+
+/// Check that an encoder honours the contract of the [`Encoder`] trait.
+///
+/// This checks that:
+/// * `Encoder::new()` creates the file and returns a `FileInfo` with zero frames and
+///   the given number of channels and sample rate.
+/// * `Encoder::discard_file()` deletes the file.
+/// * `Encoder::discard_and_restart()` keeps the file so that it can be written to again.
+/// * Every frame in `frames` can be encoded in blocks of `block_size` frames (with a
+///   partially filled last block), and `Encoder::finish_file()` leaves the file in place.
+///
+/// The encoder's default additional options are used. Once this returns, the file
+/// contains every frame in `frames`, so it can be checked with [`check_file_frames`].
+///
+/// [`check_file_frames`]: super::check_file_frames
+///
+/// # Panics
+///
+/// This will panic if `frames` is empty, if the file could not be opened, if a fatal
+/// encoder error occurs, or if the encoder does not honour one of the above invariants.
+pub fn check_encoder<E: Encoder>(
+    file: &Path,
+    sample_rate: u32,
+    block_size: usize,
+    frames: &[Vec<E::T>],
+) {
+    check_encoder_with_opts::<E>(file, sample_rate, block_size, frames, Default::default);
+}
+
+/// The same as [`check_encoder`], except every encoder is opened with the additional
+/// options returned from `opts`.
+///
+/// # Panics
+///
+/// This will panic if `frames` is empty, if the file could not be opened, if a fatal
+/// encoder error occurs, or if the encoder does not honour one of the invariants checked
+/// in [`check_encoder`].
+pub fn check_encoder_with_opts<E: Encoder>(
+    file: &Path,
+    sample_rate: u32,
+    block_size: usize,
+    frames: &[Vec<E::T>],
+    opts: impl Fn() -> E::AdditionalOpts,
+) {
+    assert!(!frames.is_empty() && !frames[0].is_empty());
+
+    let num_channels = frames.len() as u16;
+    let num_write_blocks = E::DEFAULT_NUM_WRITE_BLOCKS;
+
+    let open = || match E::new(
+        file.into(),
+        num_channels,
+        sample_rate,
+        block_size,
+        num_write_blocks,
+        opts(),
+    ) {
+        Ok(res) => res,
+        Err(e) => panic!("{:?}: failed to open file: {}", file, e),
+    };
+
+    let mut block = WriteBlock::with_layout(usize::from(num_channels), block_size, E::BLOCK_LAYOUT);
+
+    // Discard a file.
+    let (mut encoder, file_info) = open();
+    assert_eq!(file_info.num_frames, 0, "{:?}: initial frames", file);
+    assert_eq!(file_info.num_channels, num_channels, "{:?}: channels", file);
+    assert_eq!(
+        file_info.sample_rate,
+        Some(sample_rate),
+        "{:?}: sample rate",
+        file
+    );
+    assert!(file.exists(), "{:?}: file was not created", file);
+
+    fill_write_block(&mut block, frames, 0, block_size);
+    encoder.encode(&block).unwrap();
+    encoder.discard_file().unwrap();
+    assert!(!file.exists(), "{:?}: file was not discarded", file);
+    drop(encoder);
+
+    // Discard and restart a file, then write all frames.
+    let (mut encoder, _) = open();
+
+    fill_write_block(&mut block, frames, 0, block_size);
+    encoder.encode(&block).unwrap();
+    encoder.discard_and_restart().unwrap();
+    assert!(
+        file.exists(),
+        "{:?}: file does not exist after restarting",
+        file
+    );
+
+    let mut frame = 0;
+    while frame < frames[0].len() {
+        frame += fill_write_block(&mut block, frames, frame, block_size);
+        encoder.encode(&block).unwrap();
+    }
+
+    encoder.finish_file().unwrap();
+    assert!(
+        file.exists(),
+        "{:?}: file does not exist after finishing",
+        file
+    );
+}
+
+/// Check that writing every frame in `frames` through a [`WriteDiskStream`] behaves
+/// as expected, including restarting the stream and rejecting invalid buffers.
+///
+/// The frames are written in chunks that do not line up with `block_size` to check
+/// that writes spanning two blocks work. Once this returns, the file contains every
+/// frame in `frames`, so it can be checked with [`check_file_frames`].
+///
+/// [`check_file_frames`]: super::check_file_frames
+///
+/// # Panics
+///
+/// This will panic if `frames` is empty, if the file could not be opened, if a fatal
+/// error occurs, or if the stream does not behave as expected.
+pub fn check_write_stream<E: Encoder>(
+    file: &Path,
+    sample_rate: u32,
+    block_size: usize,
+    frames: &[Vec<E::T>],
+) {
+    check_write_stream_with_opts::<E>(file, sample_rate, block_size, frames, Default::default());
+}
+
+/// The same as [`check_write_stream`], except the stream is opened with the given
+/// additional encoder options.
+///
+/// # Panics
+///
+/// This will panic if `frames` is empty, if the file could not be opened, if a fatal
+/// error occurs, or if the stream does not behave as expected.
+pub fn check_write_stream_with_opts<E: Encoder>(
+    file: &Path,
+    sample_rate: u32,
+    block_size: usize,
+    frames: &[Vec<E::T>],
+    opts: E::AdditionalOpts,
+) {
+    assert!(!frames.is_empty() && !frames[0].is_empty());
+
+    let num_channels = frames.len() as u16;
+
+    let mut write_stream = match WriteDiskStream::<E>::new(
+        file,
+        num_channels,
+        sample_rate,
+        WriteStreamOptions {
+            block_size,
+            additional_opts: opts,
+            ..Default::default()
+        },
+    ) {
+        Ok(write_stream) => write_stream,
+        Err(e) => panic!("{:?}: failed to open write stream: {}", file, e),
+    };
+
+    assert_eq!(
+        write_stream.info().num_frames,
+        0,
+        "{:?}: initial frames",
+        file
+    );
+    assert_eq!(
+        write_stream.info().num_channels,
+        num_channels,
+        "{:?}: channels",
+        file
+    );
+
+    // Invalid buffers should be rejected.
+    let too_long: Vec<Vec<E::T>> = (0..num_channels)
+        .map(|_| vec![Default::default(); block_size + 1])
+        .collect();
+    let too_long: Vec<&[E::T]> = too_long.iter().map(|ch| ch.as_slice()).collect();
+    assert!(
+        matches!(
+            write_stream.write(&too_long),
+            Err(WriteError::BufferTooLong { .. })
+        ),
+        "{:?}: buffer longer than the block size was not rejected",
+        file
+    );
+    assert!(
+        matches!(
+            write_stream.write(&too_long[0..too_long.len() - 1]),
+            Err(WriteError::InvalidBuffer)
+        ),
+        "{:?}: buffer with the wrong number of channels was not rejected",
+        file
+    );
+
+    let chunk_size = (block_size / 3) + 1;
+    let write_frames = |write_stream: &mut WriteDiskStream<E>, num_frames: usize| {
+        let mut frame = 0;
+        while frame < num_frames {
+            let len = chunk_size.min(num_frames - frame);
+            let buffer: Vec<&[E::T]> = frames.iter().map(|ch| &ch[frame..frame + len]).collect();
+
+            write_stream.block_until_ready().unwrap();
+            write_stream.write(&buffer).unwrap();
+
+            frame += len;
+            assert_eq!(
+                write_stream.info().num_frames,
+                frame,
+                "{:?}: number of written frames",
+                file
+            );
+        }
+    };
+
+    // Write some frames and then restart.
+    write_frames(&mut write_stream, frames[0].len().min(block_size + 1));
+    write_stream.discard_and_restart().unwrap();
+    assert_eq!(
+        write_stream.info().num_frames,
+        0,
+        "{:?}: frames after restarting",
+        file
+    );
+
+    write_frames(&mut write_stream, frames[0].len());
+
+    write_stream.finish_and_close().unwrap();
+    while !write_stream.finish_complete() {
+        write_stream.poll().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    assert_eq!(write_stream.num_files(), 1, "{:?}: number of files", file);
+    assert!(
+        file.exists(),
+        "{:?}: file does not exist after finishing",
+        file
+    );
+}
@@ -0,0 +1,61 @@
+//! Reusable checks for implementors of the [`Decoder`] and [`Encoder`] traits.
+//!
+//! These are meant to be called from a `#[test]` function. Each check panics with
+//! a description of the broken invariant if the implementation does not honour the
+//! contract of its trait.
+//!
+//! This module is only available with the `testing` feature.
+//!
+//! [`Decoder`]: crate::Decoder
+//! [`Encoder`]: crate::Encoder
+
+mod decoder;
+mod encoder;
+
+pub use decoder::{
+    check_decoder, check_decoder_with_opts, check_file_frames, check_read_stream,
+    check_read_stream_with_opts, decode_linear, decode_linear_with_opts,
+};
+pub use encoder::{
+    check_encoder, check_encoder_with_opts, check_write_stream, check_write_stream_with_opts,
+};
+
+use std::fmt::Debug;
+
+/// Panics if the frames in `actual` do not match the frames in `expected` starting
+/// from `start_frame`.
+fn assert_frames_eq<T: PartialEq + Debug, C: AsRef<[T]>>(
+    context: &str,
+    actual: &[C],
+    expected: &[Vec<T>],
+    start_frame: usize,
+    num_frames: usize,
+) {
+    assert_eq!(
+        actual.len(),
+        expected.len(),
+        "{}: number of channels differs",
+        context
+    );
+
+    for (ch_i, (actual_ch, expected_ch)) in actual.iter().zip(expected.iter()).enumerate() {
+        let actual_part = &actual_ch.as_ref()[0..num_frames];
+        let expected_part = &expected_ch[start_frame..start_frame + num_frames];
+
+        if let Some(i) = actual_part
+            .iter()
+            .zip(expected_part.iter())
+            .position(|(a, e)| a != e)
+        {
+            panic!(
+                "{}: channel {} differs at frame {} (read from frame {}): {:?} != {:?}",
+                context,
+                ch_i,
+                start_frame + i,
+                start_frame,
+                actual_part[i],
+                expected_part[i]
+            );
+        }
+    }
+}
@@ -0,0 +1,1394 @@
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+
+use crate::memory::{MemoryDecoderOpts, MemoryEncoderOpts, MemoryOpenError, MemorySink};
+use crate::read::error::ReadError;
+use crate::*;
+
+/// Options for a [`GatedDecoder`].
+#[derive(Debug, Default)]
+struct GatedDecoderOpts {
+    /// If this is `Some`, then opening the file blocks until a message is received.
+    gate: Option<Receiver<()>>,
+    /// The total number of frames decoded by the decoder.
+    decoded_frames: Arc<AtomicUsize>,
+    /// How long to wait before decoding each block.
+    decode_delay: Option<std::time::Duration>,
+    inner: MemoryDecoderOpts<f32>,
+}
+
+/// A `MemoryDecoder` that can be made to take as long as needed to open, and that
+/// counts how many frames it decodes.
+struct GatedDecoder {
+    decoder: MemoryDecoder<f32>,
+    decoded_frames: Arc<AtomicUsize>,
+    decode_delay: Option<std::time::Duration>,
+}
+
+impl Decoder for GatedDecoder {
+    type T = f32;
+    type AdditionalOpts = GatedDecoderOpts;
+    type FileParams = ();
+    type OpenError = MemoryOpenError;
+    type FatalError = Infallible;
+
+    const DEFAULT_BLOCK_SIZE: usize = 256;
+    const DEFAULT_NUM_CACHE_BLOCKS: usize = 0;
+    const DEFAULT_NUM_LOOK_AHEAD_BLOCKS: usize = 2;
+
+    fn new(
+        file: PathBuf,
+        start_frame: usize,
+        block_size: usize,
+        additional_opts: Self::AdditionalOpts,
+    ) -> Result<(Self, FileInfo<Self::FileParams>), Self::OpenError> {
+        if let Some(gate) = additional_opts.gate {
+            let _ = gate.recv();
+        }
+
+        let (decoder, file_info) =
+            MemoryDecoder::new(file, start_frame, block_size, additional_opts.inner)?;
+        Ok((
+            Self {
+                decoder,
+                decoded_frames: additional_opts.decoded_frames,
+                decode_delay: additional_opts.decode_delay,
+            },
+            file_info,
+        ))
+    }
+
+    fn seek(&mut self, frame: usize) -> Result<(), Self::FatalError> {
+        self.decoder.seek(frame)
+    }
+
+    fn decode(&mut self, data_block: &mut DataBlock<Self::T>) -> Result<(), Self::FatalError> {
+        if let Some(decode_delay) = self.decode_delay {
+            std::thread::sleep(decode_delay);
+        }
+
+        self.decoder.decode(data_block)?;
+        self.decoded_frames
+            .fetch_add(data_block.num_frames(), Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn current_frame(&self) -> usize {
+        self.decoder.current_frame()
+    }
+}
+
+fn gated_opts(gate: Option<Receiver<()>>, frames: &[Vec<f32>]) -> ReadStreamOptions<GatedDecoder> {
+    ReadStreamOptions {
+        additional_opts: GatedDecoderOpts {
+            gate,
+            decoded_frames: Default::default(),
+            decode_delay: None,
+            inner: MemoryDecoderOpts {
+                source: frames.to_vec().into(),
+                sample_rate: Some(44100),
+            },
+        },
+        ..Default::default()
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// A minimal executor that runs a future to completion on the current thread.
+fn block_on<F: std::future::Future + Unpin>(mut future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match std::pin::Pin::new(&mut future).poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[test]
+fn open_read_stream_without_blocking() {
+    let frames = vec![(0..1000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let (gate_tx, gate_rx) = mpsc::channel();
+    let mut pending =
+        ReadDiskStream::<GatedDecoder>::open("memory", 0, gated_opts(Some(gate_rx), &frames));
+
+    // The file cannot be opened until the gate is opened.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    assert!(pending.poll_open().is_none());
+
+    gate_tx.send(()).unwrap();
+    let mut read_stream = loop {
+        if let Some(res) = pending.poll_open() {
+            break res.unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    };
+
+    assert_eq!(read_stream.info().num_frames, 1000);
+
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    let data = read_stream.read(100).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][0..100]);
+}
+
+#[test]
+#[should_panic(expected = "the stream has already been returned")]
+fn poll_open_after_open() {
+    let frames = vec![vec![0.5; 10]];
+    let mut pending = ReadDiskStream::<GatedDecoder>::open("memory", 0, gated_opts(None, &frames));
+
+    while pending.poll_open().is_none() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    let _ = pending.poll_open();
+}
+
+#[test]
+fn await_read_stream() {
+    let frames = vec![vec![0.5; 10]];
+
+    let (gate_tx, gate_rx) = mpsc::channel();
+    let pending =
+        ReadDiskStream::<GatedDecoder>::open("memory", 0, gated_opts(Some(gate_rx), &frames));
+
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        gate_tx.send(()).unwrap();
+    });
+
+    let read_stream = block_on(pending).unwrap();
+    assert_eq!(read_stream.info().num_frames, 10);
+
+    // Errors are returned from the future.
+    let res = block_on(ReadDiskStream::<GatedDecoder>::open(
+        "memory",
+        0,
+        gated_opts(None, &[]),
+    ));
+    assert!(matches!(res, Err(MemoryOpenError::NoChannels)));
+}
+
+#[test]
+fn drop_pending_read_stream() {
+    let frames = vec![vec![0.5; 10]];
+
+    // Dropping the handle before the file has been opened must neither block nor panic.
+    let (gate_tx, gate_rx) = mpsc::channel();
+    let pending =
+        ReadDiskStream::<GatedDecoder>::open("memory", 0, gated_opts(Some(gate_rx), &frames));
+    drop(pending);
+    gate_tx.send(()).unwrap();
+}
+
+#[test]
+fn open_write_stream_without_blocking() {
+    let sink = MemorySink::<f32>::new();
+
+    let pending = WriteDiskStream::<MemoryEncoder<f32>>::open(
+        "memory",
+        2,
+        44100,
+        WriteStreamOptions {
+            additional_opts: MemoryEncoderOpts {
+                sink: sink.clone(),
+                max_frames_per_file: None,
+            },
+            ..Default::default()
+        },
+    );
+
+    let mut write_stream = block_on(pending).unwrap();
+    assert_eq!(write_stream.info().num_channels, 2);
+
+    write_stream.write(&[&[1.0, 2.0], &[3.0, 4.0]]).unwrap();
+    write_stream.finish_and_close().unwrap();
+    while !write_stream.finish_complete() {
+        write_stream.poll().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    assert_eq!(sink.files(), vec![vec![vec![1.0, 2.0], vec![3.0, 4.0]]]);
+}
+
+#[test]
+fn preload() {
+    let block_size = 1000;
+    let frames = vec![
+        (0..5432).map(|i| i as f32).collect::<Vec<f32>>(),
+        (0..5432).map(|i| -(i as f32)).collect::<Vec<f32>>(),
+    ];
+
+    let mut opts = gated_opts(None, &frames);
+    let decoded_frames = Arc::clone(&opts.additional_opts.decoded_frames);
+    opts.block_size = block_size;
+    opts.num_caches = 2;
+    opts.preload_max_frames = Some(5432);
+
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 100, opts).unwrap();
+    assert!(read_stream.is_preloaded());
+    assert_eq!(read_stream.playhead(), 100);
+
+    // The stream can be read from immediately.
+    assert!(read_stream.is_ready().unwrap());
+
+    let mut buffer = vec![vec![0.0; 2345]; 2];
+    for frame in [100, 0, 5431, 999, 1000, 4000, 17] {
+        assert!(read_stream.seek(frame, SeekMode::Auto).unwrap());
+        assert!(read_stream.is_ready().unwrap());
+
+        let read_frames = read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+        assert_eq!(read_frames, buffer[0].len().min(5432 - frame));
+        for (ch, expected_ch) in buffer.iter().zip(frames.iter()) {
+            assert_eq!(ch[0..read_frames], expected_ch[frame..frame + read_frames]);
+        }
+    }
+
+    // Only the preload decoded any frames.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    read_stream.is_ready().unwrap();
+    assert_eq!(decoded_frames.load(Ordering::SeqCst), 5432);
+}
+
+#[test]
+fn preload_too_long() {
+    let frames = vec![vec![0.5; 5433]];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 1000;
+    opts.preload_max_frames = Some(5432);
+
+    let read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    assert!(!read_stream.is_preloaded());
+}
+
+#[test]
+fn shared_head_cache() {
+    let frames = vec![
+        (0..5000).map(|i| i as f32).collect::<Vec<f32>>(),
+        (0..5000).map(|i| -(i as f32)).collect::<Vec<f32>>(),
+    ];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 4;
+    let mut first_voice = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    assert!(first_voice.head_cache().is_none());
+
+    first_voice.cache(0, 0).unwrap();
+    let head_cache = loop {
+        first_voice.is_ready().unwrap();
+        if let Some(head_cache) = first_voice.head_cache() {
+            break head_cache;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    };
+    assert_eq!(head_cache.num_frames(), 1024);
+    assert_eq!(head_cache.num_channels(), 2);
+
+    let mut voices = Vec::new();
+    for start_frame in [0, 500] {
+        let mut opts = gated_opts(None, &frames);
+        let decoded_frames = Arc::clone(&opts.additional_opts.decoded_frames);
+        // The layout of the head cache is used instead.
+        opts.block_size = 100;
+        opts.head_cache = Some(head_cache.clone());
+
+        let mut voice = ReadDiskStream::<GatedDecoder>::new("memory", start_frame, opts).unwrap();
+        assert_eq!(voice.block_size(), 256);
+        assert!(voice.is_ready().unwrap());
+
+        voices.push((voice, decoded_frames, start_frame));
+    }
+
+    // The voices do not decode the head of the file themselves. They only fetch the
+    // block after the head that replaces the block they started past.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    for (voice, decoded_frames, start_frame) in voices.iter_mut() {
+        voice.is_ready().unwrap();
+        let skipped_blocks = *start_frame / 256;
+        assert_eq!(decoded_frames.load(Ordering::SeqCst), skipped_blocks * 256);
+    }
+
+    // Moving the shared cache in one voice does not affect the others.
+    voices[0].0.cache(0, 3000).unwrap();
+
+    // Each voice then streams the rest of the file independently.
+    for (voice, _, start_frame) in voices.iter_mut() {
+        let mut buffer = vec![vec![0.0; 5000 - *start_frame]; 2];
+        assert_eq!(
+            voice.fill_buffer_blocking(&mut buffer).unwrap(),
+            buffer[0].len()
+        );
+        for (ch, expected_ch) in buffer.iter().zip(frames.iter()) {
+            assert_eq!(ch[..], expected_ch[*start_frame..]);
+        }
+    }
+
+    assert!(voices[1].0.seek(1000, SeekMode::Auto).unwrap());
+    assert!(first_voice.seek(1000, SeekMode::Auto).unwrap());
+
+    // A head cache is not used for a file with a different number of channels.
+    let mono = vec![vec![0.5; 5000]];
+    let mut opts = gated_opts(None, &mono);
+    opts.head_cache = Some(head_cache);
+    let voice = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    assert!(voice.head_cache().is_none());
+}
+
+/// Poll the stream for a while to let the server process its messages.
+fn poll_for_a_while(read_stream: &mut ReadDiskStream<GatedDecoder>) {
+    for _ in 0..50 {
+        read_stream.is_ready().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn memory_budget_evicts_least_recently_used_cache() {
+    let frames = vec![(0..10000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    // Each cache holds 2 cache blocks and 2 look-ahead blocks of 256 mono frames.
+    let cache_bytes = 4 * 256 * std::mem::size_of::<f32>();
+    let budget = MemoryBudget::new(cache_bytes * 5 / 2);
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 2;
+    opts.num_look_ahead_blocks = 2;
+    opts.num_caches = 3;
+    opts.memory_budget = Some(budget.clone());
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+
+    read_stream.cache(0, 0).unwrap();
+    poll_for_a_while(&mut read_stream);
+    read_stream.cache(1, 2000).unwrap();
+    poll_for_a_while(&mut read_stream);
+    assert_eq!(budget.used_bytes(), cache_bytes * 2);
+
+    // Use the first cache so that the second one is the least recently used.
+    assert!(read_stream.seek(100, SeekMode::TryOne(0)).unwrap());
+
+    read_stream.cache(2, 4000).unwrap();
+    poll_for_a_while(&mut read_stream);
+    assert!(budget.used_bytes() <= budget.max_bytes());
+
+    assert!(read_stream.seek(4000, SeekMode::TryOne(2)).unwrap());
+    assert!(read_stream.seek(0, SeekMode::TryOne(0)).unwrap());
+
+    // Seeking to the evicted cache is a miss, not an error.
+    assert!(!read_stream.seek(2000, SeekMode::Auto).unwrap());
+
+    let mut buffer = vec![vec![0.0; 1000]];
+    read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+    assert_eq!(buffer[0][..], frames[0][2000..3000]);
+
+    // The memory is given back to the budget once the stream is dropped.
+    drop(read_stream);
+    let start = std::time::Instant::now();
+    while budget.used_bytes() != 0 {
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn uncache_and_cache_info() {
+    let frames = vec![(0..10000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.num_cache_blocks = 2;
+    opts.num_caches = 2;
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+
+    assert_eq!(read_stream.cache_info(0).unwrap(), CacheInfo::Empty);
+    assert!(matches!(
+        read_stream.cache_info(2),
+        Err(ReadError::CacheIndexOutOfRange {
+            index: 2,
+            num_caches: 2
+        })
+    ));
+
+    read_stream.cache(0, 1000).unwrap();
+    assert_eq!(
+        read_stream.cache_info(0).unwrap(),
+        CacheInfo::Pending { start_frame: 1000 }
+    );
+    poll_for_a_while(&mut read_stream);
+    assert_eq!(
+        read_stream.cache_info(0).unwrap(),
+        CacheInfo::Ready { start_frame: 1000 }
+    );
+
+    assert!(read_stream.uncache(0).unwrap());
+    assert!(!read_stream.uncache(0).unwrap());
+    assert_eq!(read_stream.cache_info(0).unwrap(), CacheInfo::Empty);
+    assert!(!read_stream.seek(1000, SeekMode::Auto).unwrap());
+
+    // A pending request is cancelled.
+    read_stream.cache(1, 5000).unwrap();
+    assert!(read_stream.uncache(1).unwrap());
+    poll_for_a_while(&mut read_stream);
+    assert_eq!(read_stream.cache_info(1).unwrap(), CacheInfo::Empty);
+
+    read_stream.cache(0, 0).unwrap();
+    read_stream.cache(1, 5000).unwrap();
+    poll_for_a_while(&mut read_stream);
+    read_stream.clear_caches().unwrap();
+    assert_eq!(read_stream.cache_info(0).unwrap(), CacheInfo::Empty);
+    assert_eq!(read_stream.cache_info(1).unwrap(), CacheInfo::Empty);
+
+    // Releasing a cache that playback relies on does not interrupt playback.
+    read_stream.cache(0, 2000).unwrap();
+    poll_for_a_while(&mut read_stream);
+    assert!(read_stream.seek(2000, SeekMode::Auto).unwrap());
+    read_stream.uncache(0).unwrap();
+
+    let mut buffer = vec![vec![0.0; 1000]];
+    read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+    assert_eq!(buffer[0][..], frames[0][2000..3000]);
+}
+
+#[test]
+fn cache_ready_notification() {
+    let frames = vec![(0..10000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let ready_tx = std::sync::Mutex::new(ready_tx);
+
+    let mut opts = gated_opts(None, &frames);
+    opts.num_cache_blocks = 2;
+    opts.on_cache_ready = Some(CacheReadyCallback::new(move |cache_index, start_frame| {
+        let _ = ready_tx.lock().unwrap().send((cache_index, start_frame));
+    }));
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+
+    assert!(!read_stream.is_cache_ready(0).unwrap());
+
+    read_stream.cache(0, 3000).unwrap();
+    let timeout = std::time::Duration::from_secs(5);
+    assert_eq!(ready_rx.recv_timeout(timeout).unwrap(), (0, 3000));
+    assert!(read_stream.is_cache_ready(0).unwrap());
+
+    // Filling the temporary seek cache does not call the callback.
+    assert!(!read_stream.seek(7000, SeekMode::NoCache).unwrap());
+    read_stream.block_until_ready().unwrap();
+    poll_for_a_while(&mut read_stream);
+    assert!(ready_rx.try_recv().is_err());
+}
+
+#[test]
+fn crossfade_on_cache_hit() {
+    let frames = vec![(0..10000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 2;
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    read_stream.read(100).unwrap();
+
+    read_stream.cache(0, 5000).unwrap();
+    while !read_stream.is_cache_ready(0).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    let fade = SeekFade {
+        frames: 64,
+        hold_on_miss: false,
+    };
+    assert!(read_stream
+        .seek_with_fade(5000, SeekMode::Auto, fade)
+        .unwrap());
+
+    let data = read_stream.read(200).unwrap();
+    for (i, &sample) in data.read_channel(0).iter().enumerate() {
+        let expected = if i < 64 {
+            let gain = i as f32 / 64.0;
+            (frames[0][100 + i] * (1.0 - gain)) + (frames[0][5000 + i] * gain)
+        } else {
+            frames[0][5000 + i]
+        };
+        assert!((sample - expected).abs() < 0.01, "frame {}", i);
+    }
+    assert_eq!(read_stream.playhead(), 5200);
+
+    // The fade is finished.
+    let data = read_stream.read(10).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][5200..5210]);
+}
+
+#[test]
+fn hold_old_position_on_cache_miss() {
+    let frames = vec![(1..=10000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.additional_opts.decode_delay = Some(std::time::Duration::from_millis(20));
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    read_stream.read(100).unwrap();
+
+    let fade = SeekFade {
+        frames: 64,
+        hold_on_miss: true,
+    };
+    assert!(!read_stream
+        .seek_with_fade(5000, SeekMode::Auto, fade)
+        .unwrap());
+
+    // The old position keeps playing until the new position is cached.
+    let mut old_pos = 100;
+    let output = loop {
+        while !read_stream.is_ready().unwrap() {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let output = read_stream.read(100).unwrap().read_channel(0).to_vec();
+        if read_stream.playhead() >= 5000 {
+            break output;
+        }
+
+        assert_eq!(output, &frames[0][old_pos..old_pos + 100]);
+        old_pos += 100;
+        assert!(old_pos < 5000);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    };
+
+    assert!(old_pos > 100);
+
+    // Then it crossfades into the new position.
+    for (i, &sample) in output.iter().enumerate() {
+        let expected = if i < 64 {
+            let gain = i as f32 / 64.0;
+            (frames[0][old_pos + i] * (1.0 - gain)) + (frames[0][5000 + i] * gain)
+        } else {
+            frames[0][5000 + i]
+        };
+        assert!((sample - expected).abs() < 0.01, "frame {}", i);
+    }
+    assert_eq!(read_stream.playhead(), 5100);
+
+    let data = read_stream.read(100).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][5100..5200]);
+
+    // Seeking again cancels a held seek.
+    assert!(!read_stream
+        .seek_with_fade(8000, SeekMode::Auto, fade)
+        .unwrap());
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let data = read_stream.read(100).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][0..100]);
+}
+
+#[test]
+fn fade_sample_mix() {
+    assert_eq!(f32::mix(1.0, 0.25, 0.5, 1.0), 0.75);
+    assert_eq!(i16::mix(i16::MAX, 1.0, i16::MAX, 1.0), i16::MAX);
+    assert_eq!(i16::mix(-1000, 0.5, 0, 0.0), -500);
+    assert_eq!(u8::mix(u8::SILENCE, 1.0, u8::SILENCE, 1.0), 128);
+    assert_eq!(u8::mix(255, 0.5, 0, 0.0), 192);
+    assert_eq!(u16::mix(0, 1.0, 0, 1.0), 0);
+}
+
+#[test]
+fn scrub_coalesces_seeks() {
+    let frames = vec![(0..20000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    // Scrubbing needs a second decoder.
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    assert!(matches!(
+        read_stream.scrub_to(0),
+        Err(ReadError::ScrubUnavailable)
+    ));
+
+    let scrub_decoded_frames = Arc::new(AtomicUsize::new(0));
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.cache_decoder_opts = Some(GatedDecoderOpts {
+        gate: None,
+        decoded_frames: Arc::clone(&scrub_decoded_frames),
+        decode_delay: Some(std::time::Duration::from_millis(2)),
+        inner: MemoryDecoderOpts {
+            source: frames.to_vec().into(),
+            sample_rate: Some(44100),
+        },
+    });
+    let decoded_frames = Arc::clone(&opts.additional_opts.decoded_frames);
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+
+    // Reading without scrubbing returns silence.
+    let data = read_stream.read_scrub(10, 1.0).unwrap();
+    assert_eq!(data.read_channel(0), &[0.0; 10]);
+
+    // Scrubbing never floods the message channel.
+    let decoded_before = decoded_frames.load(Ordering::SeqCst);
+    let scrub_decoded_before = scrub_decoded_frames.load(Ordering::SeqCst);
+    for i in 0..2000 {
+        read_stream.scrub_to(i * 4).unwrap();
+    }
+    while !read_stream.is_scrub_ready().unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(scrub_decoded_frames.load(Ordering::SeqCst) - scrub_decoded_before < 256 * 4);
+    assert_eq!(read_stream.scrub_position(), Some(7996));
+
+    let data = read_stream.read_scrub(100, 1.0).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][7996..8096]);
+
+    // Frames in between two frames are interpolated.
+    let data = read_stream.read_scrub(4, 0.5).unwrap();
+    assert_eq!(data.read_channel(0), &[8096.0, 8096.5, 8097.0, 8097.5]);
+
+    let data = read_stream.read_scrub(4, -1.0).unwrap();
+    assert_eq!(data.read_channel(0), &[8098.0, 8097.0, 8096.0, 8095.0]);
+    assert_eq!(read_stream.scrub_position(), Some(8094));
+
+    // Jogging past the edge of the window keeps playing.
+    let mut expected = 8094;
+    for _ in 0..20 {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let data = read_stream.read_scrub(64, 1.0).unwrap();
+        assert_eq!(data.read_channel(0), &frames[0][expected..expected + 64]);
+        expected += 64;
+    }
+
+    // The playhead is not moved by scrubbing, and the streaming decoder is never used.
+    assert_eq!(read_stream.playhead(), 0);
+    assert_eq!(decoded_frames.load(Ordering::SeqCst), decoded_before);
+    let data = read_stream.read(100).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][0..100]);
+
+    read_stream.stop_scrub();
+    assert_eq!(read_stream.scrub_position(), None);
+    assert!(!read_stream.is_scrub_ready().unwrap());
+}
+
+#[test]
+fn skip_requests_made_before_latest_seek() {
+    let frames = vec![(0..100000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_look_ahead_blocks = 8;
+    opts.additional_opts.decode_delay = Some(std::time::Duration::from_millis(2));
+    let decoded_frames = Arc::clone(&opts.additional_opts.decoded_frames);
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+
+    // Each seek is a cache miss that requests a full seek cache.
+    let decoded_before = decoded_frames.load(Ordering::SeqCst);
+    for i in 1..=10 {
+        read_stream.seek(i * 5000, SeekMode::NoCache).unwrap();
+    }
+    read_stream.block_until_ready().unwrap();
+
+    let data = read_stream.read(100).unwrap();
+    assert_eq!(data.read_channel(0), &frames[0][50000..50100]);
+
+    // Give the server time to fill any stale seek caches.
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    // At most the first seek cache was filled before the later seeks were received.
+    let one_cache = 8 * 256;
+    assert!(decoded_frames.load(Ordering::SeqCst) - decoded_before <= 2 * one_cache + 256);
+}
+
+#[test]
+fn separate_cache_decoder() {
+    let frames = vec![(0..20000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let cache_decoded_frames = Arc::new(AtomicUsize::new(0));
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 16;
+    opts.num_look_ahead_blocks = 2;
+    opts.num_caches = 2;
+    opts.cache_decoder_opts = Some(GatedDecoderOpts {
+        gate: None,
+        decoded_frames: Arc::clone(&cache_decoded_frames),
+        decode_delay: Some(std::time::Duration::from_millis(20)),
+        inner: MemoryDecoderOpts {
+            source: frames.to_vec().into(),
+            sample_rate: Some(44100),
+        },
+    });
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+
+    // Playback keeps going while the (slow) cache decoder fills the cache.
+    let cache_decoded_before = cache_decoded_frames.load(Ordering::SeqCst);
+    read_stream.cache(1, 10000).unwrap();
+    let mut read_frames = 0;
+    while read_frames < 6144 {
+        if read_stream.is_ready().unwrap() {
+            let data = read_stream.read(256).unwrap();
+            assert_eq!(
+                data.read_channel(0),
+                &frames[0][read_frames..read_frames + 256]
+            );
+            read_frames += 256;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(!read_stream.is_cache_ready(1).unwrap());
+
+    let start = std::time::Instant::now();
+    while !read_stream.is_cache_ready(1).unwrap() {
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let one_cache = (16 + 2) * 256;
+    assert_eq!(
+        cache_decoded_frames.load(Ordering::SeqCst) - cache_decoded_before,
+        one_cache
+    );
+
+    assert!(read_stream.seek(10000, SeekMode::TryOne(1)).unwrap());
+    let mut buffer = vec![vec![0.0; 1000]];
+    read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+    assert_eq!(buffer[0][..], frames[0][10000..11000]);
+}
+
+/// Read the stream block by block at the given pace, and check that the data is correct.
+fn read_at_pace(
+    read_stream: &mut ReadDiskStream<GatedDecoder>,
+    frames: &[Vec<f32>],
+    num_blocks: usize,
+    pace: std::time::Duration,
+) {
+    let mut read_blocks = 0;
+    while read_blocks < num_blocks {
+        if read_stream.is_ready().unwrap() {
+            let start_frame = read_stream.playhead();
+            let data = read_stream.read(256).unwrap();
+            assert_eq!(
+                data.read_channel(0),
+                &frames[0][start_frame..start_frame + 256]
+            );
+            read_blocks += 1;
+        }
+        std::thread::sleep(pace);
+    }
+}
+
+#[test]
+fn adaptive_look_ahead_grows_when_server_is_slow() {
+    let frames = vec![(0..200000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_look_ahead_blocks = 2;
+    opts.adaptive_look_ahead = Some(AdaptiveLookAhead {
+        min_blocks: 2,
+        max_blocks: 16,
+        safety_factor: 4.0,
+    });
+    opts.additional_opts.decode_delay = Some(std::time::Duration::from_millis(2));
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    assert_eq!(read_stream.num_look_ahead_blocks(), 2);
+
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        150,
+        std::time::Duration::from_millis(3),
+    );
+    assert!(read_stream.num_look_ahead_blocks() > 2);
+
+    // Seeking keeps the current look-ahead.
+    read_stream.seek(100000, SeekMode::NoCache).unwrap();
+    read_stream.block_until_ready().unwrap();
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        50,
+        std::time::Duration::from_millis(3),
+    );
+}
+
+#[test]
+fn adaptive_look_ahead_shrinks_when_server_is_fast() {
+    let frames = vec![(0..200000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let budget = MemoryBudget::new(usize::MAX);
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_look_ahead_blocks = 12;
+    opts.adaptive_look_ahead = Some(AdaptiveLookAhead {
+        min_blocks: 2,
+        max_blocks: 16,
+        safety_factor: 4.0,
+    });
+    opts.memory_budget = Some(budget.clone());
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    assert_eq!(read_stream.num_look_ahead_blocks(), 12);
+
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        150,
+        std::time::Duration::from_millis(4),
+    );
+    // How far it shrinks depends on the timing of the IO thread, which is tested
+    // on its own in `read::look_ahead`.
+    let num_look_ahead_blocks = read_stream.num_look_ahead_blocks();
+    assert!(num_look_ahead_blocks < 12);
+    poll_for_a_while(&mut read_stream);
+
+    // The blocks that are no longer needed were freed. The seek cache holds as many
+    // look-ahead blocks as the maximum.
+    let block_bytes = 256 * std::mem::size_of::<f32>();
+    assert!(budget.used_bytes() <= (16 + num_look_ahead_blocks + 2) * block_bytes);
+
+    read_stream.seek(100000, SeekMode::NoCache).unwrap();
+    read_stream.block_until_ready().unwrap();
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        20,
+        std::time::Duration::from_millis(4),
+    );
+}
+
+#[test]
+fn read_options_from_latency_budget() {
+    let frames = vec![(0..20000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let (opts, info) = ReadStreamOptions::<GatedDecoder>::builder(44100, 0.1, 0.05)
+        .num_channels(1)
+        .cache_secs(0.01)
+        .additional_opts(gated_opts(None, &frames).additional_opts)
+        .build()
+        .unwrap();
+
+    // 4410 frames of buffer and 441 frames of cache, rounded up to blocks of 256 frames.
+    assert_eq!(opts.block_size, 256);
+    assert_eq!(opts.num_look_ahead_blocks, 18);
+    assert_eq!(opts.num_cache_blocks, 2);
+    assert_eq!(info.buffer_secs, (18.0 * 256.0) / 44100.0);
+    // After a seek that misses the cache, the whole cache and look-ahead are decoded.
+    assert_eq!(
+        info.seek_recovery_secs,
+        Some(0.05 + ((20.0 * 256.0) / 44100.0))
+    );
+    assert_eq!(info.memory_bytes, ((4 * 20) + 2) * 256 * 4);
+
+    let (_, info) = ReadStreamOptions::<GatedDecoder>::builder(44100, 0.1, 0.05)
+        .cache_secs(0.01)
+        .decode_speed(10.0)
+        .build()
+        .unwrap();
+    assert_eq!(
+        info.seek_recovery_secs,
+        Some(0.05 + ((20.0 * 256.0) / 44100.0 / 10.0))
+    );
+
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    let mut buffer = vec![vec![0.0; 1000]];
+    read_stream.fill_buffer_blocking(&mut buffer).unwrap();
+    assert_eq!(buffer[0][..], frames[0][..1000]);
+
+    // The buffer must cover the latency of 9 blocks and the block being played.
+    assert_eq!(
+        ReadStreamOptions::<GatedDecoder>::builder(44100, 0.05, 0.05)
+            .build()
+            .err(),
+        Some(StreamOptionsError::BufferTooShort {
+            buffer_secs: 0.05,
+            min_buffer_secs: (10.0 * 256.0) / 44100.0,
+        })
+    );
+    assert_eq!(
+        ReadStreamOptions::<GatedDecoder>::builder(0, 0.1, 0.05)
+            .build()
+            .err(),
+        Some(StreamOptionsError::ZeroSampleRate)
+    );
+    assert_eq!(
+        ReadStreamOptions::<GatedDecoder>::builder(44100, 0.1, 0.05)
+            .block_size(0)
+            .build()
+            .err(),
+        Some(StreamOptionsError::ZeroBlockSize)
+    );
+    assert!(matches!(
+        ReadStreamOptions::<GatedDecoder>::builder(44100, f64::NAN, 0.05)
+            .build()
+            .err(),
+        Some(StreamOptionsError::InvalidDuration { .. })
+    ));
+    assert_eq!(
+        ReadStreamOptions::<GatedDecoder>::builder(44100, 0.1, 0.05)
+            .decode_speed(0.0)
+            .build()
+            .err(),
+        Some(StreamOptionsError::InvalidDecodeSpeed { speed: 0.0 })
+    );
+}
+
+#[test]
+fn write_options_from_latency_budget() {
+    let (opts, info) = WriteStreamOptions::<MemoryEncoder<f32>>::builder(48000, 2.0, 0.5)
+        .block_size(4800)
+        .build()
+        .unwrap();
+
+    assert_eq!(opts.block_size, 4800);
+    assert_eq!(opts.num_write_blocks, 20);
+    assert_eq!(info.buffer_secs, 2.0);
+    assert_eq!(info.seek_recovery_secs, None);
+    assert_eq!(info.memory_bytes, 20 * 2 * 4800 * 4);
+
+    assert_eq!(
+        WriteStreamOptions::<MemoryEncoder<f32>>::builder(48000, 2.0, 0.5)
+            .num_channels(0)
+            .build()
+            .err(),
+        Some(StreamOptionsError::ZeroChannels)
+    );
+    assert!(matches!(
+        WriteStreamOptions::<MemoryEncoder<f32>>::builder(48000, 0.5, 0.5)
+            .block_size(4800)
+            .build()
+            .err(),
+        Some(StreamOptionsError::BufferTooShort { .. })
+    ));
+}
+
+#[test]
+fn read_within_and_across_blocks() {
+    let frames = vec![
+        (0..5000).map(|i| i as f32).collect::<Vec<f32>>(),
+        (0..5000).map(|i| -(i as f32)).collect::<Vec<f32>>(),
+    ];
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 2;
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+
+    read_stream.cache(0, 3000).unwrap();
+    while !read_stream.is_cache_ready(0).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    let check_reads = |read_stream: &mut ReadDiskStream<GatedDecoder>, num_reads: usize| {
+        // Reads that end exactly on, before, and after the end of a block.
+        let sizes = [100, 156, 256, 64, 300, 40, 512, 1];
+        for &frames_to_read in sizes.iter().cycle().take(num_reads) {
+            read_stream.block_until_ready().unwrap();
+
+            let start_frame = read_stream.playhead();
+            let data = read_stream.read(frames_to_read).unwrap();
+            let len = frames_to_read.min(256);
+            assert_eq!(data.num_frames(), len);
+            for (ch, expected) in frames.iter().enumerate() {
+                assert_eq!(
+                    data.read_channel(ch),
+                    &expected[start_frame..start_frame + len]
+                );
+            }
+        }
+    };
+
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    check_reads(&mut read_stream, 20);
+
+    // The first blocks are read from the cache.
+    assert!(read_stream.seek(3000, SeekMode::TryOne(0)).unwrap());
+    check_reads(&mut read_stream, 12);
+
+    // The last read stops at the end of the file.
+    read_stream.seek(4900, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+    let data = read_stream.read(200).unwrap();
+    assert!(data.reached_end_of_file());
+    assert_eq!(data.read_channel(1), &frames[1][4900..5000]);
+}
+
+#[test]
+fn block_layouts() {
+    let channels: Vec<Vec<f32>> = (0..3)
+        .map(|ch| (0..8).map(|i| (ch * 100 + i) as f32).collect())
+        .collect();
+
+    let mut data_block = DataBlock::new(3, 8);
+    for (block_ch, ch) in data_block.append_frames(5).zip(channels.iter()) {
+        block_ch.copy_from_slice(&ch[..5]);
+    }
+    for (block_ch, ch) in data_block.append_frames(3).zip(channels.iter()) {
+        block_ch.copy_from_slice(&ch[5..]);
+    }
+    assert_eq!(data_block.num_frames(), 8);
+    assert!(data_block.channels().eq(channels.iter().map(Vec::as_slice)));
+
+    data_block.clear();
+    assert!(data_block.channels().all(|ch| ch.is_empty()));
+
+    let mut planar = WriteBlock::new(3, 8);
+    planar.append_from_channels(&channels, 0..2);
+    planar.append_from_channels(&channels, 2..4);
+    assert_eq!(planar.layout(), BlockLayout::Planar);
+    assert_eq!(planar.channel(2), &channels[2][..4]);
+
+    let mut interleaved = WriteBlock::with_layout(3, 8, BlockLayout::Interleaved);
+    interleaved.append_from_channels(&channels, 0..1);
+    interleaved.append_from_channels(&channels, 1..2);
+    assert_eq!(interleaved.written_frames(), 2);
+    assert_eq!(
+        interleaved.interleaved(),
+        &[0.0, 100.0, 200.0, 1.0, 101.0, 201.0]
+    );
+}
+
+#[test]
+fn preallocate_blocks_and_caches() {
+    let frames = vec![(0..20000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let budget = MemoryBudget::new(usize::MAX);
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 4;
+    opts.num_look_ahead_blocks = 2;
+    opts.memory_budget = Some(budget.clone());
+    opts.preallocate = true;
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+
+    // The prefetch blocks, the cache, and the two temporary caches each hold 6 blocks,
+    // plus the block that is lent out from `read`.
+    let block_bytes = 256 * std::mem::size_of::<f32>();
+    assert_eq!(
+        read_stream.preallocated_bytes(),
+        ((4 * 6) + 1) * block_bytes
+    );
+    assert_eq!(budget.used_bytes(), read_stream.preallocated_bytes());
+
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        20,
+        std::time::Duration::from_millis(1),
+    );
+
+    read_stream.cache(0, 10000).unwrap();
+    while !read_stream.is_cache_ready(0).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    poll_for_a_while(&mut read_stream);
+
+    assert!(read_stream.seek(10000, SeekMode::TryOne(0)).unwrap());
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        20,
+        std::time::Duration::from_millis(1),
+    );
+
+    // Nothing else was allocated.
+    assert_eq!(budget.used_bytes(), read_stream.preallocated_bytes());
+}
+
+#[test]
+fn hibernate_keeps_only_designated_caches() {
+    let frames = vec![(0..20000).map(|i| i as f32).collect::<Vec<f32>>()];
+
+    let budget = MemoryBudget::new(usize::MAX);
+
+    let mut opts = gated_opts(None, &frames);
+    opts.block_size = 256;
+    opts.num_cache_blocks = 4;
+    opts.num_look_ahead_blocks = 2;
+    opts.num_caches = 2;
+    opts.memory_budget = Some(budget.clone());
+    let mut read_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        20,
+        std::time::Duration::from_millis(1),
+    );
+
+    read_stream.cache(0, 0).unwrap();
+    read_stream.cache(1, 10000).unwrap();
+    while !read_stream.is_cache_ready(0).unwrap() || !read_stream.is_cache_ready(1).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(read_stream.seek(0, SeekMode::TryOne(0)).unwrap());
+
+    read_stream.hibernate(&[0]).unwrap();
+    poll_for_a_while(&mut read_stream);
+    assert!(read_stream.is_hibernating());
+    assert!(!read_stream.is_ready().unwrap());
+
+    // Only the kept cache is left.
+    let cache_bytes = 6 * 256 * std::mem::size_of::<f32>();
+    assert_eq!(budget.used_bytes(), cache_bytes);
+    assert_eq!(
+        read_stream.cache_info(0).unwrap(),
+        CacheInfo::Ready { start_frame: 0 }
+    );
+    assert_eq!(read_stream.cache_info(1).unwrap(), CacheInfo::Empty);
+
+    // Reading returns silence without moving the playhead.
+    assert_eq!(read_stream.read(100).unwrap().read_channel(0), &[0.0; 100]);
+    assert_eq!(read_stream.playhead(), 0);
+
+    // The playhead is in the kept cache, so playback resumes right away.
+    assert!(read_stream.wake().unwrap());
+    assert!(!read_stream.is_hibernating());
+    assert!(read_stream.is_ready().unwrap());
+    read_at_pace(
+        &mut read_stream,
+        &frames,
+        20,
+        std::time::Duration::from_millis(1),
+    );
+}
+
+#[test]
+fn hibernate_reads_silence_of_unsigned_samples() {
+    let opts = ReadStreamOptions::<MemoryDecoder<u8>> {
+        additional_opts: MemoryDecoderOpts {
+            source: vec![vec![255u8; 1000]].into(),
+            sample_rate: Some(44100),
+        },
+        block_size: 256,
+        ..Default::default()
+    };
+    let mut read_stream = ReadDiskStream::<MemoryDecoder<u8>>::new("memory", 0, opts).unwrap();
+    read_stream.seek(0, SeekMode::Auto).unwrap();
+    read_stream.block_until_ready().unwrap();
+
+    read_stream.hibernate(&[]).unwrap();
+    assert_eq!(read_stream.read(100).unwrap().read_channel(0), &[128; 100]);
+}
+
+#[test]
+fn multi_file_decoder() {
+    type SplitDecoder = MultiFileDecoder<MemoryDecoder<f32>>;
+
+    let part = vec![(0..1000).map(|i| i as f32).collect::<Vec<f32>>()];
+    let opts = |num_files| read::MultiFileOpts {
+        num_files,
+        decoder_opts: MemoryDecoderOpts {
+            source: part.clone().into(),
+            sample_rate: Some(44100),
+        },
+    };
+
+    // The block size does not divide the length of a file, so blocks span file boundaries.
+    let file = std::path::Path::new("memory");
+    let (file_info, linear) =
+        testing::check_decoder_with_opts::<SplitDecoder>(file, 256, || opts(Some(3)));
+    assert_eq!(file_info.num_frames, 3000);
+    assert_eq!(file_info.params[2].path, PathBuf::from("memory_002"));
+    assert_eq!(file_info.params[2].start_frame, 2000);
+    assert_eq!(linear[0], part[0].repeat(3));
+    testing::check_read_stream_with_opts::<SplitDecoder>(file, 256, &linear, opts(Some(3)));
+
+    // Find the rest of the series on the file system.
+    let dir = std::env::temp_dir().join(format!("creek_multi_file_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("take.wav");
+    std::fs::write(&file, []).unwrap();
+    std::fs::write(dir.join("take.wav_001"), []).unwrap();
+
+    let (_, file_info) = SplitDecoder::new(file, 0, 256, opts(None)).unwrap();
+    assert_eq!(file_info.num_frames, 2000);
+    assert_eq!(file_info.params[1].path, dir.join("take.wav_001"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn sequence_plays_items_back_to_back() {
+    let first = vec![(0..1000).map(|i| i as f32).collect::<Vec<f32>>()];
+    let second = vec![(0..700).map(|i| -(i as f32)).collect::<Vec<f32>>()];
+
+    let open_first = || {
+        let opts = ReadStreamOptions::<MemoryDecoder<f32>> {
+            block_size: 256,
+            additional_opts: MemoryDecoderOpts {
+                source: first.clone().into(),
+                sample_rate: Some(44100),
+            },
+            ..Default::default()
+        };
+        let mut stream = ReadDiskStream::new("memory", 0, opts).unwrap();
+        stream.seek(0, SeekMode::Auto).unwrap();
+        stream.block_until_ready().unwrap();
+        stream
+    };
+
+    // The second item uses a different decoder.
+    let mut opts = gated_opts(None, &second);
+    opts.block_size = 256;
+    let mut second_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    second_stream.seek(0, SeekMode::Auto).unwrap();
+    second_stream.block_until_ready().unwrap();
+
+    let (mut sequence, mut handle) = SequenceReadStream::<f32>::new(
+        1,
+        read::SequenceOptions {
+            max_queued_items: 2,
+            block_size: 512,
+        },
+    );
+
+    let a = handle.append(open_first()).unwrap();
+    let b = handle.append(second_stream).unwrap();
+    assert!(matches!(
+        handle.append(open_first()),
+        Err(read::AppendError::QueueFull(_))
+    ));
+    assert_eq!(handle.num_queued_items(), 2);
+
+    let mut stereo_opts = gated_opts(None, &[vec![0.0; 10], vec![0.0; 10]]);
+    stereo_opts.block_size = 256;
+    let stereo = ReadDiskStream::<GatedDecoder>::new("memory", 0, stereo_opts).unwrap();
+    assert!(matches!(
+        handle.append(stereo),
+        Err(read::AppendError::MismatchedChannels(_))
+    ));
+
+    let mut output = Vec::new();
+    let mut boundaries = Vec::new();
+    let mut c = None;
+    while output.len() < 3000 {
+        // Read no faster than the items can be prefetched.
+        while !sequence.is_ready()
+            && (sequence.current_item().is_some() || sequence.next_item().is_some())
+        {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let data = sequence.read(300);
+        for boundary in data.item_boundaries() {
+            boundaries.push(read::ItemBoundary {
+                frame: boundary.frame + output.len(),
+                ..*boundary
+            });
+        }
+        output.extend_from_slice(data.read_channel(0));
+
+        // There is room in the queue once the first two items have started.
+        if c.is_none() {
+            c = Some(handle.append(open_first()).unwrap());
+        }
+    }
+    let c = c.unwrap();
+
+    let mut expected = first[0].clone();
+    expected.extend_from_slice(&second[0]);
+    expected.extend_from_slice(&first[0]);
+    expected.resize(3000, 0.0);
+    assert_eq!(output, expected);
+
+    let boundary = |frame, ended, started| read::ItemBoundary {
+        frame,
+        ended,
+        started,
+        failed: false,
+    };
+    assert_eq!(
+        boundaries,
+        vec![
+            boundary(0, None, Some(a)),
+            boundary(1000, Some(a), Some(b)),
+            boundary(1700, Some(b), Some(c)),
+            boundary(2700, Some(c), None),
+        ]
+    );
+    assert_eq!(sequence.current_item(), None);
+}
+
+#[test]
+fn sequence_waits_for_slow_next_item() {
+    let first = vec![(1..=1000).map(|i| i as f32).collect::<Vec<f32>>()];
+    let second = vec![(1..=700).map(|i| -(i as f32)).collect::<Vec<f32>>()];
+
+    let opts = ReadStreamOptions::<MemoryDecoder<f32>> {
+        block_size: 256,
+        additional_opts: MemoryDecoderOpts {
+            source: first.clone().into(),
+            sample_rate: Some(44100),
+        },
+        ..Default::default()
+    };
+    let mut first_stream = ReadDiskStream::new("memory", 0, opts).unwrap();
+    first_stream.seek(0, SeekMode::Auto).unwrap();
+    first_stream.block_until_ready().unwrap();
+
+    // The second item is still buffering when the first one ends.
+    let mut opts = gated_opts(None, &second);
+    opts.block_size = 256;
+    opts.additional_opts.decode_delay = Some(std::time::Duration::from_millis(20));
+    let mut second_stream = ReadDiskStream::<GatedDecoder>::new("memory", 0, opts).unwrap();
+    second_stream.seek(0, SeekMode::Auto).unwrap();
+
+    let (mut sequence, mut handle) =
+        SequenceReadStream::<f32>::new(1, read::SequenceOptions::default());
+    handle.append(first_stream).unwrap();
+    let b = handle.append(second_stream).unwrap();
+
+    let mut output = Vec::new();
+    let mut read_while_buffering = false;
+    for _ in 0..10_000 {
+        if sequence.current_item() == Some(b) && !sequence.is_ready() {
+            read_while_buffering = true;
+        }
+
+        // Keep reading without waiting for the sequence to be ready.
+        output.extend_from_slice(sequence.read(300).read_channel(0));
+        if sequence.current_item().is_none() && sequence.next_item().is_none() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(read_while_buffering);
+
+    // Only silence is inserted while buffering, and no part of the second item is skipped.
+    output.retain(|&s| s != 0.0);
+    let mut expected = first[0].clone();
+    expected.extend_from_slice(&second[0]);
+    assert_eq!(output, expected);
+}
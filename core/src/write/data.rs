@@ -1,32 +1,93 @@
+use std::ops::Range;
+
+use crate::block::{BlockLayout, SampleBuffer};
+
 /// A block to write data to.
+///
+/// The samples of every channel are stored in a single allocation of
+/// `num_channels * block_size` samples, which is never reallocated. The layout of the
+/// samples is given by `Encoder::BLOCK_LAYOUT`.
 pub struct WriteBlock<T: Copy + Clone + Default + Send> {
-    pub(crate) block: Vec<Vec<T>>,
+    samples: SampleBuffer<T>,
 
     pub(crate) restart_count: usize,
 }
 
 impl<T: Copy + Clone + Default + Send> WriteBlock<T> {
+    /// Create a block with the planar layout.
     pub fn new(num_channels: usize, block_size: usize) -> Self {
+        Self::with_layout(num_channels, block_size, BlockLayout::Planar)
+    }
+
+    /// Create a block with the given layout. The write stream creates its blocks with
+    /// the `Encoder::BLOCK_LAYOUT` of its encoder.
+    pub fn with_layout(num_channels: usize, block_size: usize, layout: BlockLayout) -> Self {
         WriteBlock {
-            block: (0..num_channels)
-                .map(|_| Vec::with_capacity(block_size))
-                .collect(),
+            samples: SampleBuffer::new(num_channels, block_size, layout),
             restart_count: 0,
         }
     }
 
-    pub fn block(&self) -> &[Vec<T>] {
-        self.block.as_slice()
+    /// How the samples of the block are laid out in memory.
+    pub fn layout(&self) -> BlockLayout {
+        self.samples.layout()
     }
 
+    /// The number of channels in the block.
+    pub fn num_channels(&self) -> usize {
+        self.samples.num_channels()
+    }
+
+    /// The maximum number of frames the block can hold.
+    pub fn block_size(&self) -> usize {
+        self.samples.block_size()
+    }
+
+    /// The number of frames that have been written to the block.
     pub fn written_frames(&self) -> usize {
-        self.block[0].len()
+        self.samples.num_frames()
+    }
+
+    /// The written frames of the given channel.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `channel` is out of range, or if the block is interleaved.
+    pub fn channel(&self, channel: usize) -> &[T] {
+        self.samples.channel(channel)
+    }
+
+    /// The written frames of every channel.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the block is interleaved.
+    pub fn channels(&self) -> impl Iterator<Item = &[T]> {
+        self.samples.channels()
+    }
+
+    /// The written frames, with the samples of each frame next to each other.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the block is planar.
+    pub fn interleaved(&self) -> &[T] {
+        self.samples.interleaved()
+    }
+
+    /// Copy the given range of frames of every channel in `channels` to the end of the
+    /// block.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the range is out of bounds of a channel, or if the frames do not
+    /// fit into the block.
+    pub fn append_from_channels<C: AsRef<[T]>>(&mut self, channels: &[C], frames: Range<usize>) {
+        self.samples.append_from_channels(channels, frames);
     }
 
     pub fn clear(&mut self) {
-        for ch in self.block.iter_mut() {
-            ch.clear();
-        }
+        self.samples.clear();
     }
 }
 
@@ -3,7 +3,7 @@ use std::fmt::Debug;
 use std::path::PathBuf;
 
 use super::WriteBlock;
-use crate::FileInfo;
+use crate::{BlockLayout, FileInfo};
 
 /// The return status of writing to a file.
 #[derive(Debug, Clone, Copy)]
@@ -45,6 +45,13 @@ pub trait Encoder: Sized + 'static {
     /// write latency scenario.
     const DEFAULT_NUM_WRITE_BLOCKS: usize;
 
+    /// How the samples of the blocks given to `Encoder::encode()` are laid out. Use
+    /// `BlockLayout::Interleaved` if the file stores interleaved samples, so that the
+    /// write stream interleaves them as they are written instead of the encoder.
+    ///
+    /// The default is `BlockLayout::Planar`.
+    const BLOCK_LAYOUT: BlockLayout = BlockLayout::Planar;
+
     /// Open the file for writing.
     ///
     /// * `file` - The path of the file to open.
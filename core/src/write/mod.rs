@@ -8,8 +8,9 @@ pub mod error;
 pub use data::WriteBlock;
 pub use encoder::{num_files_to_file_name_extension, Encoder, WriteStatus};
 pub use error::{FatalWriteError, WriteError};
-pub use write_stream::WriteDiskStream;
+pub use write_stream::{PendingWriteDiskStream, WriteDiskStream};
 
+use crate::latency::WriteStreamOptionsBuilder;
 use data::HeapData;
 use server::WriteServer;
 
@@ -65,3 +66,16 @@ impl<E: Encoder> Default for WriteStreamOptions<E> {
         }
     }
 }
+
+impl<E: Encoder> WriteStreamOptions<E> {
+    /// Start building options that can record `buffer_secs` seconds at the given sample
+    /// rate while the IO server is stalled, and that can cover a disk latency of up to
+    /// `worst_case_latency_secs` seconds. See [`WriteStreamOptionsBuilder`].
+    pub fn builder(
+        sample_rate: u32,
+        buffer_secs: f64,
+        worst_case_latency_secs: f64,
+    ) -> WriteStreamOptionsBuilder<E> {
+        WriteStreamOptionsBuilder::new(sample_rate, buffer_secs, worst_case_latency_secs)
+    }
+}
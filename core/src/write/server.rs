@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 
-use rtrb::{Consumer, Producer, RingBuffer};
+use rtrb::{Consumer, Producer};
 
+use crate::open::OpenResultTx;
 use crate::{FileInfo, SERVER_WAIT_TIME};
 
 use super::{ClientToServerMsg, Encoder, HeapData, ServerToClientMsg, WriteStatus};
@@ -36,7 +37,8 @@ impl<E: Encoder> WriteServer<E> {
         to_client_tx: Producer<ServerToClientMsg<E>>,
         from_client_rx: Consumer<ClientToServerMsg<E>>,
         close_signal_rx: Consumer<Option<HeapData<E::T>>>,
-    ) -> Result<FileInfo<E::FileParams>, E::OpenError> {
+        open_tx: OpenResultTx<Result<FileInfo<E::FileParams>, E::OpenError>>,
+    ) {
         let WriteServerOptions {
             file,
             num_write_blocks,
@@ -46,9 +48,6 @@ impl<E: Encoder> WriteServer<E> {
             additional_opts,
         } = opts;
 
-        let (mut open_tx, mut open_rx) =
-            RingBuffer::<Result<FileInfo<E::FileParams>, E::OpenError>>::new(1);
-
         std::thread::spawn(move || {
             match E::new(
                 file,
@@ -59,8 +58,7 @@ impl<E: Encoder> WriteServer<E> {
                 additional_opts,
             ) {
                 Ok((encoder, file_info)) => {
-                    // Push cannot fail because only one message is ever sent.
-                    let _ = open_tx.push(Ok(file_info));
+                    open_tx.send(Ok(file_info));
 
                     WriteServer::run(Self {
                         to_client_tx,
@@ -75,19 +73,10 @@ impl<E: Encoder> WriteServer<E> {
                     });
                 }
                 Err(e) => {
-                    // Push cannot fail because only one message is ever sent.
-                    let _ = open_tx.push(Err(e));
+                    open_tx.send(Err(e));
                 }
             }
         });
-
-        loop {
-            if let Ok(res) = open_rx.pop() {
-                return res;
-            }
-
-            std::thread::sleep(SERVER_WAIT_TIME);
-        }
     }
 
     fn run(mut self) {
@@ -6,9 +6,26 @@ use super::{
     ClientToServerMsg, Encoder, HeapData, ServerToClientMsg, WriteBlock, WriteServer,
     WriteStreamOptions,
 };
+use crate::open::{open_result_channel, sealed, OpenStream, PendingDiskStream};
 use crate::write::server::WriteServerOptions;
 use crate::{FileInfo, SERVER_WAIT_TIME};
 
+/// A [`WriteDiskStream`] that is still being opened on its IO server thread.
+///
+/// If this is dropped before the file has been opened, then the file is finished and
+/// closed as soon as it has been opened, the same as when a `WriteDiskStream` is dropped.
+pub type PendingWriteDiskStream<E> = PendingDiskStream<WriteDiskStream<E>>;
+
+/// The parts of a [`WriteDiskStream`] that are created before the file has been opened.
+pub struct WriteDiskStreamOptions<E: Encoder> {
+    num_write_blocks: usize,
+    block_size: usize,
+
+    to_server_tx: Producer<ClientToServerMsg<E>>,
+    from_server_rx: Consumer<ServerToClientMsg<E>>,
+    close_signal_tx: Producer<Option<HeapData<E::T>>>,
+}
+
 /// A realtime-safe disk-streaming writer of audio files.
 pub struct WriteDiskStream<E: Encoder> {
     to_server_tx: Producer<ClientToServerMsg<E>>,
@@ -36,6 +53,9 @@ impl<E: Encoder> WriteDiskStream<E> {
     /// * `sample_rate` - The sample rate of the file.
     /// * `stream_opts` - Additional stream options.
     ///
+    /// This blocks the calling thread until the file has been opened. Use
+    /// `WriteDiskStream::open()` to open the file without blocking.
+    ///
     /// # Panics
     ///
     /// This will panic if `num_channels`, `sample_rate`, `stream_opts.block_size`,
@@ -46,6 +66,30 @@ impl<E: Encoder> WriteDiskStream<E> {
         sample_rate: u32,
         stream_opts: WriteStreamOptions<E>,
     ) -> Result<WriteDiskStream<E>, E::OpenError> {
+        Self::open(file, num_channels, sample_rate, stream_opts).wait()
+    }
+
+    /// Start opening a new realtime-safe disk-streaming writer without blocking the
+    /// calling thread.
+    ///
+    /// * `file` - The path to the file to open.
+    /// * `num_channels` - The number of channels in the file.
+    /// * `sample_rate` - The sample rate of the file.
+    /// * `stream_opts` - Additional stream options.
+    ///
+    /// The file is opened on the IO server thread. The returned handle can either be
+    /// polled with `PendingWriteDiskStream::poll_open()` or awaited as a `Future`.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if `num_channels`, `sample_rate`, `stream_opts.block_size`,
+    /// `stream_opts.num_write_blocks`, or `stream_opts.server_msg_channel_size` is `0`.
+    pub fn open<P: Into<PathBuf>>(
+        file: P,
+        num_channels: u16,
+        sample_rate: u32,
+        stream_opts: WriteStreamOptions<E>,
+    ) -> PendingWriteDiskStream<E> {
         let WriteStreamOptions {
             additional_opts,
             num_write_blocks,
@@ -72,9 +116,11 @@ impl<E: Encoder> WriteDiskStream<E> {
         // Create dedicated close signal.
         let (close_signal_tx, close_signal_rx) = RingBuffer::<Option<HeapData<E::T>>>::new(1);
 
+        let (open_tx, open_rx) = open_result_channel();
+
         let file: PathBuf = file.into();
 
-        match WriteServer::spawn(
+        WriteServer::spawn(
             WriteServerOptions {
                 file,
                 num_write_blocks,
@@ -86,36 +132,36 @@ impl<E: Encoder> WriteDiskStream<E> {
             to_client_tx,
             from_client_rx,
             close_signal_rx,
-        ) {
-            Ok(file_info) => {
-                let client = WriteDiskStream::create(
-                    to_server_tx,
-                    from_server_rx,
-                    close_signal_tx,
-                    num_write_blocks,
-                    block_size,
-                    file_info,
-                );
+            open_tx,
+        );
 
-                Ok(client)
-            }
-            Err(e) => Err(e),
-        }
+        PendingWriteDiskStream::new(
+            open_rx,
+            WriteDiskStreamOptions {
+                num_write_blocks,
+                block_size,
+                to_server_tx,
+                from_server_rx,
+                close_signal_tx,
+            },
+        )
     }
 
-    pub(crate) fn create(
-        to_server_tx: Producer<ClientToServerMsg<E>>,
-        from_server_rx: Consumer<ServerToClientMsg<E>>,
-        close_signal_tx: Producer<Option<HeapData<E::T>>>,
-        num_write_blocks: usize,
-        block_size: usize,
-        file_info: FileInfo<E::FileParams>,
-    ) -> Self {
+    fn create(opts: WriteDiskStreamOptions<E>, file_info: FileInfo<E::FileParams>) -> Self {
+        let WriteDiskStreamOptions {
+            num_write_blocks,
+            block_size,
+            to_server_tx,
+            from_server_rx,
+            close_signal_tx,
+        } = opts;
+
         let mut block_pool: Vec<WriteBlock<E::T>> = Vec::with_capacity(num_write_blocks);
         for _ in 0..num_write_blocks - 2 {
-            block_pool.push(WriteBlock::new(
+            block_pool.push(WriteBlock::with_layout(
                 usize::from(file_info.num_channels),
                 block_size,
+                E::BLOCK_LAYOUT,
             ));
         }
 
@@ -126,13 +172,15 @@ impl<E: Encoder> WriteDiskStream<E> {
 
             heap_data: Some(HeapData {
                 block_pool,
-                current_block: Some(WriteBlock::new(
+                current_block: Some(WriteBlock::with_layout(
                     usize::from(file_info.num_channels),
                     block_size,
+                    E::BLOCK_LAYOUT,
                 )),
-                next_block: Some(WriteBlock::new(
+                next_block: Some(WriteBlock::with_layout(
                     usize::from(file_info.num_channels),
                     block_size,
+                    E::BLOCK_LAYOUT,
                 )),
             }),
 
@@ -238,7 +286,7 @@ impl<E: Encoder> WriteDiskStream<E> {
         // Check that there are available blocks to write to.
         if let Some(mut current_block) = heap.current_block.take() {
             if let Some(mut next_block) = heap.next_block.take() {
-                let current_block_written_frames = current_block.block[0].len();
+                let current_block_written_frames = current_block.written_frames();
 
                 if current_block_written_frames + buffer_len > self.block_size {
                     // Need to copy to two blocks.
@@ -246,9 +294,7 @@ impl<E: Encoder> WriteDiskStream<E> {
                     let first_len = self.block_size - current_block_written_frames;
 
                     // Copy into first block.
-                    for (buffer_ch, write_ch) in buffer.iter().zip(current_block.block.iter_mut()) {
-                        write_ch.extend_from_slice(&buffer_ch[0..first_len]);
-                    }
+                    current_block.append_from_channels(buffer, 0..first_len);
 
                     // Send the now filled block to the IO server for writing.
                     // This cannot fail because we made sure there was a slot open in
@@ -259,9 +305,7 @@ impl<E: Encoder> WriteDiskStream<E> {
                     });
 
                     // Copy the remaining data into the second block.
-                    for (buffer_ch, write_ch) in buffer.iter().zip(next_block.block.iter_mut()) {
-                        write_ch.extend_from_slice(&buffer_ch[first_len..]);
-                    }
+                    next_block.append_from_channels(buffer, first_len..buffer_len);
 
                     // Move the next-up block into the current block.
                     heap.current_block = Some(next_block);
@@ -271,11 +315,9 @@ impl<E: Encoder> WriteDiskStream<E> {
                 } else {
                     // Only need to copy to first block.
 
-                    for (buffer_ch, write_ch) in buffer.iter().zip(current_block.block.iter_mut()) {
-                        write_ch.extend_from_slice(buffer_ch);
-                    }
+                    current_block.append_from_channels(buffer, 0..buffer_len);
 
-                    let current_block_written_frames = current_block.block[0].len();
+                    let current_block_written_frames = current_block.written_frames();
 
                     if current_block_written_frames == self.block_size {
                         // Block is filled. Sent it to the IO server for writing.
@@ -335,7 +377,7 @@ impl<E: Encoder> WriteDiskStream<E> {
             };
 
             if let Some(mut current_block) = heap.current_block.take() {
-                if !current_block.block[0].is_empty() {
+                if current_block.written_frames() != 0 {
                     // Send the last bit of remaining samples to be encoded.
 
                     // Check that there is at-least one slot open.
@@ -506,6 +548,25 @@ impl<E: Encoder> WriteDiskStream<E> {
     }
 }
 
+impl<E: Encoder> sealed::Sealed for WriteDiskStream<E> {}
+
+impl<E: Encoder> OpenStream for WriteDiskStream<E> {
+    type Opened = FileInfo<E::FileParams>;
+    type OpenError = E::OpenError;
+    type Options = WriteDiskStreamOptions<E>;
+
+    fn finish_open(opts: Self::Options, opened: Self::Opened) -> Self {
+        Self::create(opts, opened)
+    }
+
+    fn cancel_open(mut opts: Self::Options) {
+        // Tell the server to close once it has opened the file. There is no heap
+        // data to deallocate yet.
+        // This cannot fail because this is the only place the signal is ever sent.
+        let _ = opts.close_signal_tx.push(None);
+    }
+}
+
 impl<E: Encoder> Drop for WriteDiskStream<E> {
     fn drop(&mut self) {
         // Tell the server to deallocate any heap data.
@@ -5,22 +5,34 @@
 #![deny(trivial_numeric_casts)]
 #![forbid(unsafe_code)]
 
-use std::fs::File;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use symphonia::core::audio::AudioBuffer;
 use symphonia::core::codecs::{CodecParameters, Decoder as SymphDecoder, DecoderOptions};
 use symphonia::core::errors::Error;
 use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::io::{MediaSource as SymphMediaSource, MediaSourceStream};
 use symphonia::core::meta::{Metadata, MetadataOptions, MetadataRevision};
 use symphonia::core::probe::Hint;
 
-use creek_core::{DataBlock, Decoder, FileInfo};
+use creek_core::read::decode_into_block;
+use creek_core::{
+    DataBlock, DecodeResult, Decoder, FileInfo, FileIoOptions, SourceProvider, StreamFile,
+};
 
 mod error;
+mod source;
 pub use error::OpenError;
 
+use source::SourceAdapter;
+
+#[cfg(test)]
+mod tests;
+
+/// The number of frames before the requested frame to start decoding from when seeking.
+const SEEK_PREROLL_FRAMES: u64 = 8192;
+
 pub struct SymphoniaDecoder {
     reader: Box<dyn FormatReader>,
     decoder: Box<dyn SymphDecoder>,
@@ -30,12 +42,12 @@ pub struct SymphoniaDecoder {
     curr_decode_buffer_frame: usize,
 
     num_frames: usize,
-    sample_rate: Option<u32>,
     block_size: usize,
 
     playhead_frame: usize,
     reset_decode_buffer: bool,
-    seek_diff: usize,
+    seek_target_ts: Option<u64>,
+    track_id: u32,
 }
 
 impl Decoder for SymphoniaDecoder {
@@ -43,7 +55,7 @@ impl Decoder for SymphoniaDecoder {
     type FileParams = SymphoniaDecoderInfo;
     type OpenError = OpenError;
     type FatalError = Error;
-    type AdditionalOpts = ();
+    type AdditionalOpts = SymphoniaDecoderOpts;
 
     const DEFAULT_BLOCK_SIZE: usize = 16384;
     const DEFAULT_NUM_CACHE_BLOCKS: usize = 0;
@@ -53,7 +65,7 @@ impl Decoder for SymphoniaDecoder {
         file: PathBuf,
         start_frame: usize,
         block_size: usize,
-        _additional_opts: Self::AdditionalOpts,
+        additional_opts: Self::AdditionalOpts,
     ) -> Result<(Self, FileInfo<Self::FileParams>), Self::OpenError> {
         // Create a hint to help the format registry guess what format reader is appropriate.
         let mut hint = Hint::new();
@@ -65,7 +77,13 @@ impl Decoder for SymphoniaDecoder {
             }
         }
 
-        let source = Box::new(File::open(file)?);
+        let source: Box<dyn SymphMediaSource> = match &additional_opts.source_provider {
+            Some(provider) => Box::new(SourceAdapter::new(provider.open(&file)?)?),
+            None => Box::new(SourceAdapter::new(Box::new(StreamFile::open(
+                &file,
+                additional_opts.file_io,
+            )?))?),
+        };
 
         // Create the media source stream using the boxed media source from above.
         let mss = MediaSourceStream::new(source, Default::default());
@@ -83,26 +101,15 @@ impl Decoder for SymphoniaDecoder {
             ..Default::default()
         };
 
-        let params = {
+        let (params, track_id) = {
             // Get the default stream.
             let stream = reader.default_track().ok_or(OpenError::NoDefaultTrack)?;
 
-            stream.codec_params.clone()
+            (stream.codec_params.clone(), stream.id)
         };
         let num_frames = params.n_frames.ok_or(OpenError::NoNumFrames)? as usize;
         let sample_rate = params.sample_rate;
 
-        // Seek the reader to the requested position.
-        if start_frame != 0 {
-            reader.seek(
-                SeekMode::Accurate,
-                SeekTo::Time {
-                    time: frame_to_symphonia_time(start_frame as u64, sample_rate.unwrap_or(44100)),
-                    track_id: None,
-                },
-            )?;
-        }
-
         // Create a decoder for the stream.
         let mut decoder = symphonia::default::get_codecs().make(&params, &decoder_opts)?;
         debug_assert_eq!(params.n_frames, decoder.codec_params().n_frames);
@@ -166,25 +173,29 @@ impl Decoder for SymphoniaDecoder {
             num_channels: num_channels as u16,
             sample_rate,
         };
-        Ok((
-            Self {
-                reader,
-                decoder,
-
-                decode_buffer,
-                decode_buffer_len,
-                curr_decode_buffer_frame: 0,
-
-                num_frames,
-                sample_rate,
-                block_size,
-
-                playhead_frame: start_frame,
-                reset_decode_buffer: false,
-                seek_diff: 0,
-            },
-            file_info,
-        ))
+        let mut decoder = Self {
+            reader,
+            decoder,
+
+            decode_buffer,
+            decode_buffer_len,
+            curr_decode_buffer_frame: 0,
+
+            num_frames,
+            block_size,
+
+            playhead_frame: 0,
+            reset_decode_buffer: false,
+            seek_target_ts: None,
+            track_id,
+        };
+
+        // Seek the reader to the requested position.
+        if start_frame != 0 {
+            decoder.seek(start_frame)?;
+        }
+
+        Ok((decoder, file_info))
     }
 
     fn seek(&mut self, frame: usize) -> Result<(), Self::FatalError> {
@@ -197,54 +208,52 @@ impl Decoder for SymphoniaDecoder {
 
         self.playhead_frame = frame;
 
-        match self.reader.seek(
+        // Some codecs (i.e. MP3 and Vorbis) do not produce correct output for the first
+        // few packets after a seek, so seek to an earlier position and discard the extra
+        // frames in `decode()`.
+        //
+        // The timebase of an audio track is the sample rate, so a timestamp is an
+        // exact frame.
+        self.reader.seek(
             SeekMode::Accurate,
-            SeekTo::Time {
-                time: frame_to_symphonia_time(
-                    self.playhead_frame as u64,
-                    self.sample_rate.unwrap_or(44100),
-                ),
-                track_id: None,
+            SeekTo::TimeStamp {
+                ts: (frame as u64).saturating_sub(SEEK_PREROLL_FRAMES),
+                track_id: self.track_id,
             },
-        ) {
-            Ok(res) => {
-                // this is always correct for `SeekMode::Accurate`, it may not be for `SeekMode::Coarse`
-                debug_assert!(res.required_ts >= res.actual_ts);
+        )?;
 
-                self.seek_diff = (res.required_ts - res.actual_ts) as usize;
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        self.seek_target_ts = Some(frame as u64);
+
+        // The decoder must be reset after every seek so that no state from the
+        // previous position leaks into the new one.
+        self.decoder.reset();
 
         self.reset_decode_buffer = true;
         self.curr_decode_buffer_frame = 0;
 
-        /*
-        let decoder_opts = DecoderOptions {
-            verify: false,
-            ..Default::default()
-        };
-
-        self.decoder.close();
-        self.decoder = symphonia::default::get_codecs()
-            .make(self.decoder.codec_params(), &decoder_opts)?;
-            */
-
         Ok(())
     }
 
     fn decode(&mut self, data_block: &mut DataBlock<Self::T>) -> Result<(), Self::FatalError> {
+        decode_into_block(self, data_block).map(|_| ())
+    }
+
+    fn decode_into(
+        &mut self,
+        channels: &mut [&mut [Self::T]],
+    ) -> Result<DecodeResult, Self::FatalError> {
         if self.playhead_frame >= self.num_frames {
             // Do nothing if reached the end of the file.
-            return Ok(());
+            return Ok(DecodeResult {
+                frames: 0,
+                reached_end_of_file: true,
+            });
         }
 
         let mut reached_end_of_file = false;
 
         let mut block_start_frame = 0;
-        while block_start_frame < self.block_size {
+        while block_start_frame < self.block_size && !reached_end_of_file {
             let num_frames_to_cpy = if self.reset_decode_buffer {
                 // Get new data first.
                 self.reset_decode_buffer = false;
@@ -259,10 +268,11 @@ impl Decoder for SymphoniaDecoder {
                 let src_planes = self.decode_buffer.planes();
                 let src_channels = src_planes.planes();
 
-                for (dst_ch, src_ch) in data_block.block.iter_mut().zip(src_channels) {
+                for (dst_ch, src_ch) in channels.iter_mut().zip(src_channels) {
                     let src_ch_part = &src_ch[self.curr_decode_buffer_frame
                         ..self.curr_decode_buffer_frame + num_frames_to_cpy];
-                    dst_ch.extend_from_slice(src_ch_part);
+                    dst_ch[block_start_frame..block_start_frame + num_frames_to_cpy]
+                        .copy_from_slice(src_ch_part);
                 }
 
                 block_start_frame += num_frames_to_cpy;
@@ -277,21 +287,33 @@ impl Decoder for SymphoniaDecoder {
                 loop {
                     match self.reader.next_packet() {
                         Ok(packet) => {
+                            if packet.track_id() != self.track_id {
+                                continue;
+                            }
+
                             match self.decoder.decode(&packet) {
                                 Ok(decoded) => {
                                     self.decode_buffer_len = decoded.frames();
-                                    if self.seek_diff < self.decode_buffer_len {
+
+                                    // Skip any frames that lie before the frame that was
+                                    // seeked to.
+                                    let skip_frames = match self.seek_target_ts {
+                                        Some(target_ts) => {
+                                            target_ts.saturating_sub(packet.ts()) as usize
+                                        }
+                                        None => 0,
+                                    };
+
+                                    if skip_frames < self.decode_buffer_len {
                                         let capacity = decoded.capacity();
                                         if self.decode_buffer.capacity() < capacity {
                                             self.decode_buffer =
                                                 AudioBuffer::new(capacity as u64, *decoded.spec());
                                         }
                                         decoded.convert(&mut self.decode_buffer);
-                                        self.curr_decode_buffer_frame = self.seek_diff;
-                                        self.seek_diff = 0;
+                                        self.curr_decode_buffer_frame = skip_frames;
+                                        self.seek_target_ts = None;
                                         break;
-                                    } else {
-                                        self.seek_diff -= self.decode_buffer_len;
                                     }
                                 }
                                 Err(Error::DecodeError(err)) => {
@@ -311,7 +333,6 @@ impl Decoder for SymphoniaDecoder {
                                 if io_error.kind() == std::io::ErrorKind::UnexpectedEof {
                                     // End of file, stop decoding.
                                     reached_end_of_file = true;
-                                    block_start_frame = self.block_size;
                                     break;
                                 } else {
                                     return Err(e);
@@ -331,7 +352,10 @@ impl Decoder for SymphoniaDecoder {
             self.playhead_frame += self.block_size;
         }
 
-        Ok(())
+        Ok(DecodeResult {
+            frames: block_start_frame,
+            reached_end_of_file: self.playhead_frame >= self.num_frames,
+        })
     }
 
     fn current_frame(&self) -> usize {
@@ -360,117 +384,34 @@ impl SymphoniaDecoder {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct SymphoniaDecoderInfo {
-    pub codec_params: CodecParameters,
-    pub metadata: Option<MetadataRevision>,
-}
-
-fn frame_to_symphonia_time(frame: u64, sample_rate: u32) -> symphonia::core::units::Time {
-    // Doing it this way is more accurate for large inputs than just using f64s.
-    let seconds = frame / u64::from(sample_rate);
-    let fract_frames = frame % u64::from(sample_rate);
-    let frac = fract_frames as f64 / f64::from(sample_rate);
+/// Additional options for opening a file with a [`SymphoniaDecoder`].
+#[derive(Clone, Default)]
+pub struct SymphoniaDecoderOpts {
+    /// Where to read the file from. The path given to the stream is passed to the
+    /// provider, and its extension is used as a hint for the format of the file.
+    ///
+    /// Set this to `None` to open the file from the file system.
+    ///
+    /// The default is `None`.
+    pub source_provider: Option<Arc<dyn SourceProvider>>,
 
-    // TODO: Ask the maintainer of Symphonia to add an option to seek to an
-    // exact frame.
-    symphonia::core::units::Time { seconds, frac }
+    /// How the file is read from disk. See [`FileIoOptions`].
+    ///
+    /// This is ignored when a `source_provider` is used.
+    pub file_io: FileIoOptions,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use float_cmp::*;
-
-    #[test]
-    fn decoder_new() {
-        let files = vec![
-            //  file | num_channels | num_frames | sample_rate
-            ("../test_files/wav_u8_mono.wav", 1, 1323000, Some(44100)),
-            ("../test_files/wav_i16_mono.wav", 1, 1323000, Some(44100)),
-            ("../test_files/wav_i24_mono.wav", 1, 1323000, Some(44100)),
-            ("../test_files/wav_i32_mono.wav", 1, 1323000, Some(44100)),
-            ("../test_files/wav_f32_mono.wav", 1, 1323000, Some(44100)),
-            ("../test_files/wav_i24_stereo.wav", 2, 1323000, Some(44100)),
-            //"../test_files/ogg_mono.ogg",
-            //"../test_files/ogg_stereo.ogg",
-            //"../test_files/mp3_constant_mono.mp3",
-            //"../test_files/mp3_constant_stereo.mp3",
-            //"../test_files/mp3_variable_mono.mp3",
-            //"../test_files/mp3_variable_stereo.mp3",
-        ];
-
-        for file in files {
-            dbg!(file.0);
-            let decoder =
-                SymphoniaDecoder::new(file.0.into(), 0, SymphoniaDecoder::DEFAULT_BLOCK_SIZE, ());
-            match decoder {
-                Ok((_, file_info)) => {
-                    assert_eq!(file_info.num_channels, file.1);
-                    assert_eq!(file_info.num_frames, file.2);
-                    //assert_eq!(file_info.sample_rate, file.3);
-                }
-                Err(e) => {
-                    panic!("{}", e);
-                }
-            }
-        }
+impl std::fmt::Debug for SymphoniaDecoderOpts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SymphoniaDecoderOpts")
+            .field("source_provider", &self.source_provider.is_some())
+            .field("file_io", &self.file_io)
+            .finish()
     }
+}
 
-    #[test]
-    fn decode_first_frame() {
-        let block_size = 10;
-
-        let decoder =
-            SymphoniaDecoder::new("../test_files/wav_u8_mono.wav".into(), 0, block_size, ());
-
-        let (mut decoder, file_info) = decoder.unwrap();
-
-        let mut data_block = DataBlock::new(1, block_size);
-        data_block.clear();
-        decoder.decode(&mut data_block).unwrap();
-
-        let samples = &mut data_block.block[0];
-        assert_eq!(samples.len(), block_size);
-
-        let first_frame = [
-            0.0, 0.046875, 0.09375, 0.1484375, 0.1953125, 0.2421875, 0.2890625, 0.3359375,
-            0.3828125, 0.421875,
-        ];
-
-        for i in 0..samples.len() {
-            assert!(approx_eq!(f32, first_frame[i], samples[i], ulps = 2));
-        }
-
-        let second_frame = [
-            0.46875, 0.5078125, 0.5390625, 0.578125, 0.609375, 0.640625, 0.671875, 0.6953125,
-            0.71875, 0.7421875,
-        ];
-
-        data_block.clear();
-        decoder.decode(&mut data_block).unwrap();
-
-        let samples = &mut data_block.block[0];
-        for i in 0..samples.len() {
-            assert_approx_eq!(f32, second_frame[i], samples[i], ulps = 2);
-        }
-
-        let last_frame = [
-            -0.0859375, -0.09375, -0.1015625, -0.1015625, -0.1015625, -0.09375, -0.0859375,
-            -0.078125, -0.0625, -0.046875,
-        ];
-
-        // Seek to last frame
-        decoder.seek(file_info.num_frames - 1 - block_size).unwrap();
-
-        data_block.clear();
-        decoder.decode(&mut data_block).unwrap();
-
-        let samples = &mut data_block.block[0];
-        for i in 0..samples.len() {
-            assert_approx_eq!(f32, last_frame[i], samples[i], ulps = 2);
-        }
-
-        assert_eq!(decoder.playhead_frame, file_info.num_frames - 1);
-    }
+#[derive(Debug, Clone)]
+pub struct SymphoniaDecoderInfo {
+    pub codec_params: CodecParameters,
+    pub metadata: Option<MetadataRevision>,
 }
@@ -0,0 +1,52 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Mutex;
+
+use creek_core::MediaSource;
+use symphonia::core::io::MediaSource as SymphMediaSource;
+
+/// Wraps a [`MediaSource`] so that it can be read by Symphonia.
+pub(crate) struct SourceAdapter {
+    // Symphonia requires sources to be `Sync`. The mutex is never locked since the
+    // source is only ever accessed through `&mut self`.
+    source: Mutex<Box<dyn MediaSource>>,
+    byte_len: u64,
+}
+
+impl SourceAdapter {
+    pub(crate) fn new(mut source: Box<dyn MediaSource>) -> io::Result<Self> {
+        let pos = source.stream_position()?;
+        let byte_len = source.seek(SeekFrom::End(0))?;
+        source.seek(SeekFrom::Start(pos))?;
+
+        Ok(Self {
+            source: Mutex::new(source),
+            byte_len,
+        })
+    }
+
+    fn source(&mut self) -> &mut Box<dyn MediaSource> {
+        self.source.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Read for SourceAdapter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.source().read(buf)
+    }
+}
+
+impl Seek for SourceAdapter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.source().seek(pos)
+    }
+}
+
+impl SymphMediaSource for SourceAdapter {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.byte_len)
+    }
+}
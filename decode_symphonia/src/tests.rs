@@ -0,0 +1,215 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use creek_core::{testing, DataBlock, FileIoOptions, MediaSource, PageCacheAdvice};
+use float_cmp::*;
+
+use super::*;
+
+/// Returns every file in `test_files/` that this build of the decoder is able to open.
+fn conformance_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir("../test_files")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let extension = path.extension().and_then(|e| e.to_str());
+            matches!(extension, Some("wav") | Some("ogg"))
+                || (cfg!(feature = "mp3") && extension == Some("mp3"))
+        })
+        .collect();
+
+    files.sort();
+    files
+}
+
+#[test]
+fn decoder_new() {
+    #[rustfmt::skip]
+    let files = vec![
+        //  file | num_channels | num_frames | sample_rate
+        ("../test_files/wav_u8_mono.wav", 1, 1323000, Some(44100)),
+        ("../test_files/wav_i16_mono.wav", 1, 1323000, Some(44100)),
+        ("../test_files/wav_i24_mono.wav", 1, 1323000, Some(44100)),
+        ("../test_files/wav_i32_mono.wav", 1, 1323000, Some(44100)),
+        ("../test_files/wav_f32_mono.wav", 1, 1323000, Some(44100)),
+        ("../test_files/wav_i24_stereo.wav", 2, 1323000, Some(44100)),
+        ("../test_files/ogg_mono.ogg", 1, 1323712, Some(44100)),
+        ("../test_files/ogg_stereo.ogg", 2, 1323712, Some(44100)),
+        #[cfg(feature = "mp3")]
+        ("../test_files/mp3_constant_mono.mp3", 1, 1324800, Some(44100)),
+        #[cfg(feature = "mp3")]
+        ("../test_files/mp3_constant_stereo.mp3", 2, 1324800, Some(44100)),
+        #[cfg(feature = "mp3")]
+        ("../test_files/mp3_variable_mono.mp3", 1, 1324800, Some(44100)),
+        #[cfg(feature = "mp3")]
+        ("../test_files/mp3_variable_stereo.mp3", 2, 1324800, Some(44100)),
+    ];
+
+    for file in files {
+        let decoder = SymphoniaDecoder::new(
+            file.0.into(),
+            0,
+            SymphoniaDecoder::DEFAULT_BLOCK_SIZE,
+            Default::default(),
+        );
+        match decoder {
+            Ok((_, file_info)) => {
+                assert_eq!(file_info.num_channels, file.1);
+                assert_eq!(file_info.num_frames, file.2);
+                assert_eq!(file_info.sample_rate, file.3);
+            }
+            Err(e) => {
+                panic!("{}", e);
+            }
+        }
+    }
+}
+
+#[test]
+fn decode_first_frame() {
+    let block_size = 10;
+
+    let decoder = SymphoniaDecoder::new(
+        "../test_files/wav_u8_mono.wav".into(),
+        0,
+        block_size,
+        Default::default(),
+    );
+
+    let (mut decoder, file_info) = decoder.unwrap();
+
+    let mut data_block = DataBlock::new(1, block_size);
+    data_block.clear();
+    decoder.decode(&mut data_block).unwrap();
+
+    let samples = data_block.channel(0);
+    assert_eq!(samples.len(), block_size);
+
+    let first_frame = [
+        0.0, 0.046875, 0.09375, 0.1484375, 0.1953125, 0.2421875, 0.2890625, 0.3359375, 0.3828125,
+        0.421875,
+    ];
+
+    for i in 0..samples.len() {
+        assert!(approx_eq!(f32, first_frame[i], samples[i], ulps = 2));
+    }
+
+    let second_frame = [
+        0.46875, 0.5078125, 0.5390625, 0.578125, 0.609375, 0.640625, 0.671875, 0.6953125, 0.71875,
+        0.7421875,
+    ];
+
+    data_block.clear();
+    decoder.decode(&mut data_block).unwrap();
+
+    let samples = data_block.channel(0);
+    for i in 0..samples.len() {
+        assert_approx_eq!(f32, second_frame[i], samples[i], ulps = 2);
+    }
+
+    let last_frame = [
+        -0.0859375, -0.09375, -0.1015625, -0.1015625, -0.1015625, -0.09375, -0.0859375, -0.078125,
+        -0.0625, -0.046875,
+    ];
+
+    // Seek to last frame
+    decoder.seek(file_info.num_frames - 1 - block_size).unwrap();
+
+    data_block.clear();
+    decoder.decode(&mut data_block).unwrap();
+
+    let samples = data_block.channel(0);
+    for i in 0..samples.len() {
+        assert_approx_eq!(f32, last_frame[i], samples[i], ulps = 2);
+    }
+
+    assert_eq!(decoder.playhead_frame, file_info.num_frames - 1);
+}
+
+#[test]
+fn conformance() {
+    let block_size = 4096;
+
+    for file in conformance_files() {
+        let (_, linear) = testing::check_decoder::<SymphoniaDecoder>(&file, block_size);
+        testing::check_read_stream::<SymphoniaDecoder>(&file, block_size, &linear);
+    }
+}
+
+#[test]
+fn source_provider() {
+    let block_size = 4096;
+
+    // Serve the files from memory under a path that does not exist on disk.
+    let provider = |file: &Path| -> std::io::Result<Box<dyn MediaSource>> {
+        let file_name = file.file_name().unwrap();
+        let bytes = std::fs::read(Path::new("../test_files").join(file_name))?;
+        Ok(Box::new(Cursor::new(bytes)))
+    };
+    let opts = SymphoniaDecoderOpts {
+        source_provider: Some(Arc::new(provider)),
+        ..Default::default()
+    };
+
+    for file_name in ["wav_i24_mono.wav", "ogg_mono.ogg"] {
+        let file = Path::new("virtual").join(file_name);
+
+        let (_, expected) = testing::decode_linear::<SymphoniaDecoder>(
+            &Path::new("../test_files").join(file_name),
+            block_size,
+        );
+
+        let (_, linear) =
+            testing::check_decoder_with_opts::<SymphoniaDecoder>(&file, block_size, || {
+                opts.clone()
+            });
+        assert!(linear == expected);
+        testing::check_read_stream_with_opts::<SymphoniaDecoder>(
+            &file,
+            block_size,
+            &linear,
+            opts.clone(),
+        );
+    }
+
+    // Errors from the provider are returned when opening the file.
+    let res = SymphoniaDecoder::new("virtual/missing.wav".into(), 0, block_size, opts);
+    assert!(matches!(res, Err(OpenError::Io(_))));
+}
+
+#[test]
+fn file_io_options() {
+    let block_size = 4096;
+
+    let advice = FileIoOptions {
+        advice: Some(PageCacheAdvice {
+            will_need_bytes: 64 * 1024,
+            keep_behind_bytes: Some(64 * 1024),
+        }),
+        direct_io: false,
+    };
+    let direct_io = FileIoOptions {
+        advice: None,
+        direct_io: true,
+    };
+
+    for file_name in ["wav_i24_mono.wav", "ogg_stereo.ogg"] {
+        let file = Path::new("../test_files").join(file_name);
+
+        let (_, expected) = testing::decode_linear::<SymphoniaDecoder>(&file, block_size);
+
+        for file_io in [advice, direct_io] {
+            let opts = SymphoniaDecoderOpts {
+                file_io,
+                ..Default::default()
+            };
+
+            let (_, linear) =
+                testing::check_decoder_with_opts::<SymphoniaDecoder>(&file, block_size, || {
+                    opts.clone()
+                });
+            assert!(linear == expected);
+        }
+    }
+}
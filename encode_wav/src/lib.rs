@@ -5,13 +5,12 @@
 #![deny(trivial_numeric_casts)]
 #![forbid(unsafe_code)]
 
+use std::io::{Seek, SeekFrom, Write};
 use std::path::PathBuf;
-use std::{
-    fs::{File, OpenOptions},
-    io::{Seek, SeekFrom, Write},
-};
 
-use creek_core::{write, Encoder, FileInfo, WriteBlock, WriteStatus};
+use creek_core::{
+    write, BlockLayout, Encoder, FileInfo, FileIoOptions, StreamFile, WriteBlock, WriteStatus,
+};
 
 pub mod error;
 mod header;
@@ -77,29 +76,36 @@ pub struct Params {
     _format: Format,
 }
 
+/// Additional options for creating a file with a [`WavEncoder`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WavEncoderOpts {
+    /// How the file is written to disk. See [`FileIoOptions`].
+    pub file_io: FileIoOptions,
+}
+
 pub struct WavEncoder<B: WavBitDepth + 'static> {
-    interleave_buf: Vec<B::T>,
-    file: Option<File>,
+    file: Option<StreamFile>,
     header: Header,
     path: PathBuf,
+    file_io: FileIoOptions,
     bytes_per_frame: u64,
     frames_written: u32,
     max_file_bytes: u64,
     max_block_bytes: u64,
-    num_channels: usize,
     num_files: u32,
     bit_depth: B,
 }
 
 impl<B: WavBitDepth + 'static> Encoder for WavEncoder<B> {
     type T = B::T;
-    type AdditionalOpts = ();
+    type AdditionalOpts = WavEncoderOpts;
     type FileParams = Params;
     type OpenError = WavOpenError;
     type FatalError = WavFatalError;
 
     const DEFAULT_BLOCK_SIZE: usize = 32768;
     const DEFAULT_NUM_WRITE_BLOCKS: usize = 8;
+    const BLOCK_LAYOUT: BlockLayout = BlockLayout::Interleaved;
 
     fn new(
         path: PathBuf,
@@ -107,13 +113,10 @@ impl<B: WavBitDepth + 'static> Encoder for WavEncoder<B> {
         sample_rate: u32,
         block_size: usize,
         _num_write_blocks: usize,
-        _additional_opts: Self::AdditionalOpts,
+        additional_opts: Self::AdditionalOpts,
     ) -> Result<(Self, FileInfo<Self::FileParams>), Self::OpenError> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path.clone())?;
+        let file_io = additional_opts.file_io;
+        let mut file = StreamFile::create(&path, file_io)?;
 
         let format = B::format();
         let header = Header::new(num_channels, sample_rate, format);
@@ -121,22 +124,19 @@ impl<B: WavBitDepth + 'static> Encoder for WavEncoder<B> {
         file.write_all(header.buffer())?;
         file.flush()?;
 
-        let interleave_buf: Vec<B::T> = Vec::with_capacity(block_size * usize::from(num_channels));
-
         let max_file_bytes = u64::from(header.max_data_bytes());
         let bytes_per_frame = u64::from(num_channels) * u64::from(format.bytes_per_sample());
 
         Ok((
             Self {
-                interleave_buf,
                 file: Some(file),
                 header,
                 path,
+                file_io,
                 frames_written: 0,
                 bytes_per_frame,
                 max_file_bytes,
                 max_block_bytes: block_size as u64 * bytes_per_frame,
-                num_channels: usize::from(num_channels),
                 num_files: 1,
                 bit_depth: B::new(block_size, num_channels),
             },
@@ -161,53 +161,9 @@ impl<B: WavBitDepth + 'static> Encoder for WavEncoder<B> {
         }
 
         if let Some(mut file) = self.file.take() {
-            if self.num_channels == 1 {
-                self.bit_depth
-                    .write_to_disk(&write_block.block()[0][0..written_frames], &mut file)?;
-            } else {
-                if self.num_channels == 2 {
-                    // Provide efficient stereo interleaving.
-                    let ch1 = &write_block.block()[0][0..written_frames];
-                    let ch2 = &write_block.block()[1][0..written_frames];
-
-                    if self.interleave_buf.len() < written_frames * 2 {
-                        self.interleave_buf
-                            .resize(written_frames * 2, Default::default());
-                    }
-
-                    let interleave_buf_part = &mut self.interleave_buf[0..written_frames * 2];
-
-                    for (i, frame) in interleave_buf_part.chunks_exact_mut(2).enumerate() {
-                        frame[0] = ch1[i];
-                        frame[1] = ch2[i];
-                    }
-                } else {
-                    if self.interleave_buf.len() < written_frames * self.num_channels {
-                        self.interleave_buf
-                            .resize(written_frames * self.num_channels, Default::default());
-                    }
-
-                    let interleave_buf_part =
-                        &mut self.interleave_buf[0..written_frames * self.num_channels];
-
-                    for (ch_i, ch) in write_block.block().iter().enumerate() {
-                        let ch_slice = &ch[0..written_frames];
-
-                        for (dst, src) in interleave_buf_part[ch_i..]
-                            .iter_mut()
-                            .step_by(self.num_channels)
-                            .zip(ch_slice)
-                        {
-                            *dst = *src;
-                        }
-                    }
-                }
-
-                self.bit_depth.write_to_disk(
-                    &self.interleave_buf[0..written_frames * self.num_channels],
-                    &mut file,
-                )?;
-            }
+            // The write stream interleaves the samples as they are written.
+            self.bit_depth
+                .write_to_disk(write_block.interleaved(), &mut file)?;
 
             self.frames_written += written_frames as u32;
             let bytes_written = u64::from(self.frames_written) * self.bytes_per_frame;
@@ -239,11 +195,7 @@ impl<B: WavBitDepth + 'static> Encoder for WavEncoder<B> {
                 new_file_path.set_file_name(file_name);
 
                 // Create new file.
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .truncate(true)
-                    .create(true)
-                    .open(new_file_path)?;
+                let mut file = StreamFile::create(new_file_path, self.file_io)?;
 
                 self.frames_written = 0;
                 self.header.set_num_frames(0);
@@ -333,11 +285,7 @@ impl<B: WavBitDepth + 'static> Encoder for WavEncoder<B> {
                 }
 
                 // Re-create the original file and start over.
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .truncate(true)
-                    .create(true)
-                    .open(self.path.clone())?;
+                let mut file = StreamFile::create(&self.path, self.file_io)?;
                 file.seek(SeekFrom::Start(0))?;
                 file.write_all(self.header.buffer())?;
                 file.flush()?;
@@ -65,7 +65,7 @@ fn encode_wav() {
     block.block[0].copy_from_slice(&sine_u8_l);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Uint8>::new(path, 1, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Uint8>::new(path, 1, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -82,7 +82,7 @@ fn encode_wav() {
     block.block[1].copy_from_slice(&sine_u8_r);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Uint8>::new(path, 2, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Uint8>::new(path, 2, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -98,7 +98,7 @@ fn encode_wav() {
     block.block[0].copy_from_slice(&sine_i16_l);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Int16>::new(path, 1, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Int16>::new(path, 1, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -115,7 +115,7 @@ fn encode_wav() {
     block.block[1].copy_from_slice(&sine_i16_r);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Int16>::new(path, 2, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Int16>::new(path, 2, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -131,7 +131,7 @@ fn encode_wav() {
     block.block[0].copy_from_slice(&sine_i24_l);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Int24>::new(path, 1, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Int24>::new(path, 1, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -148,7 +148,7 @@ fn encode_wav() {
     block.block[1].copy_from_slice(&sine_i24_r);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Int24>::new(path, 2, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Int24>::new(path, 2, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -164,7 +164,7 @@ fn encode_wav() {
     block.block[0].copy_from_slice(&sine_f32_l);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Float32>::new(path, 1, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Float32>::new(path, 1, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -181,7 +181,7 @@ fn encode_wav() {
     block.block[1].copy_from_slice(&sine_f32_r);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Float32>::new(path, 2, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Float32>::new(path, 2, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -197,7 +197,7 @@ fn encode_wav() {
     block.block[0].copy_from_slice(&sine_f64);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Float64>::new(path, 1, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Float64>::new(path, 1, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -214,7 +214,7 @@ fn encode_wav() {
     block.block[1].copy_from_slice(&sine_f64_r);
     block.written_frames = 32768;
 
-    let (mut encoder, _info) = WavEncoder::<Float64>::new(path, 2, 44100, 32768, 8, ()).unwrap();
+    let (mut encoder, _info) = WavEncoder::<Float64>::new(path, 2, 44100, 32768, 8, Default::default()).unwrap();
 
     unsafe {
         encoder.encode(&block).unwrap();
@@ -223,3 +223,122 @@ fn encode_wav() {
     encoder.finish_file().unwrap();
 }
 */
+
+use std::path::PathBuf;
+
+use creek_core::{testing, FileIoOptions, PageCacheAdvice};
+use creek_decode_symphonia::SymphoniaDecoder;
+
+use crate::wav_bit_depth::{Float32, Float64, Int16, Int24, Uint8};
+use crate::{WavEncoder, WavEncoderOpts};
+
+/// Returns a unique path in the temporary directory for an output file.
+fn out_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "creek_encode_wav_{}_{}.wav",
+        std::process::id(),
+        name
+    ))
+}
+
+/// Generate a different sine wave for each channel.
+fn sine_frames(num_channels: usize, num_frames: usize) -> Vec<Vec<f64>> {
+    (0..num_channels)
+        .map(|ch| {
+            let freq = 261.626 * (ch + 1) as f64;
+            (0..num_frames)
+                .map(|n| 0.9 * (std::f64::consts::TAU * n as f64 * freq / 44100.0).sin())
+                .collect()
+        })
+        .collect()
+}
+
+fn convert<T>(frames: &[Vec<f64>], f: impl Fn(f64) -> T) -> Vec<Vec<T>> {
+    frames
+        .iter()
+        .map(|ch| ch.iter().copied().map(&f).collect())
+        .collect()
+}
+
+#[test]
+fn encoder_conformance() {
+    let block_size = 1024;
+    let num_frames = (block_size * 4) + 17;
+
+    for num_channels in [1, 2, 3] {
+        let frames = sine_frames(num_channels, num_frames);
+
+        // Float32 files are checked against the decoder, since the samples can be
+        // compared exactly.
+        let frames_f32 = convert(&frames, |s| s as f32);
+
+        let file = out_file(&format!("f32_{}ch", num_channels));
+        testing::check_encoder::<WavEncoder<Float32>>(&file, 44100, block_size, &frames_f32);
+        testing::check_file_frames::<SymphoniaDecoder>(&file, block_size, &frames_f32);
+        std::fs::remove_file(&file).unwrap();
+
+        let file = out_file(&format!("f32_{}ch_stream", num_channels));
+        testing::check_write_stream::<WavEncoder<Float32>>(&file, 44100, block_size, &frames_f32);
+        testing::check_file_frames::<SymphoniaDecoder>(&file, block_size, &frames_f32);
+        std::fs::remove_file(&file).unwrap();
+
+        let file = out_file(&format!("u8_{}ch", num_channels));
+        let frames_u8 = convert(&frames, |s| (((s + 1.0) / 2.0) * f64::from(u8::MAX)) as u8);
+        testing::check_encoder::<WavEncoder<Uint8>>(&file, 44100, block_size, &frames_u8);
+        std::fs::remove_file(&file).unwrap();
+
+        let file = out_file(&format!("i16_{}ch", num_channels));
+        let frames_i16 = convert(&frames, |s| (s * f64::from(i16::MAX)) as i16);
+        testing::check_encoder::<WavEncoder<Int16>>(&file, 44100, block_size, &frames_i16);
+        std::fs::remove_file(&file).unwrap();
+
+        let file = out_file(&format!("i24_{}ch", num_channels));
+        let frames_i24 = convert(&frames, |s| (s * f64::from(0x7FFFFF)) as i32);
+        testing::check_encoder::<WavEncoder<Int24>>(&file, 44100, block_size, &frames_i24);
+        std::fs::remove_file(&file).unwrap();
+
+        let file = out_file(&format!("f64_{}ch", num_channels));
+        testing::check_encoder::<WavEncoder<Float64>>(&file, 44100, block_size, &frames);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
+
+#[test]
+fn file_io_options() {
+    let block_size = 1000;
+    let frames = convert(&sine_frames(2, (block_size * 300) + 17), |s| s as f32);
+
+    let advice = FileIoOptions {
+        advice: Some(PageCacheAdvice {
+            will_need_bytes: 0,
+            keep_behind_bytes: Some(64 * 1024),
+        }),
+        direct_io: false,
+    };
+    let direct_io = FileIoOptions {
+        advice: None,
+        direct_io: true,
+    };
+
+    for (name, file_io) in [("advice", advice), ("direct_io", direct_io)] {
+        let opts = WavEncoderOpts { file_io };
+
+        let file = out_file(&format!("f32_{}", name));
+        testing::check_encoder_with_opts::<WavEncoder<Float32>>(
+            &file,
+            44100,
+            block_size,
+            &frames,
+            || opts,
+        );
+        testing::check_file_frames::<SymphoniaDecoder>(&file, block_size, &frames);
+        std::fs::remove_file(&file).unwrap();
+
+        let file = out_file(&format!("f32_{}_stream", name));
+        testing::check_write_stream_with_opts::<WavEncoder<Float32>>(
+            &file, 44100, block_size, &frames, opts,
+        );
+        testing::check_file_frames::<SymphoniaDecoder>(&file, block_size, &frames);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
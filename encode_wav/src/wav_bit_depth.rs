@@ -1,6 +1,6 @@
 use crate::Format;
 use byte_slice_cast::AsByteSlice;
-use std::fs::File;
+use creek_core::StreamFile;
 use std::io::Write;
 
 pub trait WavBitDepth {
@@ -10,7 +10,11 @@ pub trait WavBitDepth {
 
     fn format() -> Format;
 
-    fn write_to_disk(&mut self, data: &[Self::T], file: &mut File) -> Result<(), std::io::Error>;
+    fn write_to_disk(
+        &mut self,
+        data: &[Self::T],
+        file: &mut StreamFile,
+    ) -> Result<(), std::io::Error>;
 }
 
 pub struct Uint8 {}
@@ -26,7 +30,7 @@ impl WavBitDepth for Uint8 {
         Format::Uint8
     }
 
-    fn write_to_disk(&mut self, data: &[u8], file: &mut File) -> Result<(), std::io::Error> {
+    fn write_to_disk(&mut self, data: &[u8], file: &mut StreamFile) -> Result<(), std::io::Error> {
         file.write_all(data)
     }
 }
@@ -44,7 +48,7 @@ impl WavBitDepth for Int16 {
         Format::Int16
     }
 
-    fn write_to_disk(&mut self, data: &[i16], file: &mut File) -> Result<(), std::io::Error> {
+    fn write_to_disk(&mut self, data: &[i16], file: &mut StreamFile) -> Result<(), std::io::Error> {
         file.write_all(data.as_byte_slice())
     }
 }
@@ -68,7 +72,7 @@ impl WavBitDepth for Int24 {
         Format::Int24
     }
 
-    fn write_to_disk(&mut self, data: &[i32], file: &mut File) -> Result<(), std::io::Error> {
+    fn write_to_disk(&mut self, data: &[i32], file: &mut StreamFile) -> Result<(), std::io::Error> {
         self.cram_buffer.clear();
         let num_frames = data.len();
 
@@ -101,7 +105,7 @@ impl WavBitDepth for Float32 {
         Format::Float32
     }
 
-    fn write_to_disk(&mut self, data: &[f32], file: &mut File) -> Result<(), std::io::Error> {
+    fn write_to_disk(&mut self, data: &[f32], file: &mut StreamFile) -> Result<(), std::io::Error> {
         file.write_all(data.as_byte_slice())
     }
 }
@@ -119,7 +123,7 @@ impl WavBitDepth for Float64 {
         Format::Float64
     }
 
-    fn write_to_disk(&mut self, data: &[f64], file: &mut File) -> Result<(), std::io::Error> {
+    fn write_to_disk(&mut self, data: &[f64], file: &mut StreamFile) -> Result<(), std::io::Error> {
         file.write_all(data.as_byte_slice())
     }
 }